@@ -0,0 +1,752 @@
+//! AI smart actions
+//!
+//! Each action is a prompt template sent together with the capture to a
+//! chat-completions endpoint: an Azure OpenAI deployment, or any server
+//! speaking the OpenAI API such as a local Ollama or llama.cpp. Responses
+//! are streamed (server-sent events) and handed to a callback as they arrive.
+//!
+//! Models that can't see images get the locally recognized text instead.
+//! HTTP goes through [`HttpTransport`] so tests can point the client at a
+//! local stub server. Whether AI may be used at all is decided by the
+//! command layer, not here.
+
+use crate::error::{GrabError, GrabResult};
+use crate::types::{AzureOpenAiSettings, OpenAiCompatibleSettings, SmartAction};
+use base64::Engine;
+use image::DynamicImage;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Event carrying streamed smart action output
+pub const STREAM_EVENT: &str = "ai:stream";
+
+/// Longest image edge sent to the model; larger captures are downscaled
+const MAX_IMAGE_EDGE: u32 = 2048;
+
+const DEFAULT_API_VERSION: &str = "2024-10-21";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What servers said about their models' vision, by base URL and model
+type VisionCache = Mutex<HashMap<(String, String), Option<bool>>>;
+static DETECTED_VISION: OnceLock<VisionCache> = OnceLock::new();
+
+const SYSTEM_PROMPT: &str = "You help people work with screenshots. \
+Reply with the requested content only, without any preamble.";
+
+/// An HTTP request; the body is ignored for GET
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Status and body of a response, streamed
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Box<dyn Read + Send>,
+}
+
+/// Sends HTTP requests
+///
+/// Error statuses are returned as responses; only failures to reach the
+/// server are errors.
+pub trait HttpTransport: Send + Sync {
+    fn get(&self, request: &HttpRequest) -> GrabResult<HttpResponse>;
+    fn post(&self, request: &HttpRequest) -> GrabResult<HttpResponse>;
+}
+
+/// Transport backed by `ureq`
+pub struct UreqTransport {
+    agent: ureq::Agent,
+    timeout: Duration,
+}
+
+impl UreqTransport {
+    /// `timeout` bounds the wait for each read, so long streams still work
+    pub fn new(timeout: Duration) -> Self {
+        UreqTransport {
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(CONNECT_TIMEOUT)
+                .timeout_read(timeout)
+                .build(),
+            timeout,
+        }
+    }
+
+    fn send(
+        &self,
+        request: &HttpRequest,
+        call: ureq::Request,
+        body: Option<&[u8]>,
+    ) -> GrabResult<HttpResponse> {
+        let call = request
+            .headers
+            .iter()
+            .fold(call, |call, (name, value)| call.set(name, value));
+
+        let result = match body {
+            Some(body) => call.send_bytes(body),
+            None => call.call(),
+        };
+        let response = match result {
+            Ok(response) => response,
+            // Error statuses still carry a body worth reading
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(ureq::Error::Transport(e)) => return Err(self.transport_error(&request.url, &e)),
+        };
+
+        Ok(HttpResponse {
+            status: response.status(),
+            body: Box::new(response.into_reader()),
+        })
+    }
+
+    fn transport_error(&self, url: &str, error: &ureq::Transport) -> GrabError {
+        match error.kind() {
+            ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed => {
+                GrabError::AiUnavailable(format!("could not connect to {}", url))
+            }
+            ureq::ErrorKind::Io if is_timeout(error) => GrabError::AiUnavailable(format!(
+                "no response from {} within {}s",
+                url,
+                self.timeout.as_secs()
+            )),
+            _ => GrabError::AiFailed(error.to_string()),
+        }
+    }
+}
+
+impl HttpTransport for UreqTransport {
+    fn get(&self, request: &HttpRequest) -> GrabResult<HttpResponse> {
+        self.send(request, self.agent.get(&request.url), None)
+    }
+
+    fn post(&self, request: &HttpRequest) -> GrabResult<HttpResponse> {
+        self.send(request, self.agent.post(&request.url), Some(&request.body))
+    }
+}
+
+fn is_timeout(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = error.source();
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<io::Error>() {
+            return matches!(
+                e.kind(),
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+            );
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// A server that answers chat completions
+pub trait ChatProvider: Send + Sync {
+    /// Short name for messages
+    fn name(&self) -> &str;
+
+    /// Request carrying a chat-completions body
+    fn request(&self, body: serde_json::Value) -> HttpRequest;
+
+    /// Whether the model accepts images
+    fn supports_vision(&self, transport: &dyn HttpTransport) -> GrabResult<bool>;
+}
+
+/// A resolved Azure OpenAI deployment
+#[derive(Debug, Clone)]
+pub struct AzureConfig {
+    pub endpoint: String,
+    pub api_key: String,
+    pub deployment: String,
+    pub api_version: String,
+}
+
+impl AzureConfig {
+    /// Combine preferences with the `AZURE_OPENAI_*` environment variables
+    pub fn resolve(settings: &AzureOpenAiSettings) -> GrabResult<Self> {
+        Self::resolve_with(settings, |name| std::env::var(name).ok())
+    }
+
+    fn resolve_with(
+        settings: &AzureOpenAiSettings,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> GrabResult<Self> {
+        let value = |setting: &Option<String>, name: &str| {
+            setting
+                .clone()
+                .or_else(|| env(name))
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let required = |setting: &Option<String>, name: &str| {
+            value(setting, name).ok_or_else(|| {
+                GrabError::InvalidRequest(format!(
+                    "Azure OpenAI is not configured: {} is missing",
+                    name
+                ))
+            })
+        };
+
+        Ok(AzureConfig {
+            endpoint: required(&settings.endpoint, "AZURE_OPENAI_ENDPOINT")?
+                .trim_end_matches('/')
+                .to_string(),
+            api_key: required(&settings.api_key, "AZURE_OPENAI_API_KEY")?,
+            deployment: required(&settings.deployment, "AZURE_OPENAI_DEPLOYMENT")?,
+            api_version: value(&settings.api_version, "AZURE_OPENAI_API_VERSION")
+                .unwrap_or_else(|| DEFAULT_API_VERSION.to_string()),
+        })
+    }
+}
+
+impl ChatProvider for AzureConfig {
+    fn name(&self) -> &str {
+        "Azure OpenAI"
+    }
+
+    fn request(&self, body: serde_json::Value) -> HttpRequest {
+        HttpRequest {
+            url: format!(
+                "{}/openai/deployments/{}/chat/completions?api-version={}",
+                self.endpoint, self.deployment, self.api_version
+            ),
+            headers: vec![
+                ("api-key".to_string(), self.api_key.clone()),
+                ("Content-Type".to_string(), "application/json".to_string()),
+            ],
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    /// Deployments are expected to be vision models
+    fn supports_vision(&self, _transport: &dyn HttpTransport) -> GrabResult<bool> {
+        Ok(true)
+    }
+}
+
+/// A resolved OpenAI-compatible server
+#[derive(Debug, Clone)]
+pub struct OpenAiCompatibleConfig {
+    pub base_url: String,
+    pub model: String,
+    pub api_key: Option<String>,
+    pub vision: Option<bool>,
+}
+
+impl OpenAiCompatibleConfig {
+    pub fn resolve(settings: &OpenAiCompatibleSettings) -> GrabResult<Self> {
+        let base_url = settings.base_url.trim().trim_end_matches('/');
+        if !(base_url.starts_with("http://") || base_url.starts_with("https://")) {
+            return Err(GrabError::InvalidRequest(format!(
+                "Invalid base URL for the OpenAI-compatible provider: {}",
+                settings.base_url
+            )));
+        }
+        let model = settings.model.trim();
+        if model.is_empty() {
+            return Err(GrabError::InvalidRequest(
+                "No model set for the OpenAI-compatible provider".to_string(),
+            ));
+        }
+
+        Ok(OpenAiCompatibleConfig {
+            base_url: base_url.to_string(),
+            model: model.to_string(),
+            api_key: settings
+                .api_key
+                .as_deref()
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(str::to_string),
+            vision: settings.vision,
+        })
+    }
+
+    fn headers(&self) -> Vec<(String, String)> {
+        let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+        if let Some(key) = &self.api_key {
+            headers.push(("Authorization".to_string(), format!("Bearer {}", key)));
+        }
+        headers
+    }
+
+    /// Server root, without the `/v1` API prefix
+    fn root(&self) -> &str {
+        self.base_url.strip_suffix("/v1").unwrap_or(&self.base_url)
+    }
+
+    /// Ask the server what the model can do
+    ///
+    /// Understands Ollama's `/api/show` capabilities and llama.cpp's
+    /// `/props` modalities; `None` when neither answers.
+    fn detect_vision(&self, transport: &dyn HttpTransport) -> GrabResult<Option<bool>> {
+        let show = HttpRequest {
+            url: format!("{}/api/show", self.root()),
+            headers: self.headers(),
+            body: serde_json::json!({ "model": self.model })
+                .to_string()
+                .into_bytes(),
+        };
+        if let Some(info) = json_response(transport.post(&show)?) {
+            if let Some(capabilities) = info["capabilities"].as_array() {
+                return Ok(Some(capabilities.iter().any(|c| c == "vision")));
+            }
+        }
+
+        let props = HttpRequest {
+            url: format!("{}/props", self.root()),
+            headers: self.headers(),
+            body: Vec::new(),
+        };
+        if let Some(info) = json_response(transport.get(&props)?) {
+            if let Some(vision) = info["modalities"]["vision"].as_bool() {
+                return Ok(Some(vision));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl ChatProvider for OpenAiCompatibleConfig {
+    fn name(&self) -> &str {
+        &self.model
+    }
+
+    fn request(&self, mut body: serde_json::Value) -> HttpRequest {
+        body["model"] = serde_json::Value::String(self.model.clone());
+        HttpRequest {
+            url: format!("{}/chat/completions", self.base_url),
+            headers: self.headers(),
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    /// Configured, or detected; servers that don't say are assumed to
+    /// accept images
+    ///
+    /// Detection is asked once per server and model while the app runs.
+    fn supports_vision(&self, transport: &dyn HttpTransport) -> GrabResult<bool> {
+        if let Some(vision) = self.vision {
+            return Ok(vision);
+        }
+        let cache = DETECTED_VISION.get_or_init(Default::default);
+        let key = (self.base_url.clone(), self.model.clone());
+        let cached = cache.lock().unwrap().get(&key).copied();
+        let detected = match cached {
+            Some(detected) => detected,
+            None => {
+                let detected = self.detect_vision(transport)?;
+                cache.lock().unwrap().insert(key, detected);
+                detected
+            }
+        };
+        Ok(detected.unwrap_or(true))
+    }
+}
+
+/// Body of a successful JSON response
+fn json_response(response: HttpResponse) -> Option<serde_json::Value> {
+    if !(200..300).contains(&response.status) {
+        return None;
+    }
+    serde_json::from_reader(response.body).ok()
+}
+
+/// What the model is shown of the capture
+#[derive(Debug, Clone)]
+pub enum ActionInput {
+    /// PNG bytes
+    Image(Vec<u8>),
+    /// Text recognized in the capture, for models without vision
+    Text(String),
+}
+
+/// Pick the input a provider can take, recognizing text only when needed
+pub fn prepare_input(
+    transport: &dyn HttpTransport,
+    provider: &dyn ChatProvider,
+    image: &DynamicImage,
+    recognize: impl FnOnce(&DynamicImage) -> GrabResult<String>,
+) -> GrabResult<ActionInput> {
+    if provider.supports_vision(transport)? {
+        Ok(ActionInput::Image(encode_image(image)?))
+    } else {
+        Ok(ActionInput::Text(recognize(image)?))
+    }
+}
+
+/// Instruction sent with the capture for an action
+pub fn prompt(action: SmartAction, language: &str) -> String {
+    match action {
+        SmartAction::Ocr => "Transcribe all text in this image exactly as it appears. \
+            Keep line breaks, indentation and code as they are."
+            .to_string(),
+        SmartAction::CleanFormat => "Transcribe the text in this image and clean it up: \
+            join lines broken by wrapping and restore bullet lists, tables (as Markdown) \
+            and code blocks."
+            .to_string(),
+        SmartAction::Translate => format!(
+            "Transcribe the text in this image and translate it into {}. \
+            Keep the original formatting.",
+            language
+        ),
+        SmartAction::Summarize => "Summarize what this image shows in two or three sentences, \
+            then list the key points as bullets."
+            .to_string(),
+        SmartAction::MarkdownNotes => "Turn the content of this image into well-structured \
+            Markdown notes, using headings, lists, tables and fenced code blocks where they fit."
+            .to_string(),
+        SmartAction::BugReport => "This screenshot shows a software problem. Draft a bug report \
+            in Markdown with the sections Summary, Steps to Reproduce, Expected Result, \
+            Actual Result and Environment. Fill in what the screenshot shows and mark \
+            anything unknown as TODO."
+            .to_string(),
+    }
+}
+
+/// Chat-completions body with the prompt and the capture attached
+pub fn chat_body(action: SmartAction, language: &str, input: &ActionInput) -> serde_json::Value {
+    let content = match input {
+        ActionInput::Image(png) => {
+            let image_url = format!(
+                "data:image/png;base64,{}",
+                base64::engine::general_purpose::STANDARD.encode(png)
+            );
+            serde_json::json!([
+                { "type": "text", "text": prompt(action, language) },
+                { "type": "image_url", "image_url": { "url": image_url } },
+            ])
+        }
+        ActionInput::Text(text) => serde_json::json!(format!(
+            "{}\n\nThe image itself isn't available; this is the text recognized in it:\n\n{}",
+            prompt(action, language),
+            text
+        )),
+    };
+
+    serde_json::json!({
+        "messages": [
+            { "role": "system", "content": SYSTEM_PROMPT },
+            { "role": "user", "content": content },
+        ],
+        "stream": true,
+    })
+}
+
+/// PNG-encode an image for upload, downscaling very large captures
+pub fn encode_image(image: &DynamicImage) -> GrabResult<Vec<u8>> {
+    let image = if image.width().max(image.height()) > MAX_IMAGE_EDGE {
+        image.resize(
+            MAX_IMAGE_EDGE,
+            MAX_IMAGE_EDGE,
+            image::imageops::FilterType::Lanczos3,
+        )
+    } else {
+        image.clone()
+    };
+
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)?;
+    Ok(bytes)
+}
+
+/// Run an action against a provider, returning the full response text
+pub fn run_action(
+    transport: &dyn HttpTransport,
+    provider: &dyn ChatProvider,
+    action: SmartAction,
+    language: &str,
+    input: &ActionInput,
+    on_delta: &mut dyn FnMut(&str),
+) -> GrabResult<String> {
+    let request = provider.request(chat_body(action, language, input));
+    stream_chat(transport, &request, on_delta)
+}
+
+/// Send a chat request and read the streamed reply
+///
+/// Servers that ignore `stream` and answer with a single JSON completion
+/// are handled too.
+pub fn stream_chat(
+    transport: &dyn HttpTransport,
+    request: &HttpRequest,
+    on_delta: &mut dyn FnMut(&str),
+) -> GrabResult<String> {
+    let response = transport.post(request)?;
+    let mut reader = BufReader::new(response.body);
+
+    if !(200..300).contains(&response.status) {
+        let mut body = String::new();
+        let _ = reader.read_to_string(&mut body);
+        return Err(GrabError::AiFailed(error_message(response.status, &body)));
+    }
+
+    let mut text = String::new();
+    let mut other = String::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line).map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                GrabError::AiUnavailable("the server stopped responding".to_string())
+            }
+            _ => GrabError::Io(e),
+        })?;
+        if read == 0 {
+            break;
+        }
+        let Some(data) = line.trim().strip_prefix("data:") else {
+            other.push_str(&line);
+            continue;
+        };
+        let data = data.trim();
+        if data == "[DONE]" {
+            break;
+        }
+
+        let chunk: serde_json::Value = serde_json::from_str(data)?;
+        // Azure sends content-filter results in chunks without choices
+        if let Some(delta) = chunk["choices"][0]["delta"]["content"].as_str() {
+            if !delta.is_empty() {
+                text.push_str(delta);
+                on_delta(delta);
+            }
+        }
+    }
+
+    if text.is_empty() && !other.trim().is_empty() {
+        let completion: serde_json::Value = serde_json::from_str(&other)?;
+        if let Some(content) = completion["choices"][0]["message"]["content"].as_str() {
+            text.push_str(content);
+            on_delta(content);
+        }
+    }
+
+    Ok(text)
+}
+
+/// Readable message from an error response
+fn error_message(status: u16, body: &str) -> String {
+    let message = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| {
+            // OpenAI nests the message; Ollama's native errors are a string
+            v["error"]["message"]
+                .as_str()
+                .or_else(|| v["error"].as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| body.trim().chars().take(200).collect());
+
+    match status {
+        401 | 403 => format!("authentication failed ({}): {}", status, message),
+        404 => format!("not found (check the model or deployment): {}", message),
+        429 => format!("rate limited: {}", message),
+        _ => format!("server returned {}: {}", status, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Serve canned HTTP responses in order, one per connection, and hand
+    /// back the raw requests
+    fn stub_server(responses: Vec<&'static str>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    head.push_str(&line);
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                tx.send(head + &String::from_utf8_lossy(&body)).unwrap();
+
+                let mut stream = reader.into_inner();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        (url, rx)
+    }
+
+    fn azure(endpoint: String) -> AzureConfig {
+        let settings = AzureOpenAiSettings {
+            endpoint: Some(endpoint + "/"),
+            ..Default::default()
+        };
+        AzureConfig::resolve_with(&settings, |name| match name {
+            "AZURE_OPENAI_API_KEY" => Some("test-key".to_string()),
+            "AZURE_OPENAI_DEPLOYMENT" => Some("vision".to_string()),
+            _ => None,
+        })
+        .unwrap()
+    }
+
+    fn image_input() -> ActionInput {
+        ActionInput::Image(encode_image(&DynamicImage::new_rgba8(4, 4)).unwrap())
+    }
+
+    #[test]
+    fn test_action_streams_from_stub_server() {
+        let (url, request) = stub_server(vec![concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n",
+            "data: {\"choices\":[],\"prompt_filter_results\":[]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\" world\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        )]);
+
+        let mut deltas = Vec::new();
+        let text = run_action(
+            &UreqTransport::new(TIMEOUT),
+            &azure(url),
+            SmartAction::Translate,
+            "German",
+            &image_input(),
+            &mut |delta| deltas.push(delta.to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(text, "Hello world");
+        assert_eq!(deltas, ["Hello", " world"]);
+
+        let request = request.recv().unwrap();
+        assert!(request.starts_with(&format!(
+            "POST /openai/deployments/vision/chat/completions?api-version={} ",
+            DEFAULT_API_VERSION
+        )));
+        assert!(request.to_ascii_lowercase().contains("api-key: test-key"));
+        assert!(request.contains("translate it into German"));
+        assert!(request.contains("data:image/png;base64,"));
+    }
+
+    #[test]
+    fn test_text_only_local_model_gets_recognized_text() {
+        let (url, requests) = stub_server(vec![
+            concat!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n",
+                "{\"capabilities\":[\"completion\",\"tools\"]}",
+            ),
+            concat!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n",
+                "{\"choices\":[{\"message\":{\"role\":\"assistant\",\"content\":\"Invoice 42 is due.\"}}]}",
+            ),
+        ]);
+        let provider = OpenAiCompatibleConfig::resolve(&OpenAiCompatibleSettings {
+            base_url: format!("{}/v1/", url),
+            model: "llama3.2".to_string(),
+            api_key: Some("local-key".to_string()),
+            vision: None,
+        })
+        .unwrap();
+        let transport = UreqTransport::new(TIMEOUT);
+
+        let input = prepare_input(
+            &transport,
+            &provider,
+            &DynamicImage::new_rgba8(4, 4),
+            |_| Ok("INVOICE #42 due 2026-11-01".to_string()),
+        )
+        .unwrap();
+        let text = run_action(
+            &transport,
+            &provider,
+            SmartAction::Summarize,
+            "English",
+            &input,
+            &mut |_| {},
+        )
+        .unwrap();
+        assert_eq!(text, "Invoice 42 is due.");
+
+        let show = requests.recv().unwrap();
+        assert!(show.starts_with("POST /api/show "));
+        assert!(show.contains("\"model\":\"llama3.2\""));
+
+        let chat = requests.recv().unwrap();
+        assert!(chat.starts_with("POST /v1/chat/completions "));
+        assert!(chat
+            .to_ascii_lowercase()
+            .contains("authorization: bearer local-key"));
+        assert!(chat.contains("\"model\":\"llama3.2\""));
+        assert!(chat.contains("INVOICE #42"));
+        assert!(!chat.contains("data:image"));
+
+        // The server isn't asked again
+        assert!(!provider.supports_vision(&transport).unwrap());
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_errors_are_mapped() {
+        let (url, _requests) = stub_server(vec![concat!(
+            "HTTP/1.1 401 Unauthorized\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n",
+            "{\"error\":{\"code\":\"401\",\"message\":\"Access denied due to invalid subscription key.\"}}",
+        )]);
+
+        let error = run_action(
+            &UreqTransport::new(TIMEOUT),
+            &azure(url),
+            SmartAction::Ocr,
+            "English",
+            &image_input(),
+            &mut |_| {},
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "AI request failed: authentication failed (401): Access denied due to invalid subscription key."
+        );
+
+        // Nothing listening on the port any more
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+
+        let provider = OpenAiCompatibleConfig::resolve(&OpenAiCompatibleSettings {
+            base_url: url,
+            model: "llava".to_string(),
+            vision: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+        let error = run_action(
+            &UreqTransport::new(TIMEOUT),
+            &provider,
+            SmartAction::Ocr,
+            "English",
+            &image_input(),
+            &mut |_| {},
+        )
+        .unwrap_err();
+        assert!(matches!(error, GrabError::AiUnavailable(_)));
+    }
+}
@@ -0,0 +1,561 @@
+//! Annotation renderer
+//!
+//! Rasterizes the `Annotation` model onto a capture with anti-aliased
+//! vector drawing (tiny-skia) and text from the embedded DejaVu fonts, so
+//! the CLI and batch export produce the same pixels as the editor.
+
+use crate::error::{GrabError, GrabResult};
+use crate::types::{Annotation, Color, Point};
+use ab_glyph::{Font, FontRef, Glyph, PxScale, ScaleFont};
+use image::RgbaImage;
+use tiny_skia::{
+    BlendMode, FillRule, IntSize, LineCap, LineJoin, Paint, Path, PathBuilder, Pixmap, Rect,
+    Stroke, StrokeDash, Transform,
+};
+
+static FONT_REGULAR: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+static FONT_BOLD: &[u8] = include_bytes!("../assets/fonts/DejaVuSans-Bold.ttf");
+
+/// Padding around text with a background, relative to the font size
+pub const TEXT_PADDING: f32 = 0.3;
+
+/// Draw annotations onto an image, in order
+pub fn render(image: &RgbaImage, annotations: &[Annotation]) -> GrabResult<RgbaImage> {
+    let mut pixmap = to_pixmap(image)?;
+
+    for annotation in annotations {
+        draw(&mut pixmap, annotation);
+    }
+
+    Ok(from_pixmap(&pixmap))
+}
+
+/// Size of a text annotation, including its background when it has one
+pub fn text_size(text: &str, font_size: f32, bold: bool, background: bool) -> (f32, f32) {
+    let layout = layout_text(&font(bold), font_size, text, 0.0, 0.0);
+    let padding = if background { font_size * TEXT_PADDING * 2.0 } else { 0.0 };
+    (layout.width + padding, layout.height + padding)
+}
+
+fn draw(pixmap: &mut Pixmap, annotation: &Annotation) {
+    match annotation {
+        Annotation::Rectangle {
+            x,
+            y,
+            width,
+            height,
+            color,
+            stroke_width,
+            fill,
+            corner_radius,
+        } => {
+            if let Some(path) = rounded_rect(*x, *y, *width, *height, *corner_radius) {
+                fill_and_stroke(pixmap, &path, *color, *stroke_width, *fill);
+            }
+        }
+        Annotation::Ellipse {
+            x,
+            y,
+            width,
+            height,
+            color,
+            stroke_width,
+            fill,
+        } => {
+            let path = Rect::from_xywh(*x, *y, *width, *height).and_then(PathBuilder::from_oval);
+            if let Some(path) = path {
+                fill_and_stroke(pixmap, &path, *color, *stroke_width, *fill);
+            }
+        }
+        Annotation::Arrow {
+            from,
+            to,
+            color,
+            stroke_width,
+        } => draw_arrow(pixmap, *from, *to, *color, *stroke_width),
+        Annotation::Line {
+            from,
+            to,
+            color,
+            stroke_width,
+            dashed,
+        } => {
+            let mut builder = PathBuilder::new();
+            builder.move_to(from.x, from.y);
+            builder.line_to(to.x, to.y);
+            if let Some(path) = builder.finish() {
+                let mut stroke = round_stroke(*stroke_width);
+                if *dashed {
+                    stroke.dash = StrokeDash::new(vec![stroke_width * 3.0, stroke_width * 2.0], 0.0);
+                }
+                pixmap.stroke_path(&path, &paint(*color), &stroke, Transform::identity(), None);
+            }
+        }
+        Annotation::Freehand {
+            points,
+            color,
+            stroke_width,
+        } => {
+            if let Some(path) = smooth_path(points) {
+                let stroke = round_stroke(*stroke_width);
+                pixmap.stroke_path(&path, &paint(*color), &stroke, Transform::identity(), None);
+            }
+        }
+        Annotation::Highlighter {
+            points,
+            color,
+            stroke_width,
+        } => {
+            if let Some(path) = smooth_path(points) {
+                let mut paint = paint(*color);
+                paint.blend_mode = BlendMode::Multiply;
+                let stroke = round_stroke(*stroke_width);
+                pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+            }
+        }
+        Annotation::Text {
+            x,
+            y,
+            text,
+            color,
+            font_size,
+            bold,
+            background,
+        } => draw_text(pixmap, *x, *y, text, *color, *font_size, *bold, *background),
+        Annotation::Step {
+            x,
+            y,
+            number,
+            color,
+            radius,
+        } => draw_step(pixmap, *x, *y, *number, *color, *radius),
+        Annotation::Blur {
+            x,
+            y,
+            width,
+            height,
+            radius,
+        } => {
+            if let Some((left, top, mut region)) = extract_region(pixmap, *x, *y, *width, *height) {
+                region = image::imageops::fast_blur(&region, radius.max(0.5));
+                put_region(pixmap, left, top, &region);
+            }
+        }
+        Annotation::Pixelate {
+            x,
+            y,
+            width,
+            height,
+            block_size,
+        } => {
+            if let Some((left, top, mut region)) = extract_region(pixmap, *x, *y, *width, *height) {
+                pixelate(&mut region, (*block_size).max(2));
+                put_region(pixmap, left, top, &region);
+            }
+        }
+    }
+}
+
+fn paint(color: Color) -> Paint<'static> {
+    let [r, g, b, a] = color.0;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    paint
+}
+
+fn round_stroke(width: f32) -> Stroke {
+    Stroke {
+        width: width.max(0.5),
+        line_cap: LineCap::Round,
+        line_join: LineJoin::Round,
+        ..Default::default()
+    }
+}
+
+fn fill_and_stroke(pixmap: &mut Pixmap, path: &Path, color: Color, width: f32, fill: Option<Color>) {
+    if let Some(fill) = fill {
+        pixmap.fill_path(path, &paint(fill), FillRule::Winding, Transform::identity(), None);
+    }
+    if width > 0.0 {
+        let stroke = Stroke {
+            width,
+            line_join: LineJoin::Round,
+            ..Default::default()
+        };
+        pixmap.stroke_path(path, &paint(color), &stroke, Transform::identity(), None);
+    }
+}
+
+/// Rectangle path, with rounded corners when `radius` > 0
+pub fn rounded_rect(x: f32, y: f32, width: f32, height: f32, radius: f32) -> Option<Path> {
+    // Normalize rectangles drawn right-to-left or bottom-to-top
+    let (x, width) = if width < 0.0 { (x + width, -width) } else { (x, width) };
+    let (y, height) = if height < 0.0 { (y + height, -height) } else { (y, height) };
+    let rect = Rect::from_xywh(x, y, width, height)?;
+
+    let r = radius.min(width / 2.0).min(height / 2.0);
+    if r <= 0.0 {
+        return Some(PathBuilder::from_rect(rect));
+    }
+
+    // Cubic approximation of a quarter circle
+    let k = r * 0.552_284_8;
+    let (right, bottom) = (x + width, y + height);
+    let mut builder = PathBuilder::new();
+    builder.move_to(x + r, y);
+    builder.line_to(right - r, y);
+    builder.cubic_to(right - r + k, y, right, y + r - k, right, y + r);
+    builder.line_to(right, bottom - r);
+    builder.cubic_to(right, bottom - r + k, right - r + k, bottom, right - r, bottom);
+    builder.line_to(x + r, bottom);
+    builder.cubic_to(x + r - k, bottom, x, bottom - r + k, x, bottom - r);
+    builder.line_to(x, y + r);
+    builder.cubic_to(x, y + r - k, x + r - k, y, x + r, y);
+    builder.close();
+    builder.finish()
+}
+
+/// Path through freehand points, smoothed with quadratic curves
+fn smooth_path(points: &[Point]) -> Option<Path> {
+    let first = points.first()?;
+    let mut builder = PathBuilder::new();
+    builder.move_to(first.x, first.y);
+
+    if points.len() == 1 {
+        // A single click still leaves a dot
+        builder.line_to(first.x + 0.01, first.y);
+        return builder.finish();
+    }
+
+    for pair in points.windows(2).skip(1) {
+        let mid = Point {
+            x: (pair[0].x + pair[1].x) / 2.0,
+            y: (pair[0].y + pair[1].y) / 2.0,
+        };
+        builder.quad_to(pair[0].x, pair[0].y, mid.x, mid.y);
+    }
+    let last = points[points.len() - 1];
+    builder.line_to(last.x, last.y);
+    builder.finish()
+}
+
+fn draw_arrow(pixmap: &mut Pixmap, from: Point, to: Point, color: Color, width: f32) {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length < f32::EPSILON {
+        return;
+    }
+    let (ux, uy) = (dx / length, dy / length);
+
+    let head_length = (width * 4.0).max(10.0).min(length);
+    let head_width = head_length * 0.6;
+
+    // Stop the shaft inside the head so its round cap doesn't poke out
+    let base = Point {
+        x: to.x - ux * head_length,
+        y: to.y - uy * head_length,
+    };
+    let shaft_end = Point {
+        x: to.x - ux * head_length * 0.5,
+        y: to.y - uy * head_length * 0.5,
+    };
+
+    let mut shaft = PathBuilder::new();
+    shaft.move_to(from.x, from.y);
+    shaft.line_to(shaft_end.x, shaft_end.y);
+    if let Some(path) = shaft.finish() {
+        pixmap.stroke_path(&path, &paint(color), &round_stroke(width), Transform::identity(), None);
+    }
+
+    let mut head = PathBuilder::new();
+    head.move_to(to.x, to.y);
+    head.line_to(base.x - uy * head_width, base.y + ux * head_width);
+    head.line_to(base.x + uy * head_width, base.y - ux * head_width);
+    head.close();
+    if let Some(path) = head.finish() {
+        pixmap.fill_path(&path, &paint(color), FillRule::Winding, Transform::identity(), None);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_text(
+    pixmap: &mut Pixmap,
+    x: f32,
+    y: f32,
+    text: &str,
+    color: Color,
+    font_size: f32,
+    bold: bool,
+    background: Option<Color>,
+) {
+    let font = font(bold);
+    let layout = layout_text(&font, font_size, text, x, y);
+
+    if let Some(background) = background {
+        let padding = font_size * TEXT_PADDING;
+        let path = rounded_rect(
+            x - padding,
+            y - padding,
+            layout.width + padding * 2.0,
+            layout.height + padding * 2.0,
+            padding,
+        );
+        if let Some(path) = path {
+            pixmap.fill_path(&path, &paint(background), FillRule::Winding, Transform::identity(), None);
+        }
+    }
+
+    draw_glyphs(pixmap, &font, &layout.glyphs, color);
+}
+
+fn draw_step(pixmap: &mut Pixmap, x: f32, y: f32, number: u32, color: Color, radius: f32) {
+    if let Some(circle) = PathBuilder::from_circle(x, y, radius) {
+        pixmap.fill_path(&circle, &paint(color), FillRule::Winding, Transform::identity(), None);
+    }
+
+    let label = number.to_string();
+    let font = font(true);
+    let font_size = radius * if label.len() > 2 { 0.8 } else { 1.1 };
+    // Center the digits' ink rather than the line box
+    let mut layout = layout_text(&font, font_size, &label, 0.0, 0.0);
+    if let Some(bounds) = ink_bounds(&font, &layout.glyphs) {
+        let dx = x - (bounds.min.x + bounds.max.x) / 2.0;
+        let dy = y - (bounds.min.y + bounds.max.y) / 2.0;
+        for glyph in &mut layout.glyphs {
+            glyph.position.x += dx;
+            glyph.position.y += dy;
+        }
+    }
+
+    // White or black, whichever reads better on the marker
+    let [r, g, b, _] = color.0;
+    let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    let text_color = if luma > 160.0 {
+        Color([0, 0, 0, 255])
+    } else {
+        Color::WHITE
+    };
+    draw_glyphs(pixmap, &font, &layout.glyphs, text_color);
+}
+
+fn font(bold: bool) -> FontRef<'static> {
+    let data = if bold { FONT_BOLD } else { FONT_REGULAR };
+    FontRef::try_from_slice(data).expect("embedded font is valid")
+}
+
+/// Positioned glyphs and the size of the text block
+struct TextLayout {
+    glyphs: Vec<Glyph>,
+    width: f32,
+    height: f32,
+}
+
+/// Lay out text with its top-left corner at `x`, `y`
+fn layout_text(font: &FontRef<'static>, font_size: f32, text: &str, x: f32, y: f32) -> TextLayout {
+    let scale = PxScale::from(font_size.max(1.0));
+    let scaled = font.as_scaled(scale);
+    let line_height = scaled.height() + scaled.line_gap();
+
+    let mut glyphs = Vec::new();
+    let mut width: f32 = 0.0;
+    let mut line_count = 0;
+
+    for (i, line) in text.split('\n').enumerate() {
+        let mut caret = ab_glyph::point(x, y + scaled.ascent() + i as f32 * line_height);
+        let mut previous = None;
+
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                caret.x += scaled.kern(previous, id);
+            }
+            glyphs.push(id.with_scale_and_position(scale, caret));
+            caret.x += scaled.h_advance(id);
+            previous = Some(id);
+        }
+
+        width = width.max(caret.x - x);
+        line_count = i + 1;
+    }
+
+    TextLayout {
+        glyphs,
+        width,
+        height: line_count as f32 * line_height - scaled.line_gap(),
+    }
+}
+
+/// Pixel bounds covered by the glyphs' outlines
+fn ink_bounds(font: &FontRef<'static>, glyphs: &[Glyph]) -> Option<ab_glyph::Rect> {
+    glyphs
+        .iter()
+        .filter_map(|glyph| font.outline_glyph(glyph.clone()))
+        .map(|outlined| outlined.px_bounds())
+        .reduce(|a, b| ab_glyph::Rect {
+            min: ab_glyph::point(a.min.x.min(b.min.x), a.min.y.min(b.min.y)),
+            max: ab_glyph::point(a.max.x.max(b.max.x), a.max.y.max(b.max.y)),
+        })
+}
+
+fn draw_glyphs(pixmap: &mut Pixmap, font: &FontRef<'static>, glyphs: &[Glyph], color: Color) {
+    for glyph in glyphs {
+        let Some(outlined) = font.outline_glyph(glyph.clone()) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            blend_pixel(pixmap, px, py, color, coverage);
+        });
+    }
+}
+
+/// Source-over blend of one colour at partial coverage
+fn blend_pixel(pixmap: &mut Pixmap, x: i32, y: i32, color: Color, coverage: f32) {
+    if x < 0 || y < 0 || x >= pixmap.width() as i32 || y >= pixmap.height() as i32 {
+        return;
+    }
+    let index = (y as usize * pixmap.width() as usize + x as usize) * 4;
+    let data = pixmap.data_mut();
+
+    let alpha = color.0[3] as f32 / 255.0 * coverage.clamp(0.0, 1.0);
+    for c in 0..4 {
+        let src = if c == 3 { 255.0 } else { color.0[c] as f32 } * alpha;
+        let dst = data[index + c] as f32;
+        data[index + c] = (src + dst * (1.0 - alpha)).round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Copy a clamped region of the pixmap out as an image
+fn extract_region(
+    pixmap: &Pixmap,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+) -> Option<(u32, u32, RgbaImage)> {
+    let (x, width) = if width < 0.0 { (x + width, -width) } else { (x, width) };
+    let (y, height) = if height < 0.0 { (y + height, -height) } else { (y, height) };
+
+    let left = x.max(0.0).floor() as u32;
+    let top = y.max(0.0).floor() as u32;
+    let right = ((x + width).ceil().max(0.0) as u32).min(pixmap.width());
+    let bottom = ((y + height).ceil().max(0.0) as u32).min(pixmap.height());
+    if right <= left || bottom <= top {
+        return None;
+    }
+
+    let stride = pixmap.width() as usize * 4;
+    let data = pixmap.data();
+    let region = RgbaImage::from_fn(right - left, bottom - top, |rx, ry| {
+        let i = (top + ry) as usize * stride + (left + rx) as usize * 4;
+        image::Rgba([data[i], data[i + 1], data[i + 2], data[i + 3]])
+    });
+    Some((left, top, region))
+}
+
+fn put_region(pixmap: &mut Pixmap, left: u32, top: u32, region: &RgbaImage) {
+    let stride = pixmap.width() as usize * 4;
+    let data = pixmap.data_mut();
+    for (rx, ry, pixel) in region.enumerate_pixels() {
+        let i = (top + ry) as usize * stride + (left + rx) as usize * 4;
+        data[i..i + 4].copy_from_slice(&pixel.0);
+    }
+}
+
+/// Replace each block with its average colour
+pub fn pixelate(region: &mut RgbaImage, block_size: u32) {
+    let (width, height) = region.dimensions();
+    for by in (0..height).step_by(block_size as usize) {
+        for bx in (0..width).step_by(block_size as usize) {
+            let (bw, bh) = (block_size.min(width - bx), block_size.min(height - by));
+            let mut sum = [0u32; 4];
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    for (s, v) in sum.iter_mut().zip(region.get_pixel(x, y).0) {
+                        *s += v as u32;
+                    }
+                }
+            }
+            let count = bw * bh;
+            let average = image::Rgba(sum.map(|s| ((s + count / 2) / count) as u8));
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    region.put_pixel(x, y, average);
+                }
+            }
+        }
+    }
+}
+
+/// Convert to tiny-skia's premultiplied RGBA
+pub fn to_pixmap(image: &RgbaImage) -> GrabResult<Pixmap> {
+    let size = IntSize::from_wh(image.width(), image.height())
+        .ok_or_else(|| GrabError::InvalidRequest("Image is empty".to_string()))?;
+
+    let mut data = image.as_raw().clone();
+    for pixel in data.chunks_exact_mut(4) {
+        let a = pixel[3] as u32;
+        if a != 255 {
+            for c in &mut pixel[..3] {
+                *c = ((*c as u32 * a + 127) / 255) as u8;
+            }
+        }
+    }
+
+    Pixmap::from_vec(data, size)
+        .ok_or_else(|| GrabError::InvalidRequest("Image is too large".to_string()))
+}
+
+pub fn from_pixmap(pixmap: &Pixmap) -> RgbaImage {
+    let mut data = pixmap.data().to_vec();
+    for pixel in data.chunks_exact_mut(4) {
+        let a = pixel[3] as u32;
+        if a != 255 && a != 0 {
+            for c in &mut pixel[..3] {
+                *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+            }
+        }
+    }
+    RgbaImage::from_raw(pixmap.width(), pixmap.height(), data)
+        .expect("pixmap buffer matches its size")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_render_annotations() {
+        let image = RgbaImage::from_pixel(200, 120, Rgba([255, 255, 255, 255]));
+        let red = Color([255, 0, 0, 255]);
+
+        let annotations: Vec<Annotation> = serde_json::from_str(
+            r##"[
+                {"type": "rectangle", "x": 10, "y": 10, "width": 50, "height": 30,
+                 "color": "#ff0000", "strokeWidth": 4, "fill": "#0000ff"},
+                {"type": "step", "x": 150, "y": 30, "number": 3, "color": "#ff0000", "radius": 14},
+                {"type": "text", "x": 10, "y": 70, "text": "Hi", "color": "#000000", "fontSize": 24},
+                {"type": "pixelate", "x": 100, "y": 60, "width": 40, "height": 40, "blockSize": 8}
+            ]"##,
+        )
+        .unwrap();
+
+        let rendered = render(&image, &annotations).unwrap();
+
+        assert_eq!(rendered.get_pixel(35, 25).0, [0, 0, 255, 255]); // fill
+        assert_eq!(rendered.get_pixel(11, 25).0, red.0); // stroke
+        assert_eq!(rendered.get_pixel(150, 20).0, red.0); // marker, above the digit
+        assert!(rendered.get_pixel(5, 5).0 == [255, 255, 255, 255]);
+
+        // Text leaves anti-aliased ink somewhere in its box
+        let ink = (10..50)
+            .flat_map(|x| (70..100).map(move |y| (x, y)))
+            .filter(|&(x, y)| rendered.get_pixel(x, y).0[0] < 128)
+            .count();
+        assert!(ink > 20);
+    }
+}
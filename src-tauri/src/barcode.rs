@@ -0,0 +1,222 @@
+//! QR code and barcode decoding
+//!
+//! Uses rxing, a pure-Rust port of ZXing, so nothing has to be installed.
+//! A capture can hold several codes; each is returned with the points the
+//! decoder located, mapped back to image coordinates.
+
+use crate::error::{GrabError, GrabResult};
+use crate::types::{DecodedCode, Point, RegionBounds};
+use image::{imageops, RgbaImage};
+use rxing::{BarcodeFormat, DecodeHints, Exceptions};
+
+/// Formats looked for: the common 2D codes and retail/logistics barcodes
+const FORMATS: [BarcodeFormat; 11] = [
+    BarcodeFormat::QR_CODE,
+    BarcodeFormat::DATA_MATRIX,
+    BarcodeFormat::AZTEC,
+    BarcodeFormat::PDF_417,
+    BarcodeFormat::EAN_13,
+    BarcodeFormat::EAN_8,
+    BarcodeFormat::UPC_A,
+    BarcodeFormat::UPC_E,
+    BarcodeFormat::CODE_128,
+    BarcodeFormat::CODE_39,
+    BarcodeFormat::ITF,
+];
+
+/// Find and decode all codes in an image, or in a region of it
+pub fn scan(image: &RgbaImage, region: Option<&RegionBounds>) -> GrabResult<Vec<DecodedCode>> {
+    let (x, y, width, height) = match region {
+        Some(region) => {
+            let x = region.x.clamp(0, image.width() as i32) as u32;
+            let y = region.y.clamp(0, image.height() as i32) as u32;
+            (
+                x,
+                y,
+                region.width.min(image.width() - x),
+                region.height.min(image.height() - y),
+            )
+        }
+        None => (0, 0, image.width(), image.height()),
+    };
+    if width == 0 || height == 0 {
+        return Err(GrabError::InvalidRequest(
+            "Scan region lies outside the image".to_string(),
+        ));
+    }
+
+    let view = imageops::crop_imm(image, x, y, width, height).to_image();
+    let luma = view
+        .pixels()
+        .map(|p| {
+            let [r, g, b, _] = p.0;
+            ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8
+        })
+        .collect();
+
+    let mut hints = DecodeHints {
+        PossibleFormats: Some(FORMATS.into_iter().collect()),
+        TryHarder: Some(true),
+        // Light-on-dark codes, e.g. in dark-mode apps
+        AlsoInverted: Some(true),
+        ..Default::default()
+    };
+    let results =
+        match rxing::helpers::detect_multiple_in_luma_with_hints(luma, width, height, &mut hints) {
+            Ok(results) => results,
+            Err(Exceptions::NotFoundException(_)) => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(GrabError::CaptureFailed(format!(
+                    "Code detection failed: {}",
+                    e
+                )))
+            }
+        };
+
+    let mut codes: Vec<DecodedCode> = Vec::new();
+    for result in results {
+        let format = format_name(result.getBarcodeFormat());
+        let text = result.getText().to_string();
+        // The multi-reader can report a code once per pass
+        if codes.iter().any(|c| c.format == format && c.text == text) {
+            continue;
+        }
+
+        let points: Vec<Point> = result
+            .getPoints()
+            .iter()
+            .map(|p| Point {
+                x: p.x + x as f32,
+                y: p.y + y as f32,
+            })
+            .collect();
+        codes.push(DecodedCode {
+            bounds: bounding_box(&points),
+            format,
+            text,
+            points,
+        });
+    }
+
+    // Reading order, top to bottom
+    codes.sort_by_key(|c| (c.bounds.y, c.bounds.x));
+    Ok(codes)
+}
+
+/// Kebab-case name of a format, e.g. `qr-code` or `ean-13`
+fn format_name(format: &BarcodeFormat) -> String {
+    format!("{:?}", format)
+        .to_ascii_lowercase()
+        .replace('_', "-")
+}
+
+fn bounding_box(points: &[Point]) -> RegionBounds {
+    let (mut left, mut top) = (f32::MAX, f32::MAX);
+    let (mut right, mut bottom) = (f32::MIN, f32::MIN);
+    for p in points {
+        left = left.min(p.x);
+        top = top.min(p.y);
+        right = right.max(p.x);
+        bottom = bottom.max(p.y);
+    }
+    if points.is_empty() {
+        return RegionBounds {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        };
+    }
+
+    RegionBounds {
+        x: left.floor() as i32,
+        y: top.floor() as i32,
+        width: (right.ceil() - left.floor()) as u32,
+        height: (bottom.ceil() - top.floor()) as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+    use rxing::{MultiFormatWriter, Writer};
+
+    /// Draw an encoded code onto the image at a position
+    fn draw(
+        image: &mut RgbaImage,
+        format: BarcodeFormat,
+        text: &str,
+        at: (u32, u32),
+        size: (i32, i32),
+    ) {
+        let matrix = MultiFormatWriter
+            .encode(text, &format, size.0, size.1)
+            .unwrap();
+        for my in 0..matrix.height() {
+            for mx in 0..matrix.width() {
+                if matrix.get(mx, my) {
+                    image.put_pixel(at.0 + mx, at.1 + my, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_scan_finds_codes_with_positions() {
+        let mut image = RgbaImage::from_pixel(900, 500, Rgba([255, 255, 255, 255]));
+        let secret = "otpauth://totp/Grab:ada@example.com?secret=JBSWY3DPEHPK3PXP";
+        draw(
+            &mut image,
+            BarcodeFormat::QR_CODE,
+            secret,
+            (40, 40),
+            (240, 240),
+        );
+        draw(
+            &mut image,
+            BarcodeFormat::DATA_MATRIX,
+            "PAIR-7F3A-91C2",
+            (620, 40),
+            (200, 200),
+        );
+        draw(
+            &mut image,
+            BarcodeFormat::CODE_128,
+            "GRAB-0042",
+            (360, 340),
+            (400, 100),
+        );
+
+        let codes = scan(&image, None).unwrap();
+        assert_eq!(codes.len(), 3);
+        let find = |format: &str| codes.iter().find(|c| c.format == format).unwrap();
+
+        let qr = find("qr-code");
+        assert_eq!(qr.text, secret);
+        assert!(qr.points.len() >= 3);
+        assert!(qr.bounds.x >= 40 && qr.bounds.x + qr.bounds.width as i32 <= 280);
+        assert!(qr.bounds.y >= 40 && qr.bounds.y + qr.bounds.height as i32 <= 280);
+
+        assert_eq!(find("data-matrix").text, "PAIR-7F3A-91C2");
+
+        let barcode = find("code-128");
+        assert_eq!(barcode.text, "GRAB-0042");
+        assert!(barcode.points.iter().all(|p| p.x >= 360.0 && p.y >= 340.0));
+
+        // Only the region is searched, and positions stay in image coordinates
+        let region = RegionBounds {
+            x: 300,
+            y: 300,
+            width: 600,
+            height: 200,
+        };
+        let codes = scan(&image, Some(&region)).unwrap();
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].text, "GRAB-0042");
+        assert!(codes[0].points.iter().all(|p| p.x >= 360.0 && p.y >= 340.0));
+
+        let blank = RgbaImage::from_pixel(200, 200, Rgba([255, 255, 255, 255]));
+        assert!(scan(&blank, None).unwrap().is_empty());
+    }
+}
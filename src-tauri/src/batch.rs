@@ -0,0 +1,385 @@
+//! Batch export of history items
+//!
+//! Each item is decoded once and written out for every target. Items are
+//! shared out to a small pool of worker threads; a cancelled job stops after
+//! the outputs already in progress.
+
+use crate::annotate;
+use crate::capture;
+use crate::error::{GrabError, GrabResult};
+use crate::export;
+use crate::metadata;
+use crate::types::{
+    Annotation, BatchExportReport, BatchItemResult, BatchProgress, BatchTarget, CaptureMode,
+    ExportOptions, ExportPreset,
+};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Upper bound on worker threads; encoding is CPU-bound
+const MAX_WORKERS: usize = 4;
+
+/// Cancellation flags for running batch jobs
+#[derive(Default)]
+pub struct BatchJobs {
+    jobs: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl BatchJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a job and return its cancellation flag
+    pub fn register(&self, job_id: &str) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(job_id.to_string(), flag.clone());
+        flag
+    }
+
+    /// Request cancellation; returns false if the job isn't running
+    pub fn cancel(&self, job_id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, job_id: &str) {
+        self.jobs.lock().unwrap().remove(job_id);
+    }
+}
+
+/// A batch target with its export options resolved
+#[derive(Debug, Clone)]
+pub struct ResolvedTarget {
+    /// Used in reports and for the `{target}` template token
+    pub label: String,
+    pub options: ExportOptions,
+}
+
+/// A history item to export
+#[derive(Debug, Clone)]
+pub struct BatchSource {
+    pub item_id: String,
+    pub path: PathBuf,
+    pub mode: Option<CaptureMode>,
+    pub timestamp: String,
+    /// Drawn onto the image before every target is exported
+    pub annotations: Vec<Annotation>,
+}
+
+/// Resolve targets against the saved presets, giving each a unique label
+pub fn resolve_targets(
+    targets: &[BatchTarget],
+    presets: &[ExportPreset],
+) -> GrabResult<Vec<ResolvedTarget>> {
+    if targets.is_empty() {
+        return Err(GrabError::InvalidRequest("No export targets given".to_string()));
+    }
+
+    let mut resolved: Vec<ResolvedTarget> = Vec::new();
+    for target in targets {
+        let (label, options) = match (&target.options, &target.preset) {
+            (Some(options), preset) => {
+                let label = preset
+                    .as_deref()
+                    .map(export::preset_slug)
+                    .unwrap_or_else(|| options.format.extension().to_string());
+                (label, options.clone())
+            }
+            (None, Some(name)) => {
+                let preset = export::find_preset(presets, name).ok_or_else(|| {
+                    GrabError::InvalidRequest(format!("Unknown export preset: {}", name))
+                })?;
+                (export::preset_slug(&preset.name), preset.options.clone())
+            }
+            (None, None) => {
+                return Err(GrabError::InvalidRequest(
+                    "Export target needs a preset or options".to_string(),
+                ));
+            }
+        };
+
+        // Two plain JPEG targets shouldn't write to the same name
+        let mut unique = label.clone();
+        let mut n = 2;
+        while resolved.iter().any(|t| t.label == unique) {
+            unique = format!("{}-{}", label, n);
+            n += 1;
+        }
+        resolved.push(ResolvedTarget {
+            label: unique,
+            options,
+        });
+    }
+
+    Ok(resolved)
+}
+
+/// Export every source to every target using a pool of worker threads
+///
+/// `on_progress` is called from the worker threads after each output.
+#[allow(clippy::too_many_arguments)]
+pub fn run_batch(
+    job_id: &str,
+    sources: &[BatchSource],
+    targets: &[ResolvedTarget],
+    output_dir: &Path,
+    naming_template: &str,
+    strip_metadata: bool,
+    cancel: &AtomicBool,
+    on_progress: &(dyn Fn(&BatchProgress) + Sync),
+) -> GrabResult<BatchExportReport> {
+    fs::create_dir_all(output_dir)?;
+
+    let total = sources.len() * targets.len();
+    let next = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let claimed = Mutex::new(HashSet::new());
+    let results = Mutex::new(Vec::new());
+
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(2)
+        .min(MAX_WORKERS)
+        .min(sources.len())
+        .max(1);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if cancel.load(Ordering::SeqCst) {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(source) = sources.get(index) else {
+                    break;
+                };
+
+                let image = fs::read(&source.path)
+                    .map_err(GrabError::from)
+                    .and_then(|bytes| Ok((load_annotated(&bytes, &source.annotations)?, bytes)));
+                let source_metadata = image
+                    .as_ref()
+                    .ok()
+                    .and_then(|(_, bytes)| metadata::read_embedded(bytes));
+                let scale_factor = source_metadata.as_ref().map(|m| m.scale_factor).unwrap_or(1.0);
+                let embedded = source_metadata.as_ref().filter(|_| !strip_metadata);
+
+                for (target_index, target) in targets.iter().enumerate() {
+                    if cancel.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    let outcome = image.as_ref().map_err(|e| e.clone()).and_then(|(image, _)| {
+                        let name = file_name(naming_template, source, target, index + 1);
+                        let path = claim_path(&claimed, output_dir, &name, &target.options);
+                        export::export_to_file(image, &target.options, embedded, scale_factor, &path)
+                            .map(|_| path)
+                    });
+
+                    let result = BatchItemResult {
+                        item_id: source.item_id.clone(),
+                        source_path: source.path.to_string_lossy().to_string(),
+                        target: target.label.clone(),
+                        output_path: outcome
+                            .as_ref()
+                            .ok()
+                            .map(|p| p.to_string_lossy().to_string()),
+                        error: outcome.as_ref().err().map(|e| e.to_string()),
+                    };
+
+                    on_progress(&BatchProgress {
+                        job_id: job_id.to_string(),
+                        completed: completed.fetch_add(1, Ordering::SeqCst) + 1,
+                        total,
+                        item_id: result.item_id.clone(),
+                        target: result.target.clone(),
+                        error: result.error.clone(),
+                    });
+
+                    results
+                        .lock()
+                        .unwrap()
+                        .push(((index, target_index), result));
+                }
+            });
+        }
+    });
+
+    // Report in request order regardless of which worker finished first
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(order, _)| *order);
+    let results: Vec<BatchItemResult> = results.into_iter().map(|(_, r)| r).collect();
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    Ok(BatchExportReport {
+        job_id: job_id.to_string(),
+        succeeded: results.len() - failed,
+        failed,
+        cancelled: results.len() < total,
+        results,
+    })
+}
+
+/// Decode an image and draw its annotations, if any
+fn load_annotated(bytes: &[u8], annotations: &[Annotation]) -> GrabResult<image::DynamicImage> {
+    let image = image::load_from_memory(bytes)?;
+    if annotations.is_empty() {
+        return Ok(image);
+    }
+    let rendered = annotate::render(&image.to_rgba8(), annotations)?;
+    Ok(image::DynamicImage::ImageRgba8(rendered))
+}
+
+/// Expand the naming template for one output (without extension)
+fn file_name(template: &str, source: &BatchSource, target: &ResolvedTarget, index: usize) -> String {
+    let stem = source
+        .path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let time = chrono::DateTime::parse_from_rfc3339(&source.timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&chrono::Utc));
+
+    let name = template
+        .replace("{name}", &stem)
+        .replace("{target}", &target.label)
+        .replace("{index}", &format!("{:03}", index));
+    let name = capture::expand_template(&name, time, source.mode);
+
+    // Templates must not escape the output directory
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect();
+    let name = name.trim().trim_start_matches('.').to_string();
+
+    if name.is_empty() {
+        format!("export-{:03}", index)
+    } else {
+        name
+    }
+}
+
+/// Pick an output path that neither exists nor is used by another output
+fn claim_path(
+    claimed: &Mutex<HashSet<PathBuf>>,
+    output_dir: &Path,
+    name: &str,
+    options: &ExportOptions,
+) -> PathBuf {
+    let ext = options.format.extension();
+    let mut claimed = claimed.lock().unwrap();
+
+    let mut path = output_dir.join(format!("{}.{}", name, ext));
+    let mut n = 2;
+    while claimed.contains(&path) || path.exists() {
+        path = output_dir.join(format!("{}-{}.{}", name, n, ext));
+        n += 1;
+    }
+    claimed.insert(path.clone());
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{CapturePreferences, ExportFormat, ResizeOptions};
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn test_batch_exports_every_target_and_reports_failures() {
+        let dir = std::env::temp_dir().join(format!("grab-batch-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let source_path = dir.join("shot.png");
+        RgbaImage::from_pixel(1600, 400, Rgba([20, 40, 60, 255]))
+            .save(&source_path)
+            .unwrap();
+
+        let sources = vec![
+            BatchSource {
+                item_id: "a".to_string(),
+                path: source_path,
+                mode: Some(CaptureMode::Region),
+                timestamp: "2024-05-01T10:20:30Z".to_string(),
+                annotations: Vec::new(),
+            },
+            BatchSource {
+                item_id: "b".to_string(),
+                path: dir.join("missing.png"),
+                mode: None,
+                timestamp: String::new(),
+                annotations: Vec::new(),
+            },
+        ];
+
+        let presets = CapturePreferences::default().export_presets;
+        let targets = resolve_targets(
+            &[
+                BatchTarget {
+                    preset: Some("Docs 1200px".to_string()),
+                    options: None,
+                },
+                BatchTarget {
+                    preset: None,
+                    options: Some(ExportOptions {
+                        format: ExportFormat::Jpeg,
+                        resize: Some(ResizeOptions::MaxSize {
+                            max_width: Some(800),
+                            max_height: None,
+                        }),
+                        ..Default::default()
+                    }),
+                },
+            ],
+            &presets,
+        )
+        .unwrap();
+
+        let progress = AtomicUsize::new(0);
+        let out = dir.join("out");
+        let report = run_batch(
+            "job",
+            &sources,
+            &targets,
+            &out,
+            "{name}-{mode}-{target}",
+            false,
+            &AtomicBool::new(false),
+            &|_| {
+                progress.fetch_add(1, Ordering::SeqCst);
+            },
+        )
+        .unwrap();
+
+        assert_eq!(progress.load(Ordering::SeqCst), 4);
+        assert_eq!((report.succeeded, report.failed, report.cancelled), (2, 2, false));
+        assert_eq!(
+            report.results[1].output_path.as_deref(),
+            Some(out.join("shot-region-jpg.jpg").to_string_lossy().as_ref())
+        );
+        let jpeg = image::open(out.join("shot-region-jpg.jpg")).unwrap();
+        assert_eq!((jpeg.width(), jpeg.height()), (800, 200));
+        assert!(report.results[2].error.is_some());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,408 @@
+//! Screenshot beautifier
+//!
+//! Frames a capture for docs and social posts: padding over a solid or
+//! gradient background, anti-aliased rounded corners, a soft drop shadow
+//! and optional window chrome. Produces a new image; the capture itself is
+//! never changed.
+
+use crate::annotate;
+use crate::error::{GrabError, GrabResult};
+use crate::types::{
+    BeautifyBackground, BeautifyStyle, ChromeStyle, Color, ExportOptions, WindowChrome,
+};
+use image::RgbaImage;
+use tiny_skia::{
+    FillRule, FilterQuality, GradientStop, LinearGradient, Paint, PathBuilder, Pattern, Pixmap,
+    PixmapPaint, Rect, SpreadMode, Stroke, Transform,
+};
+
+/// Box blur passes approximating a gaussian shadow
+const BLUR_PASSES: usize = 3;
+
+/// Find a style by name (case-insensitive)
+pub fn find_style<'a>(styles: &'a [BeautifyStyle], name: &str) -> Option<&'a BeautifyStyle> {
+    styles
+        .iter()
+        .find(|s| s.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Frame an export with the named style
+pub fn use_style(
+    options: &mut ExportOptions,
+    styles: &[BeautifyStyle],
+    name: &str,
+) -> GrabResult<()> {
+    let style = find_style(styles, name)
+        .ok_or_else(|| GrabError::InvalidRequest(format!("Unknown beautify style: {}", name)))?;
+    options.beautify = Some(style.clone());
+    Ok(())
+}
+
+/// Frame an image with a style
+///
+/// `scale_factor` is the capture's display scale; the style's sizes are
+/// multiplied by it so a style looks the same on every display.
+pub fn apply(image: &RgbaImage, style: &BeautifyStyle, scale_factor: f64) -> GrabResult<RgbaImage> {
+    let scale = if scale_factor.is_finite() && scale_factor > 0.0 {
+        scale_factor as f32
+    } else {
+        1.0
+    };
+    let content = with_chrome(image, style.window_chrome.as_ref(), scale)?;
+
+    let padding = (style.padding as f32 * scale).round() as u32;
+    let (width, height) = (
+        content.width() + 2 * padding,
+        content.height() + 2 * padding,
+    );
+    let mut canvas = Pixmap::new(width, height)
+        .ok_or_else(|| GrabError::InvalidRequest("Beautified image is too large".to_string()))?;
+    fill_background(&mut canvas, &style.background);
+
+    let (x, y) = (padding as f32, padding as f32);
+    let (content_width, content_height) = (content.width() as f32, content.height() as f32);
+    let radius = style.corner_radius.max(0.0) * scale;
+
+    if let Some(ref shadow) = style.shadow {
+        let mut layer = Pixmap::new(width, height).ok_or_else(|| {
+            GrabError::InvalidRequest("Beautified image is too large".to_string())
+        })?;
+        if let Some(path) = annotate::rounded_rect(
+            x + shadow.offset_x * scale,
+            y + shadow.offset_y * scale,
+            content_width,
+            content_height,
+            radius,
+        ) {
+            let [r, g, b, a] = shadow.color.0;
+            let alpha = (a as f32 * shadow.opacity.clamp(0.0, 1.0)).round() as u8;
+            layer.fill_path(
+                &path,
+                &solid(Color([r, g, b, alpha])),
+                FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+        }
+        let blur_radius = (shadow.blur.max(0.0) * scale / 2.0).round() as usize;
+        blur(
+            layer.data_mut(),
+            width as usize,
+            height as usize,
+            blur_radius,
+        );
+        canvas.draw_pixmap(
+            0,
+            0,
+            layer.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+    }
+
+    // Clip the content to the rounded rectangle by filling it with the
+    // content as a pattern, which keeps the corners anti-aliased
+    if let Some(path) = annotate::rounded_rect(x, y, content_width, content_height, radius) {
+        let paint = Paint {
+            shader: Pattern::new(
+                content.as_ref(),
+                SpreadMode::Pad,
+                FilterQuality::Nearest,
+                1.0,
+                Transform::from_translate(x, y),
+            ),
+            anti_alias: true,
+            ..Default::default()
+        };
+        canvas.fill_path(
+            &path,
+            &paint,
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+    }
+
+    Ok(annotate::from_pixmap(&canvas))
+}
+
+fn fill_background(canvas: &mut Pixmap, background: &BeautifyBackground) {
+    match background {
+        BeautifyBackground::Solid { color } => canvas.fill(skia_color(*color)),
+        BeautifyBackground::LinearGradient { from, to, angle } => {
+            // Like CSS: the gradient line passes through the center and is
+            // long enough for the corners to get the end colours
+            let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+            let (sin, cos) = angle.to_radians().sin_cos();
+            let half = (width * sin.abs() + height * cos.abs()) / 2.0;
+            let (cx, cy) = (width / 2.0, height / 2.0);
+            let shader = LinearGradient::new(
+                tiny_skia::Point::from_xy(cx - sin * half, cy + cos * half),
+                tiny_skia::Point::from_xy(cx + sin * half, cy - cos * half),
+                vec![
+                    GradientStop::new(0.0, skia_color(*from)),
+                    GradientStop::new(1.0, skia_color(*to)),
+                ],
+                SpreadMode::Pad,
+                Transform::identity(),
+            );
+            let paint = match shader {
+                Some(shader) => Paint {
+                    shader,
+                    ..Default::default()
+                },
+                // Degenerate gradient, e.g. both colours the same
+                None => solid(*from),
+            };
+            if let Some(rect) = Rect::from_xywh(0.0, 0.0, width, height) {
+                canvas.fill_rect(rect, &paint, Transform::identity(), None);
+            }
+        }
+        BeautifyBackground::Transparent => {}
+    }
+}
+
+/// The image with a title bar above it, or just the image
+fn with_chrome(image: &RgbaImage, chrome: Option<&WindowChrome>, scale: f32) -> GrabResult<Pixmap> {
+    let source = annotate::to_pixmap(image)?;
+    let Some(chrome) = chrome else {
+        return Ok(source);
+    };
+
+    let bar = match chrome.style {
+        ChromeStyle::Macos => 28.0 * scale,
+        ChromeStyle::Windows => 32.0 * scale,
+    }
+    .round();
+    let mut content = Pixmap::new(image.width(), image.height() + bar as u32)
+        .ok_or_else(|| GrabError::InvalidRequest("Image is too large".to_string()))?;
+    if let Some(rect) = Rect::from_xywh(0.0, 0.0, image.width() as f32, bar) {
+        content.fill_rect(rect, &solid(chrome.color), Transform::identity(), None);
+    }
+
+    match chrome.style {
+        ChromeStyle::Macos => {
+            let lights = [
+                Color([255, 95, 87, 255]),
+                Color([254, 188, 46, 255]),
+                Color([40, 200, 64, 255]),
+            ];
+            for (i, color) in lights.into_iter().enumerate() {
+                let cx = (14.0 + 20.0 * i as f32) * scale;
+                if let Some(circle) = PathBuilder::from_circle(cx, bar / 2.0, 6.0 * scale) {
+                    content.fill_path(
+                        &circle,
+                        &solid(color),
+                        FillRule::Winding,
+                        Transform::identity(),
+                        None,
+                    );
+                }
+            }
+        }
+        ChromeStyle::Windows => {
+            let [r, g, b, _] = chrome.color.0;
+            let light = 299 * r as u32 + 587 * g as u32 + 114 * b as u32 > 128_000;
+            let glyph = if light {
+                Color([32, 32, 32, 255])
+            } else {
+                Color([240, 240, 240, 255])
+            };
+            let stroke = Stroke {
+                width: scale,
+                ..Default::default()
+            };
+            let (cell, size, cy) = (46.0 * scale, 10.0 * scale, bar / 2.0);
+            let right = image.width() as f32;
+
+            // Minimize, maximize and close, right to left
+            let mut builder = PathBuilder::new();
+            let close = right - cell / 2.0;
+            builder.move_to(close - size / 2.0, cy - size / 2.0);
+            builder.line_to(close + size / 2.0, cy + size / 2.0);
+            builder.move_to(close + size / 2.0, cy - size / 2.0);
+            builder.line_to(close - size / 2.0, cy + size / 2.0);
+            let maximize = right - cell * 1.5;
+            if let Some(rect) = Rect::from_xywh(maximize - size / 2.0, cy - size / 2.0, size, size)
+            {
+                builder.push_rect(rect);
+            }
+            let minimize = right - cell * 2.5;
+            builder.move_to(minimize - size / 2.0, cy);
+            builder.line_to(minimize + size / 2.0, cy);
+            if let Some(path) = builder.finish() {
+                content.stroke_path(&path, &solid(glyph), &stroke, Transform::identity(), None);
+            }
+        }
+    }
+
+    content.draw_pixmap(
+        0,
+        bar as i32,
+        source.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+    Ok(content)
+}
+
+fn solid(color: Color) -> Paint<'static> {
+    let [r, g, b, a] = color.0;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    paint
+}
+
+fn skia_color(color: Color) -> tiny_skia::Color {
+    let [r, g, b, a] = color.0;
+    tiny_skia::Color::from_rgba8(r, g, b, a)
+}
+
+/// Approximate gaussian blur of premultiplied RGBA, transparent outside
+fn blur(data: &mut [u8], width: usize, height: usize, radius: usize) {
+    if radius == 0 {
+        return;
+    }
+    let mut scratch = vec![0u8; data.len()];
+    for _ in 0..BLUR_PASSES {
+        box_blur(data, &mut scratch, width, height, 4, width * 4, radius);
+        box_blur(&scratch, data, height, width, width * 4, 4, radius);
+    }
+}
+
+/// One box blur pass over `lines` lines of `len` pixels
+///
+/// `step` is the byte distance between pixels of a line and `line_step`
+/// between the starts of consecutive lines, so the same code runs across
+/// rows and down columns.
+fn box_blur(
+    src: &[u8],
+    dst: &mut [u8],
+    len: usize,
+    lines: usize,
+    step: usize,
+    line_step: usize,
+    radius: usize,
+) {
+    let window = (2 * radius + 1) as u32;
+    for line in 0..lines {
+        let start = line * line_step;
+        for channel in 0..4 {
+            let at = |i: usize| src[start + i * step + channel] as u32;
+            let mut sum: u32 = (0..radius.min(len)).map(at).sum();
+            for i in 0..len {
+                if i + radius < len {
+                    sum += at(i + radius);
+                }
+                dst[start + i * step + channel] = ((sum + window / 2) / window) as u8;
+                if i >= radius {
+                    sum -= at(i - radius);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ShadowStyle;
+    use image::Rgba;
+
+    fn style() -> BeautifyStyle {
+        BeautifyStyle {
+            name: "Test".to_string(),
+            padding: 20,
+            background: BeautifyBackground::Solid {
+                color: Color::WHITE,
+            },
+            corner_radius: 10.0,
+            shadow: Some(ShadowStyle {
+                color: Color([0, 0, 0, 255]),
+                blur: 8.0,
+                offset_x: 0.0,
+                offset_y: 6.0,
+                opacity: 0.5,
+            }),
+            window_chrome: Some(WindowChrome {
+                style: ChromeStyle::Macos,
+                color: Color([232, 232, 232, 255]),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_apply_frames_capture() {
+        let image = RgbaImage::from_pixel(100, 60, Rgba([200, 0, 0, 255]));
+        let framed = apply(&image, &style(), 1.0).unwrap();
+
+        // Padding on every side, and a 28px title bar
+        assert_eq!(framed.dimensions(), (140, 128));
+        // The capture itself is unchanged inside the frame
+        assert_eq!(framed.get_pixel(70, 90).0, [200, 0, 0, 255]);
+        // Title bar with the first traffic light
+        assert_eq!(framed.get_pixel(80, 30).0, [232, 232, 232, 255]);
+        assert_eq!(framed.get_pixel(34, 34).0, [255, 95, 87, 255]);
+        // Shadow below the content, fading out
+        let near = framed.get_pixel(70, 112).0[0];
+        let far = framed.get_pixel(70, 126).0[0];
+        assert!(near < 220 && near < far);
+        assert_eq!(framed.get_pixel(2, 2).0, [255, 255, 255, 255]);
+
+        // Rounded corners show the background, with anti-aliased edges
+        let framed = apply(
+            &image,
+            &BeautifyStyle {
+                shadow: None,
+                ..style()
+            },
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(framed.get_pixel(20, 20).0, [255, 255, 255, 255]);
+        assert!((20..30).any(|i| {
+            let [r, ..] = framed.get_pixel(i, i).0;
+            r > 232 && r < 255
+        }));
+
+        // Sizes follow the scale factor
+        let framed = apply(&image, &style(), 2.0).unwrap();
+        assert_eq!(framed.dimensions(), (180, 196));
+    }
+
+    #[test]
+    fn test_gradient_and_transparent_backgrounds() {
+        let image = RgbaImage::from_pixel(40, 40, Rgba([0, 0, 0, 255]));
+        let mut style = BeautifyStyle {
+            background: BeautifyBackground::LinearGradient {
+                from: Color([255, 0, 0, 255]),
+                to: Color([0, 0, 255, 255]),
+                angle: 90.0,
+            },
+            corner_radius: 0.0,
+            shadow: None,
+            window_chrome: None,
+            ..style()
+        };
+        let framed = apply(&image, &style, 1.0).unwrap();
+        let left = framed.get_pixel(0, 40).0;
+        let right = framed.get_pixel(79, 40).0;
+        assert!(left[0] > 240 && left[2] < 15);
+        assert!(right[2] > 240 && right[0] < 15);
+
+        style.background = BeautifyBackground::Transparent;
+        let framed = apply(&image, &style, 1.0).unwrap();
+        assert_eq!(framed.get_pixel(5, 5).0[3], 0);
+        assert_eq!(framed.get_pixel(20, 20).0, [0, 0, 0, 255]);
+
+        let styles = crate::types::CapturePreferences::default().beautify_styles;
+        assert!(find_style(&styles, " social ").is_some());
+        let mut options = ExportOptions::default();
+        use_style(&mut options, &styles, "Social").unwrap();
+        assert!(options.beautify.is_some());
+        assert!(use_style(&mut options, &styles, "Missing").is_err());
+    }
+}
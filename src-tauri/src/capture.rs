@@ -4,10 +4,10 @@
 //! Optimized for performance with fast PNG compression.
 
 use crate::error::{GrabError, GrabResult};
+use crate::metadata;
 use crate::types::{CaptureMetadata, CaptureMode, CaptureSource, RegionBounds};
-use chrono::Utc;
-use image::codecs::png::{CompressionType, FilterType, PngEncoder};
-use image::{ImageEncoder, RgbaImage};
+use chrono::{DateTime, Utc};
+use image::RgbaImage;
 use std::fs::File;
 use std::io::BufWriter;
 use std::path::PathBuf;
@@ -39,6 +39,14 @@ pub fn capture_display(display_id: &str) -> GrabResult<(RgbaImage, CaptureMetada
     capture_monitor(&monitor)
 }
 
+/// Capture the monitor containing a point in global screen coordinates
+pub fn capture_monitor_at(x: i32, y: i32) -> GrabResult<(RgbaImage, CaptureMetadata)> {
+    let monitor = Monitor::from_point(x, y)
+        .map_err(|_| GrabError::SourceNotFound(format!("No display at {},{}", x, y)))?;
+
+    capture_monitor(&monitor)
+}
+
 /// Capture a specific monitor
 fn capture_monitor(monitor: &Monitor) -> GrabResult<(RgbaImage, CaptureMetadata)> {
     let image = monitor
@@ -58,6 +66,11 @@ fn capture_monitor(monitor: &Monitor) -> GrabResult<(RgbaImage, CaptureMetadata)
         timestamp: Utc::now().to_rfc3339(),
         scale_factor: monitor.scale_factor().unwrap_or(1.0) as f64,
         file_name: None,
+        window_title: None,
+        app_name: None,
+        redactions: Vec::new(),
+        trim: None,
+        tags: Vec::new(),
     };
 
     Ok((image, metadata))
@@ -89,11 +102,46 @@ pub fn capture_window(window_id: &str) -> GrabResult<(RgbaImage, CaptureMetadata
         timestamp: Utc::now().to_rfc3339(),
         scale_factor: 1.0, // Windows don't have individual scale factors
         file_name: None,
+        window_title: window.title().ok().filter(|t| !t.is_empty()),
+        app_name: window.app_name().ok().filter(|n| !n.is_empty()),
+        redactions: Vec::new(),
+        trim: None,
+        tags: Vec::new(),
     };
 
     Ok((image, metadata))
 }
 
+/// Find a window by its title or app name
+///
+/// An exact title wins; otherwise the first window whose title or app name
+/// contains `query`, ignoring case. Returns the window ID.
+pub fn find_window(query: &str) -> GrabResult<String> {
+    let windows = Window::all().map_err(|e| GrabError::CaptureFailed(e.to_string()))?;
+    let needle = query.to_lowercase();
+
+    let mut partial = None;
+    for window in &windows {
+        if window.width().unwrap_or(0) == 0 || window.height().unwrap_or(0) == 0 {
+            continue;
+        }
+        let id = window.id().map(|id| id.to_string()).unwrap_or_default();
+        let title = window.title().unwrap_or_default();
+        if title == query {
+            return Ok(id);
+        }
+        let app_name = window.app_name().unwrap_or_default();
+        if partial.is_none()
+            && (title.to_lowercase().contains(&needle)
+                || app_name.to_lowercase().contains(&needle))
+        {
+            partial = Some(id);
+        }
+    }
+
+    partial.ok_or_else(|| GrabError::SourceNotFound(format!("No window matching {}", query)))
+}
+
 /// Capture a region of the screen
 pub fn capture_region(
     region: &RegionBounds,
@@ -189,42 +237,80 @@ pub fn get_window_sources() -> GrabResult<Vec<CaptureSource>> {
 
 /// Generate a filename based on the naming template
 pub fn generate_filename(template: &str, mode: CaptureMode) -> String {
-    let now = Utc::now();
+    expand_template(template, Some(Utc::now()), Some(mode))
+}
+
+/// Expand the `{date}`, `{time}`, `{timestamp}`, `{mode}` and `{user}`
+/// tokens shared by naming templates and watermarks
+///
+/// Tokens for an unknown time or mode expand to nothing.
+pub fn expand_template(template: &str, time: Option<DateTime<Utc>>, mode: Option<CaptureMode>) -> String {
+    let format_time = |fmt: &str| time.map(|t| t.format(fmt).to_string()).unwrap_or_default();
+
+    template
+        .replace("{date}", &format_time("%Y-%m-%d"))
+        .replace("{time}", &format_time("%H-%M-%S"))
+        .replace("{timestamp}", &format_time("%s"))
+        .replace("{mode}", mode.map(mode_name).unwrap_or(""))
+        .replace("{user}", &user_name())
+}
 
-    let mode_str = match mode {
+/// Name of the logged-in user
+fn user_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+/// Short name for a capture mode, as used in file names
+pub fn mode_name(mode: CaptureMode) -> &'static str {
+    match mode {
         CaptureMode::FullScreen => "fullscreen",
         CaptureMode::Display => "display",
         CaptureMode::Window => "window",
         CaptureMode::Region => "region",
-    };
-
-    template
-        .replace("{date}", &now.format("%Y-%m-%d").to_string())
-        .replace("{time}", &now.format("%H-%M-%S").to_string())
-        .replace("{mode}", mode_str)
-        .replace("{timestamp}", &now.timestamp().to_string())
+        CaptureMode::Composite => "composite",
+    }
 }
 
 /// Save image to disk with optimized PNG compression
 ///
 /// Uses fastest possible encoding (no filter) for maximum speed.
 /// PNG is lossless regardless of settings, so quality is identical.
-pub fn save_image(image: &RgbaImage, path: &PathBuf) -> GrabResult<()> {
+/// File size is recovered afterwards by the background optimizer.
+///
+/// When `metadata` is given it is embedded as PNG text chunks.
+pub fn save_image(
+    image: &RgbaImage,
+    path: &PathBuf,
+    metadata: Option<&CaptureMetadata>,
+) -> GrabResult<()> {
+    let png_err = |e: png::EncodingError| GrabError::ExportFailed(e.to_string());
+
     let file = File::create(path)?;
     let writer = BufWriter::with_capacity(1024 * 1024, file); // 1MB buffer
-    
+
+    let mut encoder = png::Encoder::new(writer, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Fast);
     // NoFilter is MUCH faster than Adaptive - filters are the slowest part of PNG
-    let encoder = PngEncoder::new_with_quality(writer, CompressionType::Fast, FilterType::NoFilter);
-    
-    encoder
-        .write_image(
-            image.as_raw(),
-            image.width(),
-            image.height(),
-            image::ExtendedColorType::Rgba8,
-        )
-        .map_err(|e| GrabError::Image(e))?;
-    
+    encoder.set_filter(png::Filter::NoFilter);
+
+    if let Some(metadata) = metadata {
+        for chunk in metadata::png_text_chunks(metadata) {
+            if chunk.utf8 {
+                encoder.add_itxt_chunk(chunk.keyword, chunk.text).map_err(png_err)?;
+            } else {
+                encoder.add_text_chunk(chunk.keyword, chunk.text).map_err(png_err)?;
+            }
+        }
+    }
+
+    let mut writer = encoder.write_header().map_err(png_err)?;
+    writer.write_image_data(image.as_raw()).map_err(png_err)?;
+    writer.finish().map_err(png_err)?;
+
     Ok(())
 }
 
@@ -240,4 +326,16 @@ mod tests {
         assert!(filename.starts_with("grab-"));
         assert!(filename.contains("fullscreen"));
     }
+
+    #[test]
+    fn test_expand_template() {
+        let time = DateTime::parse_from_rfc3339("2026-03-14T09:26:53Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            expand_template("{date} {time} {timestamp} {mode}", Some(time), Some(CaptureMode::Region)),
+            "2026-03-14 09-26-53 1773480413 region"
+        );
+        assert_eq!(expand_template("[{date}{mode}]", None, None), "[]");
+    }
 }
@@ -0,0 +1,853 @@
+//! Command-line interface
+//!
+//! `grab <command> ...` runs headless and exits. Anything that isn't a known
+//! command starts the GUI as usual.
+
+use crate::annotate;
+use crate::beautify;
+use crate::capture;
+use crate::diff;
+use crate::error::{GrabError, GrabResult};
+use crate::export;
+use crate::metadata;
+use crate::ocr;
+use crate::pdf;
+use crate::preferences;
+use crate::project;
+use crate::redact;
+use crate::secrets;
+use crate::types::{
+    Annotation, CapturePreferences, DiffOptions, ExportFormat, ExportOptions, PdfLayout, PdfOptions,
+    Redaction, RegionBounds, ResizeOptions,
+};
+use crate::watermark;
+use image::DynamicImage;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Bundle identifier, which names the app data directory
+const APP_IDENTIFIER: &str = "com.akv004.grab";
+
+/// Exit code of `verify` when the capture differs from the baseline
+const VERIFY_FAILED: i32 = 1;
+/// Exit code of `verify` when it couldn't compare at all, so CI can tell a
+/// broken job from a regression
+const VERIFY_ERROR: i32 = 2;
+
+const USAGE: &str = "\
+Usage: grab <command> [options]
+
+Commands:
+  export <input>      Export an image or .grab project with resize, format and DPI options
+  pdf <inputs...>     Bundle images into a multi-page PDF
+  diff <before> <after>
+                      Compare two images and write a diff image
+  verify              Capture and compare against a baseline image
+  help                Show this message
+
+Export options:
+  -o, --output <path>     Output file or directory
+  --preset <name>         Start from a named export preset
+  --format <fmt>          png, jpeg or webp
+  --quality <1-100>       JPEG quality
+  --max-width <px>        Shrink to at most this width
+  --max-height <px>       Shrink to at most this height
+  --scale <percent>       Scale by a percentage
+  --size <WxH>            Resize to an exact size
+  --fit <mode>            fit, fill or pad (with --size)
+  --logical               Downscale HiDPI captures to 1x
+  --filter <name>         nearest, bilinear, catmull-rom, gaussian or lanczos3
+  --dpi <dpi>             Resolution to record in the file
+  --background <color>    Padding/flattening colour, e.g. #ffffff
+  --trim                  Crop uniform margins first
+  --style <name>          Frame with a named beautify style
+  --watermark <text>      Stamp text, e.g. \"INTERNAL {date}\"
+  --no-watermark          Skip the watermark from preferences
+  --redactions <file>     Redact regions from a JSON file first
+  --safe-share            Also redact detected secrets (needs tesseract)
+  --annotations <file>    Draw annotations from a JSON file first
+
+PDF options:
+  -o, --output <path>     Output file (default: captures.pdf)
+  --page-size <size>      a4, letter or fit
+  --grid <CxR>            Columns and rows per page
+  --caption <kind>        none, file-name or timestamp
+  --encoding <kind>       flate or jpeg
+  --quality <1-100>       JPEG quality
+
+Diff options:
+  -o, --output <path>     Diff image (default: diff.png)
+  --mode <mode>           highlight, side-by-side or slider
+  --tolerance <0-255>     Channel difference still counted as the same
+  --no-align              Don't look for a scroll offset
+  --max-offset <px>       Largest scroll offset to look for
+  --color <color>         Highlight colour, e.g. #ff0050
+  --json                  Print the result as JSON
+
+Verify options:
+  --baseline <path>       Reference image (required)
+  --window <title>        Capture a window by title or app name
+  --display <id>          Capture a display (default: the primary one)
+  --region <x,y,w,h>      Capture part of the screen
+  --input <path>          Check an existing image instead of capturing
+  --threshold <percent>   Changed pixels allowed, e.g. 0.5% (default 0)
+  --tolerance <0-255>     Channel difference still counted as the same
+  --align                 Undo a scroll offset before comparing
+  --max-offset <px>       Largest scroll offset to look for
+  --ignore <x,y,w,h>      Leave a region out of the comparison; repeatable
+  --ignore-file <file>    Regions to leave out, from a JSON file
+  --diff <path>           Diff image on failure (default: next to the baseline)
+  --update                Save the capture as the new baseline
+
+verify exits with 1 when the capture differs by more than the threshold,
+and with 2 when it can't compare at all (bad arguments, missing baseline,
+failed capture).
+
+With no command, grab starts normally.";
+
+/// Run a CLI command
+///
+/// Returns the exit code, or `None` if the arguments don't name a command
+/// and the GUI should start.
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+
+    let (result, error_code) = match command.as_str() {
+        "export" => (export_command(rest).map(|()| 0), 1),
+        "pdf" => (pdf_command(rest).map(|()| 0), 1),
+        "diff" => (diff_command(rest).map(|()| 0), 1),
+        "verify" => (verify_command(rest), VERIFY_ERROR),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            return Some(0);
+        }
+        _ => return None,
+    };
+
+    match result {
+        Ok(code) => Some(code),
+        Err(e) => {
+            eprintln!("grab: {}", e);
+            Some(error_code)
+        }
+    }
+}
+
+/// App data directory, matching the one Tauri uses for this bundle
+fn app_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(APP_IDENTIFIER)
+}
+
+/// Parsed `grab export` arguments
+#[derive(Debug)]
+struct ExportArgs {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    preset: Option<String>,
+    options: ExportOptions,
+    redactions: Option<PathBuf>,
+    safe_share: bool,
+    annotations: Option<PathBuf>,
+}
+
+fn export_command(args: &[String]) -> GrabResult<()> {
+    let preferences = preferences::load_preferences(&app_data_dir());
+    let args = parse_export_args(args, &preferences)?;
+
+    // Projects are flattened: redacted, visible layers drawn, then cropped
+    let (mut image, mut source_metadata) = if project::is_project(&args.input) {
+        let contents = project::read_project(&args.input)?;
+        let flattened = project::flatten(&contents.project, &contents.original()?)?;
+        let metadata = redact::record(&contents.project.metadata, &contents.project.redactions);
+        (DynamicImage::ImageRgba8(flattened), Some(metadata))
+    } else {
+        let bytes = std::fs::read(&args.input)?;
+        (image::load_from_memory(&bytes)?, metadata::read_embedded(&bytes))
+    };
+
+    let mut redactions: Vec<Redaction> = match args.redactions {
+        Some(ref path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+        None => Vec::new(),
+    };
+    if args.safe_share {
+        let settings = &preferences.secret_scan;
+        let findings = secrets::scan_image(
+            &ocr::Tesseract::new(&preferences.ocr),
+            &image.to_rgba8(),
+            settings,
+            &preferences.ocr.languages,
+        )?;
+        for finding in &findings {
+            eprintln!("redacting {} ({})", finding.rule, finding.preview);
+        }
+        redactions.extend(secrets::to_redactions(&findings, settings.method));
+    }
+    if !redactions.is_empty() {
+        image = DynamicImage::ImageRgba8(redact::apply(&image.to_rgba8(), &redactions)?);
+        source_metadata = source_metadata.map(|m| redact::record(&m, &redactions));
+    }
+
+    if let Some(ref path) = args.annotations {
+        let annotations: Vec<Annotation> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        image = DynamicImage::ImageRgba8(annotate::render(&image.to_rgba8(), &annotations)?);
+    }
+
+    let scale_factor = source_metadata.as_ref().map(|m| m.scale_factor).unwrap_or(1.0);
+    let embedded = source_metadata
+        .as_ref()
+        .filter(|_| !preferences.strip_metadata);
+
+    let output = output_path(&args);
+    export::export_to_file(&image, &args.options, embedded, scale_factor, &output)?;
+
+    println!("{}", output.display());
+    Ok(())
+}
+
+fn parse_export_args(args: &[String], preferences: &CapturePreferences) -> GrabResult<ExportArgs> {
+    let mut input = None;
+    let mut output = None;
+    let mut preset = None;
+    let mut format = None;
+    let mut quality = None;
+    let mut max_width = None;
+    let mut max_height = None;
+    let mut scale = None;
+    let mut size = None;
+    let mut fit = None;
+    let mut logical = false;
+    let mut filter = None;
+    let mut dpi = None;
+    let mut background = None;
+    let mut style = None;
+    let mut watermark_text = None;
+    let mut no_watermark = false;
+    let mut redactions = None;
+    let mut safe_share = false;
+    let mut annotations = None;
+    let mut trim = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| GrabError::InvalidRequest(format!("{} needs a value", arg)))
+        };
+
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "--preset" => preset = Some(value()?),
+            "--format" => {
+                let name = value()?;
+                format = Some(ExportFormat::from_name(&name).ok_or_else(|| {
+                    GrabError::InvalidRequest(format!("Unknown format: {}", name))
+                })?);
+            }
+            "--quality" => quality = Some(parse_number::<u8>(arg, &value()?)?),
+            "--max-width" => max_width = Some(parse_number::<u32>(arg, &value()?)?),
+            "--max-height" => max_height = Some(parse_number::<u32>(arg, &value()?)?),
+            "--scale" => {
+                let percent = value()?;
+                scale = Some(parse_number::<f32>(arg, percent.trim_end_matches('%'))?);
+            }
+            "--size" => size = Some(parse_size(arg, &value()?)?),
+            "--fit" => fit = Some(parse_name(arg, &value()?)?),
+            "--logical" => logical = true,
+            "--filter" => filter = Some(parse_name(arg, &value()?)?),
+            "--dpi" => dpi = Some(parse_number::<u32>(arg, &value()?)?),
+            "--background" => background = Some(parse_name(arg, &value()?)?),
+            "--style" => style = Some(value()?),
+            "--watermark" => watermark_text = Some(value()?),
+            "--no-watermark" => no_watermark = true,
+            "--redactions" => redactions = Some(PathBuf::from(value()?)),
+            "--safe-share" => safe_share = true,
+            "--annotations" => annotations = Some(PathBuf::from(value()?)),
+            "--trim" => trim = true,
+            flag if flag.starts_with('-') => {
+                return Err(GrabError::InvalidRequest(format!("Unknown option: {}", flag)));
+            }
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => {
+                return Err(GrabError::InvalidRequest(format!(
+                    "Unexpected argument: {}",
+                    arg
+                )));
+            }
+        }
+    }
+
+    let input =
+        input.ok_or_else(|| GrabError::InvalidRequest("No input file given".to_string()))?;
+
+    let mut options = match preset {
+        Some(ref name) => export::find_preset(&preferences.export_presets, name)
+            .map(|p| p.options.clone())
+            .ok_or_else(|| GrabError::InvalidRequest(format!("Unknown export preset: {}", name)))?,
+        None => ExportOptions::default(),
+    };
+
+    // Explicit flags override the preset
+    let resizes = [
+        max_width.is_some() || max_height.is_some(),
+        scale.is_some(),
+        size.is_some(),
+        logical,
+    ];
+    if resizes.iter().filter(|r| **r).count() > 1 {
+        return Err(GrabError::InvalidRequest(
+            "Use only one of --max-width/--max-height, --scale, --size and --logical".to_string(),
+        ));
+    }
+    if max_width.is_some() || max_height.is_some() {
+        options.resize = Some(ResizeOptions::MaxSize {
+            max_width,
+            max_height,
+        });
+    } else if let Some(percent) = scale {
+        options.resize = Some(ResizeOptions::Scale { percent });
+    } else if let Some((width, height)) = size {
+        options.resize = Some(ResizeOptions::Exact {
+            width,
+            height,
+            fit: fit.unwrap_or_default(),
+        });
+    } else if logical {
+        options.resize = Some(ResizeOptions::Logical);
+    }
+
+    if let Some(format) = format {
+        options.format = format;
+    } else if preset.is_none() {
+        // Follow the output extension when nothing else says otherwise
+        if let Some(format) = output
+            .as_ref()
+            .and_then(|p: &PathBuf| p.extension())
+            .and_then(|e| ExportFormat::from_name(&e.to_string_lossy()))
+        {
+            options.format = format;
+        }
+    }
+    options.quality = quality.or(options.quality);
+    options.filter = filter.unwrap_or(options.filter);
+    options.dpi = dpi.or(options.dpi);
+    options.background = background.or(options.background);
+    if let Some(name) = style {
+        beautify::use_style(&mut options, &preferences.beautify_styles, &name)?;
+    }
+    if trim {
+        // The auto-trim settings, if any, say how
+        options.trim = Some(preferences.auto_trim.clone().unwrap_or_default());
+    }
+    if let Some(text) = watermark_text {
+        options.watermark = Some(watermark::with_text(&preferences.watermark.watermark, text));
+    } else if no_watermark {
+        options.watermark = None;
+    } else {
+        watermark::use_default(&mut options, &preferences.watermark);
+    }
+
+    Ok(ExportArgs {
+        input,
+        output,
+        preset,
+        options,
+        redactions,
+        safe_share,
+        annotations,
+    })
+}
+
+/// Output file, defaulting to `<input>-<preset>.<ext>` next to the input
+fn output_path(args: &ExportArgs) -> PathBuf {
+    let stem = args
+        .input
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "capture".to_string());
+    let suffix = args
+        .preset
+        .as_deref()
+        .map(export::preset_slug)
+        .unwrap_or_else(|| "export".to_string());
+    let file_name = format!("{}-{}.{}", stem, suffix, args.options.format.extension());
+
+    match args.output {
+        Some(ref path) if path.is_dir() => path.join(file_name),
+        Some(ref path) => path.clone(),
+        None => args
+            .input
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(file_name),
+    }
+}
+
+fn pdf_command(args: &[String]) -> GrabResult<()> {
+    let mut inputs = Vec::new();
+    let mut output = PathBuf::from("captures.pdf");
+    let mut options = PdfOptions::default();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| GrabError::InvalidRequest(format!("{} needs a value", arg)))
+        };
+
+        match arg.as_str() {
+            "-o" | "--output" => output = PathBuf::from(value()?),
+            "--page-size" => options.page_size = parse_name(arg, &value()?)?,
+            "--grid" => {
+                let (columns, rows) = parse_size(arg, &value()?)?;
+                options.layout = PdfLayout::Grid { columns, rows };
+            }
+            "--caption" => options.caption = parse_name(arg, &value()?)?,
+            "--encoding" => options.encoding = parse_name(arg, &value()?)?,
+            "--quality" => options.quality = Some(parse_number(arg, &value()?)?),
+            flag if flag.starts_with('-') => {
+                return Err(GrabError::InvalidRequest(format!("Unknown option: {}", flag)));
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let images = inputs
+        .iter()
+        .map(|path| {
+            let bytes = std::fs::read(path)?;
+            let timestamp = metadata::read_embedded(&bytes).map(|m| m.timestamp);
+            Ok(pdf::PdfImage {
+                image: image::load_from_memory(&bytes)?,
+                caption: pdf::caption_for(options.caption, path, timestamp.as_deref()),
+            })
+        })
+        .collect::<GrabResult<Vec<_>>>()?;
+
+    std::fs::write(&output, pdf::write_pdf(&images, &options)?)?;
+
+    println!("{}", output.display());
+    Ok(())
+}
+
+/// Parsed `grab diff` arguments
+#[derive(Debug)]
+struct DiffArgs {
+    before: PathBuf,
+    after: PathBuf,
+    output: PathBuf,
+    options: DiffOptions,
+    json: bool,
+}
+
+fn diff_command(args: &[String]) -> GrabResult<()> {
+    let args = parse_diff_args(args)?;
+    let before = image::open(&args.before)?.to_rgba8();
+    let after = image::open(&args.after)?.to_rgba8();
+    let diff = diff::diff(&before, &after, &args.options)?;
+    diff.image.save(&args.output)?;
+
+    let result = &diff.result;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(result)?);
+        return Ok(());
+    }
+    println!(
+        "{}% changed in {} region(s)",
+        result.changed_percentage,
+        result.regions.len()
+    );
+    if (result.offset_x, result.offset_y) != (0, 0) {
+        println!("Aligned at an offset of {},{}", result.offset_x, result.offset_y);
+    }
+    for region in &result.regions {
+        println!(
+            "  {}x{} at {},{}",
+            region.width, region.height, region.x, region.y
+        );
+    }
+    println!("{}", args.output.display());
+    Ok(())
+}
+
+fn parse_diff_args(args: &[String]) -> GrabResult<DiffArgs> {
+    let mut inputs = Vec::new();
+    let mut output = PathBuf::from("diff.png");
+    let mut options = DiffOptions::default();
+    let mut json = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| GrabError::InvalidRequest(format!("{} needs a value", arg)))
+        };
+
+        match arg.as_str() {
+            "-o" | "--output" => output = PathBuf::from(value()?),
+            "--mode" => options.output = parse_name(arg, &value()?)?,
+            "--tolerance" => options.tolerance = parse_number(arg, &value()?)?,
+            "--no-align" => options.align = false,
+            "--max-offset" => options.max_offset = parse_number(arg, &value()?)?,
+            "--color" => options.color = parse_name(arg, &value()?)?,
+            "--json" => json = true,
+            flag if flag.starts_with('-') => {
+                return Err(GrabError::InvalidRequest(format!("Unknown option: {}", flag)));
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [before, after]: [PathBuf; 2] = inputs.try_into().map_err(|_| {
+        GrabError::InvalidRequest("diff needs a before and an after image".to_string())
+    })?;
+    Ok(DiffArgs {
+        before,
+        after,
+        output,
+        options,
+        json,
+    })
+}
+
+/// What `grab verify` checks
+#[derive(Debug, PartialEq)]
+enum VerifyTarget {
+    Screen,
+    Display(String),
+    /// Title or app name
+    Window(String),
+    Region(RegionBounds),
+    /// An image made some other way
+    File(PathBuf),
+}
+
+/// Parsed `grab verify` arguments
+#[derive(Debug)]
+struct VerifyArgs {
+    target: VerifyTarget,
+    baseline: PathBuf,
+    /// Changed pixels allowed, in percent
+    threshold: f64,
+    options: DiffOptions,
+    ignore_file: Option<PathBuf>,
+    diff_output: Option<PathBuf>,
+    update: bool,
+}
+
+/// Capture and compare against a baseline
+///
+/// Exits with `VERIFY_FAILED` when more pixels changed than the threshold
+/// allows, after writing a highlighted diff image. Errors exit with
+/// `VERIFY_ERROR`.
+fn verify_command(args: &[String]) -> GrabResult<i32> {
+    let mut args = parse_verify_args(args)?;
+    let image = match args.target {
+        VerifyTarget::Screen => capture::capture_full_screen()?.0,
+        VerifyTarget::Display(ref id) => capture::capture_display(id)?.0,
+        VerifyTarget::Window(ref query) => {
+            capture::capture_window(&capture::find_window(query)?)?.0
+        }
+        VerifyTarget::Region(ref region) => capture::capture_region(region, None)?.0,
+        VerifyTarget::File(ref path) => image::open(path)?.to_rgba8(),
+    };
+
+    if args.update {
+        if let Some(parent) = args.baseline.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        image.save(&args.baseline)?;
+        println!("Updated {}", args.baseline.display());
+        return Ok(0);
+    }
+    if !args.baseline.exists() {
+        return Err(GrabError::SourceNotFound(format!(
+            "{} (run with --update to create it)",
+            args.baseline.display()
+        )));
+    }
+
+    if let Some(ref path) = args.ignore_file {
+        let regions: Vec<RegionBounds> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        args.options.ignore.extend(regions);
+    }
+    let baseline = image::open(&args.baseline)?.to_rgba8();
+    let diff = diff::diff(&baseline, &image, &args.options)?;
+    let result = &diff.result;
+
+    // Unrounded, so a threshold of 0 catches a single pixel
+    let changed = if result.compared_pixels == 0 {
+        0.0
+    } else {
+        result.changed_pixels as f64 * 100.0 / result.compared_pixels as f64
+    };
+    if changed <= args.threshold {
+        println!("Passed: {}% changed", result.changed_percentage);
+        return Ok(0);
+    }
+
+    let diff_path = args.diff_output.clone().unwrap_or_else(|| {
+        let stem = args
+            .baseline
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "baseline".to_string());
+        args.baseline.with_file_name(format!("{}.diff.png", stem))
+    });
+    diff.image.save(&diff_path)?;
+
+    println!(
+        "Failed: {}% changed in {} region(s), over the {}% threshold",
+        result.changed_percentage,
+        result.regions.len(),
+        args.threshold
+    );
+    for region in &result.regions {
+        println!(
+            "  {}x{} at {},{}",
+            region.width, region.height, region.x, region.y
+        );
+    }
+    println!("{}", diff_path.display());
+    Ok(VERIFY_FAILED)
+}
+
+fn parse_verify_args(args: &[String]) -> GrabResult<VerifyArgs> {
+    let mut targets = Vec::new();
+    let mut baseline = None;
+    let mut threshold = 0.0;
+    // A shifted layout is a regression here, not something to undo
+    let mut options = DiffOptions {
+        align: false,
+        ..Default::default()
+    };
+    let mut ignore_file = None;
+    let mut diff_output = None;
+    let mut update = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| GrabError::InvalidRequest(format!("{} needs a value", arg)))
+        };
+
+        match arg.as_str() {
+            "--window" => targets.push(VerifyTarget::Window(value()?)),
+            "--display" => targets.push(VerifyTarget::Display(value()?)),
+            "--region" => targets.push(VerifyTarget::Region(parse_bounds(arg, &value()?)?)),
+            "--input" => targets.push(VerifyTarget::File(PathBuf::from(value()?))),
+            "--baseline" => baseline = Some(PathBuf::from(value()?)),
+            "--threshold" => {
+                threshold = parse_number(arg, value()?.trim_end_matches('%'))?;
+            }
+            "--tolerance" => options.tolerance = parse_number(arg, &value()?)?,
+            "--align" => options.align = true,
+            "--max-offset" => options.max_offset = parse_number(arg, &value()?)?,
+            "--ignore" => options.ignore.push(parse_bounds(arg, &value()?)?),
+            "--ignore-file" => ignore_file = Some(PathBuf::from(value()?)),
+            "--diff" => diff_output = Some(PathBuf::from(value()?)),
+            "--update" => update = true,
+            other => {
+                return Err(GrabError::InvalidRequest(format!("Unknown option: {}", other)));
+            }
+        }
+    }
+
+    if targets.len() > 1 {
+        return Err(GrabError::InvalidRequest(
+            "Choose one of --window, --display, --region or --input".to_string(),
+        ));
+    }
+    let baseline =
+        baseline.ok_or_else(|| GrabError::InvalidRequest("verify needs --baseline".to_string()))?;
+    Ok(VerifyArgs {
+        target: targets.pop().unwrap_or(VerifyTarget::Screen),
+        baseline,
+        threshold,
+        options,
+        ignore_file,
+        diff_output,
+        update,
+    })
+}
+
+/// Parse a `WxH` pair
+fn parse_size(flag: &str, value: &str) -> GrabResult<(u32, u32)> {
+    let (w, h) = value.split_once(['x', 'X']).ok_or_else(|| {
+        GrabError::InvalidRequest(format!("Expected WxH for {}, got {}", flag, value))
+    })?;
+    Ok((parse_number(flag, w)?, parse_number(flag, h)?))
+}
+
+/// Parse an `x,y,width,height` region
+fn parse_bounds(flag: &str, value: &str) -> GrabResult<RegionBounds> {
+    let parts: Vec<&str> = value.split(',').collect();
+    let [x, y, width, height] = parts[..] else {
+        return Err(GrabError::InvalidRequest(format!(
+            "Expected x,y,width,height for {}, got {}",
+            flag, value
+        )));
+    };
+    Ok(RegionBounds {
+        x: parse_number(flag, x)?,
+        y: parse_number(flag, y)?,
+        width: parse_number(flag, width)?,
+        height: parse_number(flag, height)?,
+    })
+}
+
+fn parse_number<T: FromStr>(flag: &str, value: &str) -> GrabResult<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| GrabError::InvalidRequest(format!("Invalid value for {}: {}", flag, value)))
+}
+
+/// Parse a value using its serde name (e.g. `catmull-rom`, `#ffffff`)
+fn parse_name<T: DeserializeOwned>(flag: &str, value: &str) -> GrabResult<T> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| GrabError::InvalidRequest(format!("Invalid value for {}: {}", flag, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DiffOutput, FitMode, ResampleFilter, TrimOptions, WatermarkContent};
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_export_args() {
+        let preferences = CapturePreferences::default();
+
+        let parsed = parse_export_args(
+            &args("shot.png --size 800x600 --fit pad --filter catmull-rom -o out.jpg"),
+            &preferences,
+        )
+        .unwrap();
+        assert_eq!(parsed.options.format, ExportFormat::Jpeg);
+        assert_eq!(parsed.options.filter, ResampleFilter::CatmullRom);
+        assert_eq!(
+            parsed.options.resize,
+            Some(ResizeOptions::Exact {
+                width: 800,
+                height: 600,
+                fit: FitMode::Pad,
+            })
+        );
+
+        // Flags override the preset's settings
+        let mut preset_args = args("shot.png --dpi 300 --preset");
+        preset_args.push("Slack 2x".to_string());
+        let parsed = parse_export_args(&preset_args, &preferences).unwrap();
+        assert_eq!(parsed.options.dpi, Some(300));
+        assert_eq!(output_path(&parsed), PathBuf::from("shot-slack-2x.png"));
+
+        let parsed = parse_export_args(&args("shot.png --style social"), &preferences).unwrap();
+        assert_eq!(parsed.options.beautify.unwrap().name, "Social");
+
+        let parsed = parse_export_args(&args("shot.png --trim"), &preferences).unwrap();
+        assert_eq!(parsed.options.trim, Some(TrimOptions::default()));
+
+        let parsed = parse_export_args(&args("shot.png --no-watermark"), &preferences).unwrap();
+        assert!(parsed.options.watermark.is_none());
+        let mut watermark_args = args("shot.png --watermark");
+        watermark_args.push("INTERNAL {user}".to_string());
+        let parsed = parse_export_args(&watermark_args, &preferences).unwrap();
+        assert!(matches!(
+            parsed.options.watermark.unwrap().content,
+            WatermarkContent::Text { ref text, .. } if text == "INTERNAL {user}"
+        ));
+
+        assert!(parse_export_args(&args("shot.png --preset Missing"), &preferences).is_err());
+        assert!(parse_export_args(&args("shot.png --style Missing"), &preferences).is_err());
+        assert!(parse_export_args(&args("shot.png --scale 50 --logical"), &preferences).is_err());
+    }
+
+    #[test]
+    fn test_parse_diff_args() {
+        let parsed =
+            parse_diff_args(&args("a.png b.png --mode side-by-side --tolerance 4 --no-align"))
+                .unwrap();
+        assert_eq!(parsed.before, PathBuf::from("a.png"));
+        assert_eq!(parsed.after, PathBuf::from("b.png"));
+        assert_eq!(parsed.output, PathBuf::from("diff.png"));
+        assert_eq!(parsed.options.output, DiffOutput::SideBySide);
+        assert_eq!(parsed.options.tolerance, 4);
+        assert!(!parsed.options.align);
+
+        assert!(parse_diff_args(&args("a.png")).is_err());
+        assert!(parse_diff_args(&args("a.png b.png c.png")).is_err());
+        assert!(parse_diff_args(&args("a.png b.png --mode wipe")).is_err());
+    }
+
+    #[test]
+    fn test_parse_verify_args() {
+        let mut verify_args = args("--baseline baselines/login.png --threshold 0.5% --window");
+        verify_args.push("My App".to_string());
+        let parsed = parse_verify_args(&verify_args).unwrap();
+        assert_eq!(parsed.target, VerifyTarget::Window("My App".to_string()));
+        assert_eq!(parsed.threshold, 0.5);
+        assert!(!parsed.options.align);
+        assert!(!parsed.update);
+
+        let parsed = parse_verify_args(&args(
+            "--baseline b.png --ignore 0,0,200,40 --ignore 10,300,50,50 --update",
+        ))
+        .unwrap();
+        assert_eq!(parsed.target, VerifyTarget::Screen);
+        assert_eq!(
+            parsed.options.ignore[1],
+            RegionBounds {
+                x: 10,
+                y: 300,
+                width: 50,
+                height: 50
+            }
+        );
+        assert!(parsed.update);
+
+        assert!(parse_verify_args(&args("--window app")).is_err());
+        assert!(parse_verify_args(&args("--baseline b.png --input a.png --display 1")).is_err());
+        assert!(parse_verify_args(&args("--baseline b.png --ignore 0,0,10")).is_err());
+    }
+
+    #[test]
+    fn test_verify_exit_codes() {
+        let dir = std::env::temp_dir().join(format!("grab-verify-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = |name: &str, shade: u8| {
+            let path = dir.join(name);
+            image::RgbaImage::from_pixel(20, 20, image::Rgba([shade, shade, shade, 255]))
+                .save(&path)
+                .unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let baseline = image("baseline.png", 255);
+        let same = image("same.png", 255);
+        let changed = image("changed.png", 0);
+        let verify = |baseline: &str, input: &str| {
+            let command = ["verify", "--baseline", baseline, "--input", input];
+            run(&command.map(str::to_string))
+        };
+
+        assert_eq!(verify(&baseline, &same), Some(0));
+        assert_eq!(verify(&baseline, &changed), Some(VERIFY_FAILED));
+        assert!(dir.join("baseline.diff.png").exists());
+        let missing = dir.join("missing.png").to_string_lossy().to_string();
+        assert_eq!(verify(&missing, &same), Some(VERIFY_ERROR));
+        assert_eq!(verify(&baseline, &missing), Some(VERIFY_ERROR));
+        assert_eq!(run(&args("verify --threshold 1%")), Some(VERIFY_ERROR));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
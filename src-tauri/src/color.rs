@@ -0,0 +1,386 @@
+//! Colour picking and palette extraction
+//!
+//! Colours are read from a capture (or a fresh capture of the monitor under
+//! a point) and written out as HEX, RGB, HSL and OKLCH strings ready to
+//! paste into CSS or design tools.
+
+use crate::capture;
+use crate::error::{GrabError, GrabResult};
+use crate::types::{Color, ColorFormat, ColorSample, ColorValue, PaletteColor, RegionBounds};
+use image::RgbaImage;
+
+/// Largest magnifier radius, for a 33x33 grid
+pub const MAX_RADIUS: u32 = 16;
+
+/// Largest palette
+pub const MAX_PALETTE_SIZE: usize = 16;
+
+/// Pixels looked at for a palette; larger images are sampled evenly
+const PALETTE_SAMPLES: u32 = 65_536;
+
+/// k-means passes refining the median-cut palette
+const REFINE_PASSES: usize = 5;
+
+/// A colour in every text format
+pub fn describe(color: Color) -> ColorValue {
+    ColorValue {
+        hex: color.to_hex(),
+        rgb: format_color(color, ColorFormat::Rgb),
+        hsl: format_color(color, ColorFormat::Hsl),
+        oklch: format_color(color, ColorFormat::Oklch),
+    }
+}
+
+/// A colour in one text format
+pub fn format_color(color: Color, format: ColorFormat) -> String {
+    let [r, g, b, a] = color.0;
+    let alpha = (a < 255).then(|| (a as f32 / 255.0 * 100.0).round() / 100.0);
+
+    match format {
+        ColorFormat::Hex => color.to_hex(),
+        ColorFormat::Rgb => match alpha {
+            Some(alpha) => format!("rgba({}, {}, {}, {})", r, g, b, alpha),
+            None => format!("rgb({}, {}, {})", r, g, b),
+        },
+        ColorFormat::Hsl => {
+            let (h, s, l) = to_hsl(r, g, b);
+            match alpha {
+                Some(alpha) => format!("hsla({:.0}, {:.0}%, {:.0}%, {})", h, s, l, alpha),
+                None => format!("hsl({:.0}, {:.0}%, {:.0}%)", h, s, l),
+            }
+        }
+        ColorFormat::Oklch => {
+            let (l, c, h) = to_oklch(r, g, b);
+            match alpha {
+                Some(alpha) => format!("oklch({:.1}% {:.3} {:.1} / {})", l, c, h, alpha),
+                None => format!("oklch({:.1}% {:.3} {:.1})", l, c, h),
+            }
+        }
+    }
+}
+
+/// Read the pixel at a point and the grid around it
+pub fn sample(image: &RgbaImage, x: u32, y: u32, radius: u32) -> GrabResult<ColorSample> {
+    if x >= image.width() || y >= image.height() {
+        return Err(GrabError::InvalidRequest(format!(
+            "Point {},{} lies outside the {}x{} image",
+            x,
+            y,
+            image.width(),
+            image.height()
+        )));
+    }
+
+    let radius = radius.min(MAX_RADIUS) as i64;
+    let mut grid = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+    for gy in y as i64 - radius..=y as i64 + radius {
+        for gx in x as i64 - radius..=x as i64 + radius {
+            let inside =
+                (0..image.width() as i64).contains(&gx) && (0..image.height() as i64).contains(&gy);
+            grid.push(inside.then(|| Color(image.get_pixel(gx as u32, gy as u32).0)));
+        }
+    }
+
+    Ok(ColorSample {
+        x,
+        y,
+        color: describe(Color(image.get_pixel(x, y).0)),
+        grid_size: 2 * radius as u32 + 1,
+        grid,
+    })
+}
+
+/// Read the colour at global screen coordinates from a fresh capture
+pub fn sample_screen(x: i32, y: i32, radius: u32) -> GrabResult<ColorSample> {
+    let (image, metadata) = capture::capture_monitor_at(x, y)?;
+    let (px, py) = screen_to_pixel(&metadata.bounds, &image, x, y).ok_or_else(|| {
+        GrabError::InvalidRequest(format!("Point {},{} is not on any display", x, y))
+    })?;
+    sample(&image, px, py, radius)
+}
+
+/// Map global screen coordinates to a pixel of a monitor capture
+///
+/// The capture can be larger than the monitor's bounds on HiDPI displays,
+/// so coordinates are scaled by the ratio between the two.
+fn screen_to_pixel(bounds: &RegionBounds, image: &RgbaImage, x: i32, y: i32) -> Option<(u32, u32)> {
+    if bounds.width == 0 || bounds.height == 0 {
+        return None;
+    }
+    let scale_x = image.width() as f64 / bounds.width as f64;
+    let scale_y = image.height() as f64 / bounds.height as f64;
+    let px = ((x - bounds.x) as f64 * scale_x).floor();
+    let py = ((y - bounds.y) as f64 * scale_y).floor();
+
+    if px < 0.0 || py < 0.0 || px >= image.width() as f64 || py >= image.height() as f64 {
+        return None;
+    }
+    Some((px as u32, py as u32))
+}
+
+/// The dominant colours of an image, most common first
+///
+/// Median cut gives the starting colours, which a few k-means passes then
+/// settle onto the actual clusters. Mostly transparent pixels are ignored.
+pub fn palette(image: &RgbaImage, count: usize) -> Vec<PaletteColor> {
+    let count = count.clamp(1, MAX_PALETTE_SIZE);
+    let pixels = sample_pixels(image);
+    if pixels.is_empty() {
+        return Vec::new();
+    }
+
+    let mut centroids: Vec<[f32; 3]> = median_cut(pixels.clone(), count)
+        .iter()
+        .map(|bucket| mean(bucket.iter()))
+        .collect();
+
+    let mut assignment = vec![0; pixels.len()];
+    for _ in 0..REFINE_PASSES {
+        for (pixel, assigned) in pixels.iter().zip(assignment.iter_mut()) {
+            *assigned = nearest(&centroids, pixel);
+        }
+        centroids = (0..centroids.len())
+            .map(|i| {
+                let members = pixels
+                    .iter()
+                    .zip(&assignment)
+                    .filter(|(_, &a)| a == i)
+                    .map(|(p, _)| p);
+                mean(members)
+            })
+            .collect();
+    }
+
+    let mut counts = vec![0usize; centroids.len()];
+    for &a in &assignment {
+        counts[a] += 1;
+    }
+
+    let mut colors: Vec<(Color, usize)> = centroids
+        .iter()
+        .zip(counts)
+        .filter(|(_, n)| *n > 0)
+        .map(|(c, n)| {
+            let [r, g, b] = c.map(|v| v.round().clamp(0.0, 255.0) as u8);
+            (Color([r, g, b, 255]), n)
+        })
+        .collect();
+    colors.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+
+    colors
+        .into_iter()
+        .map(|(color, n)| PaletteColor {
+            color: describe(color),
+            percentage: (n as f32 / pixels.len() as f32 * 10_000.0).round() / 100.0,
+        })
+        .collect()
+}
+
+/// Opaque pixels on an even grid
+fn sample_pixels(image: &RgbaImage) -> Vec<[u8; 3]> {
+    let area = image.width() as u64 * image.height() as u64;
+    let step = ((area as f64 / PALETTE_SAMPLES as f64).sqrt().ceil() as u32).max(1);
+
+    let mut pixels = Vec::new();
+    for y in (0..image.height()).step_by(step as usize) {
+        for x in (0..image.width()).step_by(step as usize) {
+            let [r, g, b, a] = image.get_pixel(x, y).0;
+            if a >= 128 {
+                pixels.push([r, g, b]);
+            }
+        }
+    }
+    pixels
+}
+
+/// Split the pixels into up to `count` buckets along their widest channel
+fn median_cut(pixels: Vec<[u8; 3]>, count: usize) -> Vec<Vec<[u8; 3]>> {
+    let mut buckets = vec![pixels];
+
+    while buckets.len() < count {
+        let widest = buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| {
+                let (channel, range) = widest_channel(bucket);
+                (i, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let Some((index, channel, range)) = widest else {
+            break;
+        };
+        if range == 0 {
+            break;
+        }
+
+        let mut bucket = buckets.swap_remove(index);
+        bucket.sort_unstable_by_key(|p| p[channel]);
+        let upper = bucket.split_off(bucket.len() / 2);
+        buckets.push(bucket);
+        buckets.push(upper);
+    }
+
+    buckets
+}
+
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = pixels.iter().fold((u8::MAX, u8::MIN), |(min, max), p| {
+                (min.min(p[channel]), max.max(p[channel]))
+            });
+            (channel, max.saturating_sub(min))
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+fn mean<'a>(pixels: impl Iterator<Item = &'a [u8; 3]>) -> [f32; 3] {
+    let mut sum = [0.0f64; 3];
+    let mut n = 0usize;
+    for p in pixels {
+        for c in 0..3 {
+            sum[c] += p[c] as f64;
+        }
+        n += 1;
+    }
+    if n == 0 {
+        return [0.0; 3];
+    }
+    sum.map(|v| (v / n as f64) as f32)
+}
+
+fn nearest(centroids: &[[f32; 3]], pixel: &[u8; 3]) -> usize {
+    let distance = |c: &[f32; 3]| {
+        (0..3)
+            .map(|i| (c[i] - pixel[i] as f32).powi(2))
+            .sum::<f32>()
+    };
+    centroids
+        .iter()
+        .enumerate()
+        .min_by(|a, b| distance(a.1).total_cmp(&distance(b.1)))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Hue in degrees, saturation and lightness in percent
+fn to_hsl(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+    let [r, g, b] = [r, g, b].map(|v| v as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let l = (max + min) / 2.0;
+
+    if delta == 0.0 {
+        return (0.0, 0.0, l * 100.0);
+    }
+    let s = delta / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    (h.round() % 360.0, s * 100.0, l * 100.0)
+}
+
+/// Lightness in percent, chroma, and hue in degrees
+fn to_oklch(r: u8, g: u8, b: u8) -> (f64, f64, f64) {
+    let linear = |v: u8| {
+        let v = v as f64 / 255.0;
+        if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(r), linear(g), linear(b));
+
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    let lightness = 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s;
+    let a = 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s;
+    let b = 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s;
+
+    let chroma = (a * a + b * b).sqrt();
+    // Greys have no meaningful hue
+    let hue = if chroma < 1e-4 {
+        0.0
+    } else {
+        b.atan2(a).to_degrees().rem_euclid(360.0)
+    };
+
+    (lightness * 100.0, chroma, hue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_color_formats() {
+        let red = describe(Color([255, 0, 0, 255]));
+        assert_eq!(red.hex, "#ff0000");
+        assert_eq!(red.rgb, "rgb(255, 0, 0)");
+        assert_eq!(red.hsl, "hsl(0, 100%, 50%)");
+        assert_eq!(red.oklch, "oklch(62.8% 0.258 29.2)");
+
+        let blue = describe(Color([59, 130, 246, 255]));
+        assert_eq!(blue.hsl, "hsl(217, 91%, 60%)");
+        assert_eq!(blue.oklch, "oklch(62.3% 0.188 259.8)");
+
+        let grey = describe(Color([128, 128, 128, 128]));
+        assert_eq!(grey.rgb, "rgba(128, 128, 128, 0.5)");
+        assert_eq!(grey.oklch, "oklch(60.0% 0.000 0.0 / 0.5)");
+    }
+
+    #[test]
+    fn test_sample_and_palette() {
+        // Left half red, then blue, then a white strip
+        let image = RgbaImage::from_fn(100, 40, |x, _| match x {
+            0..=49 => Rgba([220, 38, 38, 255]),
+            50..=79 => Rgba([37, 99, 235, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        });
+
+        let picked = sample(&image, 0, 0, 1).unwrap();
+        assert_eq!(picked.color.hex, "#dc2626");
+        assert_eq!(picked.grid_size, 3);
+        assert_eq!(picked.grid[..4], [None, None, None, None]);
+        assert_eq!(picked.grid[4], Some(Color([220, 38, 38, 255])));
+        assert!(sample(&image, 100, 0, 0).is_err());
+
+        let colors = palette(&image, 6);
+        let found: Vec<(&str, f32)> = colors
+            .iter()
+            .map(|c| (c.color.hex.as_str(), c.percentage))
+            .collect();
+        assert_eq!(
+            found,
+            [("#dc2626", 50.0), ("#2563eb", 30.0), ("#ffffff", 20.0)]
+        );
+    }
+
+    #[test]
+    fn test_screen_to_pixel_scales_for_hidpi() {
+        let bounds = RegionBounds {
+            x: -1440,
+            y: 0,
+            width: 1440,
+            height: 900,
+        };
+        let image = RgbaImage::new(2880, 1800);
+        assert_eq!(screen_to_pixel(&bounds, &image, -1440, 0), Some((0, 0)));
+        assert_eq!(
+            screen_to_pixel(&bounds, &image, -1, 899),
+            Some((2878, 1798))
+        );
+        assert_eq!(screen_to_pixel(&bounds, &image, 0, 0), None);
+    }
+}
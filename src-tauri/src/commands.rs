@@ -2,17 +2,47 @@
 //!
 //! These commands are exposed to the frontend via Tauri's invoke system.
 
+use crate::ai;
+use crate::annotate;
+use crate::barcode;
+use crate::batch::{self, BatchJobs};
+use crate::beautify;
 use crate::capture;
+use crate::color;
+use crate::compose;
+use crate::dedupe::{self, HashWorker};
+use crate::diff;
 use crate::error::{GrabError, GrabResult};
 use crate::history::HistoryStore;
+use crate::export;
+use crate::measure;
+use crate::metadata;
+use crate::ocr::{self, OcrCache, OcrEngine};
+use crate::optimize::OptimizeWorker;
+use crate::pdf;
 use crate::preferences::PreferencesStore;
+use crate::project;
+use crate::redact;
+use crate::search::SearchIndexer;
+use crate::secrets;
+use crate::trim;
 use crate::types::{
-    CapturePreferences, CaptureResult, CaptureSource, HistoryItem, RegionBounds,
+    AiProvider, Annotation, BatchExportReport, BatchExportRequest, BatchItemResult, BeautifyStyle,
+    CaptureMetadata, CaptureMode, CapturePreferences, CaptureResult, CaptureSource, CapturedCodes,
+    CodeScanRequest, Collection, Color, ColorPickRequest, ColorSample, CompositionRequest,
+    DecodedCode, DiffRequest, DiffResult, DistanceMeasurement, DistanceRequest, DuplicateGroup, EdgeProbeRequest,
+    ElementMeasurement, ExportFormat, ExportOptions, GrabProject, HistoryItem, HistoryPage,
+    HistoryQuery, Measurement,
+    OcrRequest, OcrResult, OpenedProject, PaletteColor, PaletteRequest, PdfExportRequest,
+    ProjectExportRequest, Redaction, RegionBounds, SaveProjectRequest, SearchRequest,
+    SearchResults, SecretFinding, SmartActionEvent, SmartActionRequest, TagCount,
 };
+use crate::watermark;
 use base64::Engine;
 use image::RgbaImage;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tauri::{AppHandle, Emitter, Manager, State};
 use tauri_plugin_clipboard_manager::ClipboardExt;
 use tauri_plugin_dialog::DialogExt;
@@ -104,17 +134,155 @@ pub fn get_window_sources() -> Result<Vec<CaptureSource>, GrabError> {
 // History Commands
 // ============================================================================
 
-/// Get all history items
+/// Get the newest history items
+///
+/// Only the first page of `query_history`'s default query; use that to see
+/// the rest of the library.
 #[tauri::command]
 pub fn get_history(
     history: State<'_, HistoryStore>,
     prefs: State<'_, PreferencesStore>,
-) -> Vec<HistoryItem> {
+    indexer: State<'_, SearchIndexer>,
+    hasher: State<'_, HashWorker>,
+) -> Result<Vec<HistoryItem>, GrabError> {
     // Scan directory first to pick up any new files
+    let preferences = prefs.get();
     let output_folder = prefs.get_output_folder();
-    history.scan_directory(&output_folder).ok();
+    let added = history.scan_directory(&output_folder).unwrap_or(0);
+    history.prune(preferences.history_limit)?;
+    if added > 0 {
+        hasher.queue_unhashed();
+        if preferences.ocr.index_captures {
+            indexer.queue_unindexed(preferences.ocr);
+        }
+    }
 
-    history.get_all()
+    Ok(history.query(&HistoryQuery::default())?.items)
+}
+
+/// Get a page of history, sorted and filtered
+#[tauri::command]
+pub fn query_history(
+    query: Option<HistoryQuery>,
+    history: State<'_, HistoryStore>,
+) -> Result<HistoryPage, GrabError> {
+    history.query(&query.unwrap_or_default())
+}
+
+/// Search the text of captures, titles, app names, file names and tags
+///
+/// Captures' text is read in the background, so recent ones may only be
+/// found by title or name for a few seconds.
+#[tauri::command]
+pub fn search_history(
+    request: SearchRequest,
+    history: State<'_, HistoryStore>,
+) -> Result<SearchResults, GrabError> {
+    history.search(&request)
+}
+
+/// Tag captures, also writing the tags into their files
+///
+/// Files are rewritten in the background. Returns the updated items.
+#[tauri::command]
+pub fn add_tags(
+    item_ids: Vec<String>,
+    tags: Vec<String>,
+    history: State<'_, HistoryStore>,
+    prefs: State<'_, PreferencesStore>,
+    optimizer: State<'_, OptimizeWorker>,
+) -> Result<Vec<HistoryItem>, GrabError> {
+    history.add_tags(&item_ids, &tags)?;
+    embed_tags(&history, &optimizer, &item_ids, &prefs.get())
+}
+
+/// Untag captures, also removing the tags from their files
+///
+/// Files are rewritten in the background. Returns the updated items.
+#[tauri::command]
+pub fn remove_tags(
+    item_ids: Vec<String>,
+    tags: Vec<String>,
+    history: State<'_, HistoryStore>,
+    prefs: State<'_, PreferencesStore>,
+    optimizer: State<'_, OptimizeWorker>,
+) -> Result<Vec<HistoryItem>, GrabError> {
+    history.remove_tags(&item_ids, &tags)?;
+    embed_tags(&history, &optimizer, &item_ids, &prefs.get())
+}
+
+/// All tags in use with their counts, most used first
+///
+/// `query_history` with a tag lists the captures that have it.
+#[tauri::command]
+pub fn list_tags(history: State<'_, HistoryStore>) -> Result<Vec<TagCount>, GrabError> {
+    history.tags()
+}
+
+/// Pin captures so trimming history to its limit never drops them
+///
+/// Returns how many captures changed.
+#[tauri::command]
+pub fn set_pinned(
+    item_ids: Vec<String>,
+    pinned: bool,
+    history: State<'_, HistoryStore>,
+) -> Result<usize, GrabError> {
+    history.set_pinned(&item_ids, pinned)
+}
+
+/// All collections, by name
+///
+/// `query_history` with a collection id lists the captures in it.
+#[tauri::command]
+pub fn list_collections(history: State<'_, HistoryStore>) -> Result<Vec<Collection>, GrabError> {
+    history.collections()
+}
+
+#[tauri::command]
+pub fn create_collection(
+    name: String,
+    history: State<'_, HistoryStore>,
+) -> Result<Collection, GrabError> {
+    history.create_collection(&name)
+}
+
+#[tauri::command]
+pub fn rename_collection(
+    collection_id: String,
+    name: String,
+    history: State<'_, HistoryStore>,
+) -> Result<Collection, GrabError> {
+    history.rename_collection(&collection_id, &name)
+}
+
+/// Delete a collection, keeping the captures in it
+#[tauri::command]
+pub fn delete_collection(
+    collection_id: String,
+    history: State<'_, HistoryStore>,
+) -> Result<bool, GrabError> {
+    history.delete_collection(&collection_id)
+}
+
+/// Add captures to a collection; returns how many weren't in it yet
+#[tauri::command]
+pub fn add_to_collection(
+    collection_id: String,
+    item_ids: Vec<String>,
+    history: State<'_, HistoryStore>,
+) -> Result<usize, GrabError> {
+    history.add_to_collection(&collection_id, &item_ids)
+}
+
+/// Take captures out of a collection; returns how many were in it
+#[tauri::command]
+pub fn remove_from_collection(
+    collection_id: String,
+    item_ids: Vec<String>,
+    history: State<'_, HistoryStore>,
+) -> Result<usize, GrabError> {
+    history.remove_from_collection(&collection_id, &item_ids)
 }
 
 /// Remove an item from history
@@ -131,8 +299,13 @@ pub fn remove_from_history(
 pub fn scan_directory(
     directory: String,
     history: State<'_, HistoryStore>,
+    hasher: State<'_, HashWorker>,
 ) -> Result<usize, GrabError> {
-    history.scan_directory(&PathBuf::from(directory))
+    let added = history.scan_directory(&PathBuf::from(directory))?;
+    if added > 0 {
+        hasher.queue_unhashed();
+    }
+    Ok(added)
 }
 
 // ============================================================================
@@ -277,27 +450,64 @@ pub async fn delete_screenshot(
     file_path: String,
     history: State<'_, HistoryStore>,
 ) -> Result<bool, GrabError> {
-    // Try to move to trash
-    let path = PathBuf::from(&file_path);
+    delete_capture(&history, &file_path)
+}
 
-    if path.exists() {
-        // Use trash crate if available, otherwise just delete
-        #[cfg(feature = "trash")]
-        {
-            trash::delete(&path).map_err(|e| GrabError::ExportFailed(e.to_string()))?;
-        }
-        #[cfg(not(feature = "trash"))]
-        {
-            fs::remove_file(&path)?;
-        }
+/// Find captures that look alike
+///
+/// `threshold` is the number of bits perceptual hashes may differ by; 0
+/// finds only exact copies.
+#[tauri::command]
+pub fn find_duplicates(
+    threshold: Option<u32>,
+    history: State<'_, HistoryStore>,
+) -> Result<Vec<DuplicateGroup>, GrabError> {
+    Ok(dedupe::find_duplicates(
+        &history.get_all()?,
+        threshold.unwrap_or(dedupe::DEFAULT_THRESHOLD),
+    ))
+}
 
-        // Remove from history
-        history.remove(&file_path)?;
+/// Delete several captures at once
+///
+/// Returns how many files were deleted.
+#[tauri::command]
+pub async fn delete_captures(
+    item_ids: Vec<String>,
+    history: State<'_, HistoryStore>,
+) -> Result<usize, GrabError> {
+    let mut deleted = 0;
+    for id in &item_ids {
+        if let Some(item) = history.get(id)? {
+            if delete_capture(&history, &item.file_path)? {
+                deleted += 1;
+            }
+        }
+    }
+    Ok(deleted)
+}
 
-        Ok(true)
-    } else {
-        Ok(false)
+/// Delete the captures that duplicate the newest of their group
+///
+/// Only those within the threshold of the newest itself go; ones a group
+/// merely chains to it are kept, and so are pinned captures. Returns the
+/// deleted items.
+#[tauri::command]
+pub async fn keep_newest_duplicates(
+    threshold: Option<u32>,
+    history: State<'_, HistoryStore>,
+) -> Result<Vec<HistoryItem>, GrabError> {
+    let threshold = threshold.unwrap_or(dedupe::DEFAULT_THRESHOLD);
+    let groups = dedupe::find_duplicates(&history.get_all()?, threshold);
+    let mut deleted = Vec::new();
+    for group in &groups {
+        for item in dedupe::duplicates_of_newest(group, threshold) {
+            if !item.pinned && delete_capture(&history, &item.file_path)? {
+                deleted.push(item.clone());
+            }
+        }
     }
+    Ok(deleted)
 }
 
 /// Reveal a file in the system file manager
@@ -310,19 +520,48 @@ pub async fn reveal_in_folder(file_path: String, app: AppHandle) -> Result<(), G
 }
 
 /// Export a capture (with options)
+///
+/// Export settings come from `options`, else the named `preset`, else
+/// `format` and `quality`; `beautify_style` frames the image with a named
+/// style. `source_path` points at the original capture when `image_data` is
+/// an edited copy, so its embedded metadata can be carried over. Without
+/// any, metadata is made from the image and history, so the redactions are
+/// always recorded unless metadata is stripped.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn export_capture(
     image_data: String,
     format: String,
     quality: Option<u8>,
+    source_path: Option<String>,
+    preset: Option<String>,
+    options: Option<ExportOptions>,
+    beautify_style: Option<String>,
+    redactions: Option<Vec<Redaction>>,
+    safe_share: Option<bool>,
     app: AppHandle,
+    prefs: State<'_, PreferencesStore>,
+    history: State<'_, HistoryStore>,
 ) -> Result<Option<String>, GrabError> {
     use std::sync::mpsc;
-    
-    let ext = match format.as_str() {
-        "jpeg" | "jpg" => "jpg",
-        _ => "png",
+
+    let preferences = prefs.get();
+    let mut options = match (options, preset) {
+        (Some(options), _) => options,
+        (None, Some(name)) => export::find_preset(&preferences.export_presets, &name)
+            .map(|p| p.options.clone())
+            .ok_or_else(|| GrabError::InvalidRequest(format!("Unknown export preset: {}", name)))?,
+        (None, None) => ExportOptions {
+            format: ExportFormat::from_name(&format).unwrap_or_default(),
+            quality,
+            ..Default::default()
+        },
     };
+    if let Some(ref name) = beautify_style {
+        beautify::use_style(&mut options, &preferences.beautify_styles, name)?;
+    }
+    watermark::use_default(&mut options, &preferences.watermark);
+    let ext = options.format.extension();
 
     let (tx, rx) = mpsc::channel();
     
@@ -354,22 +593,809 @@ pub fn export_capture(
         fs::read(&image_data)?
     };
 
-    let img = image::load_from_memory(&bytes)
+    let mut img = image::load_from_memory(&bytes)
         .map_err(|e| GrabError::ExportFailed(e.to_string()))?;
 
-    // Save with appropriate format
-    match format.as_str() {
-        "jpeg" | "jpg" => {
-            let _quality = quality.unwrap_or(90);
-            img.save_with_format(&file_path, image::ImageFormat::Jpeg)
-                .map_err(|e| GrabError::ExportFailed(e.to_string()))?;
+    // Redact the pixel buffer itself, before anything is encoded. Safe
+    // share adds a redaction over every detected secret.
+    let mut redactions = redactions.unwrap_or_default();
+    if safe_share.unwrap_or(false) {
+        let findings = secrets::scan_image(
+            &ocr::Tesseract::new(&preferences.ocr),
+            &img.to_rgba8(),
+            &preferences.secret_scan,
+            &preferences.ocr.languages,
+        )?;
+        redactions.extend(secrets::to_redactions(&findings, preferences.secret_scan.method));
+    }
+    if !redactions.is_empty() {
+        img = image::DynamicImage::ImageRgba8(redact::apply(&img.to_rgba8(), &redactions)?);
+    }
+
+    // The source file's metadata supplies the scale factor for logical
+    // resizing even when it isn't carried over
+    let source_metadata = match source_path {
+        Some(ref path) => metadata::read_from_file(&PathBuf::from(path)),
+        None if !image_data.starts_with("data:") => metadata::read_embedded(&bytes),
+        None => None,
+    };
+    let scale_factor = source_metadata.as_ref().map(|m| m.scale_factor).unwrap_or(1.0);
+    let embedded = if preferences.strip_metadata {
+        None
+    } else {
+        let source = source_path.as_deref().unwrap_or_default();
+        let item = match source_path {
+            Some(ref path) => history.get_by_path(path)?,
+            None => None,
+        };
+        let fallback = || fallback_metadata(&img.to_rgba8(), source, item.as_ref());
+        Some(redact::export_metadata(source_metadata, fallback, &redactions))
+    };
+
+    export::export_to_file(&img, &options, embedded.as_ref(), scale_factor, &file_path)?;
+
+    Ok(Some(file_path.to_string_lossy().to_string()))
+}
+
+/// Render annotations onto an image and return it as a PNG data URL
+///
+/// Lets the editor show exactly what exports will contain.
+#[tauri::command]
+pub async fn render_annotations(
+    image_data: String,
+    annotations: Vec<Annotation>,
+) -> Result<String, GrabError> {
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<String> {
+        let bytes = decode_image_data(&image_data)?;
+        let image = image::load_from_memory(&bytes)?.to_rgba8();
+        let rendered = annotate::render(&image, &annotations)?;
+
+        encode_png_data_url(&rendered)
+    })
+    .await?
+}
+
+/// Frame an image with a beautify style and return it as a PNG data URL
+///
+/// Takes the style itself so the editor can preview unsaved changes.
+/// `scale_factor` defaults to the one recorded in the image.
+#[tauri::command]
+pub async fn beautify_image(
+    image_data: String,
+    style: BeautifyStyle,
+    scale_factor: Option<f64>,
+) -> Result<String, GrabError> {
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<String> {
+        let bytes = decode_image_data(&image_data)?;
+        let image = image::load_from_memory(&bytes)?.to_rgba8();
+        let scale_factor = scale_factor
+            .or_else(|| metadata::read_embedded(&bytes).map(|m| m.scale_factor))
+            .unwrap_or(1.0);
+        let framed = beautify::apply(&image, &style, scale_factor)?;
+
+        encode_png_data_url(&framed)
+    })
+    .await?
+}
+
+/// Apply redactions to an image and return it as a PNG data URL
+///
+/// Fails like an export would if a redaction is too weak.
+#[tauri::command]
+pub async fn redact_image(
+    image_data: String,
+    redactions: Vec<Redaction>,
+) -> Result<String, GrabError> {
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<String> {
+        let bytes = decode_image_data(&image_data)?;
+        let image = image::load_from_memory(&bytes)?.to_rgba8();
+        let redacted = redact::apply(&image, &redactions)?;
+
+        encode_png_data_url(&redacted)
+    })
+    .await?
+}
+
+/// Find likely secrets and personal data in an image
+///
+/// Needs a locally installed `tesseract`.
+#[tauri::command]
+pub async fn detect_secrets(
+    image_data: String,
+    prefs: State<'_, PreferencesStore>,
+) -> Result<Vec<SecretFinding>, GrabError> {
+    let preferences = prefs.get();
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<Vec<SecretFinding>> {
+        let bytes = decode_image_data(&image_data)?;
+        let image = image::load_from_memory(&bytes)?.to_rgba8();
+        secrets::scan_image(
+            &ocr::Tesseract::new(&preferences.ocr),
+            &image,
+            &preferences.secret_scan,
+            &preferences.ocr.languages,
+        )
+    })
+    .await?
+}
+
+/// Recognize the text in a history item or image file
+///
+/// Results are cached per file contents, languages and region.
+#[tauri::command]
+pub async fn extract_text(
+    request: OcrRequest,
+    history: State<'_, HistoryStore>,
+    prefs: State<'_, PreferencesStore>,
+    cache: State<'_, OcrCache>,
+) -> Result<OcrResult, GrabError> {
+    let path = source_path(
+        &history,
+        request.item_id.as_deref(),
+        request.file_path.as_deref(),
+        "OCR",
+    )?;
+
+    let settings = prefs.get().ocr;
+    let languages = request.languages.unwrap_or(settings.languages.clone());
+    let region = request.region;
+    let cache = cache.inner().clone();
+    let whole_image = region.is_none().then(|| path.to_string_lossy().to_string());
+
+    let result = tauri::async_runtime::spawn_blocking(move || -> GrabResult<OcrResult> {
+        let engine = ocr::Tesseract::new(&settings);
+        let bytes = fs::read(&path)?;
+        let key = OcrCache::key(&bytes, engine.name(), &languages, region.as_ref());
+        if let Some(result) = cache.get(&key) {
+            return Ok(result);
+        }
+
+        let image = image::load_from_memory(&bytes)?.to_rgba8();
+        let result = ocr::recognize_region(&engine, &image, region.as_ref(), &languages)?;
+        if let Err(e) = cache.put(&key, &result) {
+            eprintln!("Failed to cache OCR result: {}", e);
         }
-        _ => {
-            img.save_with_format(&file_path, image::ImageFormat::Png)
-                .map_err(|e| GrabError::ExportFailed(e.to_string()))?;
+        Ok(result)
+    })
+    .await??;
+
+    // The text of a whole capture goes into the library
+    if let Some(ref file_path) = whole_image {
+        history.set_ocr_text(file_path, &result.text)?;
+    }
+    Ok(result)
+}
+
+/// Languages the installed OCR engine can read
+#[tauri::command]
+pub async fn get_ocr_languages(prefs: State<'_, PreferencesStore>) -> Result<Vec<String>, GrabError> {
+    let settings = prefs.get().ocr;
+    tauri::async_runtime::spawn_blocking(move || ocr::Tesseract::new(&settings).languages()).await?
+}
+
+/// Decode the QR codes and barcodes in a history item or image file
+#[tauri::command]
+pub async fn scan_codes(
+    request: CodeScanRequest,
+    history: State<'_, HistoryStore>,
+) -> Result<Vec<DecodedCode>, GrabError> {
+    let path = source_path(
+        &history,
+        request.item_id.as_deref(),
+        request.file_path.as_deref(),
+        "Code scanning",
+    )?;
+
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<Vec<DecodedCode>> {
+        let image = image::open(&path)?.to_rgba8();
+        barcode::scan(&image, request.region.as_ref())
+    })
+    .await?
+}
+
+/// Copy text to the clipboard, e.g. a decoded QR code
+#[tauri::command]
+pub fn copy_text(text: String, app: AppHandle) -> Result<(), GrabError> {
+    app.clipboard()
+        .write_text(text)
+        .map_err(|e| GrabError::ClipboardFailed(e.to_string()))
+}
+
+/// Read the colour at a point, with the pixels around it
+///
+/// Without an item or file, the point is in global screen coordinates and
+/// the monitor under it is captured fresh.
+#[tauri::command]
+pub async fn pick_color(
+    request: ColorPickRequest,
+    app: AppHandle,
+    history: State<'_, HistoryStore>,
+) -> Result<ColorSample, GrabError> {
+    let path = match (&request.item_id, &request.file_path) {
+        (None, None) => None,
+        (item_id, file_path) => Some(source_path(
+            &history,
+            item_id.as_deref(),
+            file_path.as_deref(),
+            "Colour picking",
+        )?),
+    };
+
+    let (x, y, radius) = (request.x, request.y, request.radius);
+    let picked = tauri::async_runtime::spawn_blocking(move || -> GrabResult<ColorSample> {
+        match path {
+            Some(path) => {
+                let image = image::open(&path)?.to_rgba8();
+                if x < 0 || y < 0 {
+                    return Err(GrabError::InvalidRequest(format!(
+                        "Point {},{} lies outside the image",
+                        x, y
+                    )));
+                }
+                color::sample(&image, x as u32, y as u32, radius)
+            }
+            None => color::sample_screen(x, y, radius),
         }
+    })
+    .await??;
+
+    if let Some(format) = request.copy {
+        copy_text(picked.color.get(format).to_string(), app)?;
+    }
+
+    Ok(picked)
+}
+
+/// Find the dominant colours of a capture, most common first
+#[tauri::command]
+pub async fn extract_palette(
+    request: PaletteRequest,
+    app: AppHandle,
+    history: State<'_, HistoryStore>,
+) -> Result<Vec<PaletteColor>, GrabError> {
+    let path = source_path(
+        &history,
+        request.item_id.as_deref(),
+        request.file_path.as_deref(),
+        "Palette extraction",
+    )?;
+
+    let count = request.count;
+    let colors = tauri::async_runtime::spawn_blocking(move || -> GrabResult<Vec<PaletteColor>> {
+        let image = image::open(&path)?.to_rgba8();
+        Ok(color::palette(&image, count))
+    })
+    .await??;
+
+    if let Some(format) = request.copy {
+        let text = colors
+            .iter()
+            .map(|c| c.color.get(format))
+            .collect::<Vec<_>>()
+            .join("\n");
+        copy_text(text, app)?;
     }
 
+    Ok(colors)
+}
+
+/// Find the bounds of the element under a point, like a smart guide
+#[tauri::command]
+pub async fn measure_element(
+    request: EdgeProbeRequest,
+    history: State<'_, HistoryStore>,
+) -> Result<ElementMeasurement, GrabError> {
+    let path = source_path(
+        &history,
+        request.item_id.as_deref(),
+        request.file_path.as_deref(),
+        "Measuring",
+    )?;
+
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<ElementMeasurement> {
+        let image = image::open(&path)?.to_rgba8();
+        let scale = scale_factor(&path, request.scale_factor);
+        measure::probe(&image, request.x, request.y, request.tolerance, scale)
+    })
+    .await?
+}
+
+/// Measure the distance between two points of a capture
+#[tauri::command]
+pub async fn measure_distance(
+    request: DistanceRequest,
+    history: State<'_, HistoryStore>,
+) -> Result<DistanceMeasurement, GrabError> {
+    let path = source_path(
+        &history,
+        request.item_id.as_deref(),
+        request.file_path.as_deref(),
+        "Measuring",
+    )?;
+
+    let scale = scale_factor(&path, request.scale_factor);
+    Ok(measure::distance(request.from, request.to, scale))
+}
+
+/// Convert measurements to annotations for the editor
+#[tauri::command]
+pub fn measurement_annotations(
+    measurements: Vec<Measurement>,
+    color: Option<Color>,
+) -> Vec<Annotation> {
+    measure::to_annotations(&measurements, color.unwrap_or(measure::DEFAULT_COLOR))
+}
+
+/// Lay several history items out in one image and save it as a new capture
+///
+/// The composite is saved like any other capture: history, clipboard and
+/// notifications per preferences. It's kept as laid out, though: not
+/// trimmed, which would take away its padding and background, nor
+/// watermarked again or swapped for an identical earlier file.
+#[tauri::command]
+pub async fn compose_captures(
+    request: CompositionRequest,
+    app: AppHandle,
+    prefs: State<'_, PreferencesStore>,
+    history: State<'_, HistoryStore>,
+) -> Result<CaptureResult, GrabError> {
+    let selected = request
+        .item_ids
+        .iter()
+        .map(|id| history.get(id)?.ok_or_else(|| GrabError::SourceNotFound(id.clone())))
+        .collect::<GrabResult<Vec<HistoryItem>>>()?;
+
+    let options = request.options;
+    let (image, scale_factor) =
+        tauri::async_runtime::spawn_blocking(move || -> GrabResult<(RgbaImage, f64)> {
+            let mut scale_factor: f64 = 1.0;
+            let mut images = Vec::new();
+            for (i, item) in selected.iter().enumerate() {
+                let path = PathBuf::from(&item.file_path);
+                if let Some(metadata) = metadata::read_from_file(&path) {
+                    scale_factor = scale_factor.max(metadata.scale_factor);
+                }
+                let label = match options.labels.get(i) {
+                    Some(label) => Some(label.clone()),
+                    None => pdf::caption_for(options.caption, &path, Some(&item.timestamp)),
+                };
+                images.push(compose::CompositionImage {
+                    image: image::open(&path)?.to_rgba8(),
+                    label,
+                });
+            }
+            let composite = compose::compose(&images, &options, scale_factor)?;
+            Ok((composite, scale_factor))
+        })
+        .await??;
+
+    let metadata = CaptureMetadata {
+        mode: CaptureMode::Composite,
+        display_id: None,
+        window_id: None,
+        bounds: RegionBounds {
+            x: 0,
+            y: 0,
+            width: image.width(),
+            height: image.height(),
+        },
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        scale_factor,
+        file_name: None,
+        window_title: None,
+        app_name: None,
+        redactions: Vec::new(),
+        trim: None,
+        tags: Vec::new(),
+    };
+    let mut preferences = prefs.get();
+    preferences.auto_trim = None;
+    preferences.watermark.on_save = false;
+    preferences.skip_identical_captures = false;
+    save_and_process_capture(&app, &image, metadata, &preferences, &history).await
+}
+
+/// Compare two history items
+///
+/// Returns the changed area and regions with the diff image as a PNG data
+/// URL. Regions are in the before capture's pixels.
+#[tauri::command]
+pub async fn diff_captures(
+    request: DiffRequest,
+    history: State<'_, HistoryStore>,
+) -> Result<DiffResult, GrabError> {
+    let before = source_path(&history, Some(&request.before_id), None, "Diff")?;
+    let after = source_path(&history, Some(&request.after_id), None, "Diff")?;
+
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<DiffResult> {
+        let before = image::open(&before)?.to_rgba8();
+        let after = image::open(&after)?.to_rgba8();
+        let diff = diff::diff(&before, &after, &request.options)?;
+
+        Ok(DiffResult {
+            image: Some(encode_png_data_url(&diff.image)?),
+            ..diff.result
+        })
+    })
+    .await?
+}
+
+/// Run an AI smart action on a capture
+///
+/// Output is streamed as `ai:stream` events tagged with the request id, the
+/// last one marked `done`. Returns the full text. Nothing is sent unless AI
+/// features are enabled in preferences.
+#[tauri::command]
+pub async fn run_smart_action(
+    request: SmartActionRequest,
+    app: AppHandle,
+    prefs: State<'_, PreferencesStore>,
+    history: State<'_, HistoryStore>,
+) -> Result<String, GrabError> {
+    let preferences = prefs.get();
+    let settings = preferences.ai;
+    if !settings.enabled {
+        return Err(GrabError::PermissionDenied(
+            "AI features are turned off in preferences".to_string(),
+        ));
+    }
+
+    let source = match (&request.item_id, &request.image_data) {
+        (Some(id), _) => history
+            .get(id)?
+            .map(|item| item.file_path)
+            .ok_or_else(|| GrabError::SourceNotFound(id.clone()))?,
+        (None, Some(data)) => data.clone(),
+        (None, None) => {
+            return Err(GrabError::InvalidRequest(
+                "Smart actions need a history item or image".to_string(),
+            ));
+        }
+    };
+
+    let action = request.action;
+    let provider: Box<dyn ai::ChatProvider> =
+        match request.provider.unwrap_or(settings.provider_for(action)) {
+            AiProvider::Azure => Box::new(ai::AzureConfig::resolve(&settings.azure)?),
+            AiProvider::OpenAiCompatible => Box::new(ai::OpenAiCompatibleConfig::resolve(
+                &settings.openai_compatible,
+            )?),
+        };
+    let transport = ai::UreqTransport::new(Duration::from_secs(settings.timeout_secs.max(1)));
+    let language = request.language.unwrap_or(settings.target_language);
+    let ocr_settings = preferences.ocr;
+    let request_id = request.request_id;
+
+    let emit = move |delta: &str, done: bool, error: Option<String>| {
+        if let Some(window) = app.get_webview_window("main") {
+            let event = SmartActionEvent {
+                request_id: request_id.clone(),
+                delta: delta.to_string(),
+                done,
+                error,
+            };
+            window.emit(ai::STREAM_EVENT, event).ok();
+        }
+    };
+
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<String> {
+        // Text-only models get what the local OCR engine reads instead
+        let recognize = |image: &image::DynamicImage| {
+            let engine = ocr::Tesseract::new(&ocr_settings);
+            let text = engine.recognize(&image.to_rgba8(), &ocr_settings.languages)?.text;
+            if text.trim().is_empty() {
+                return Err(GrabError::InvalidRequest(format!(
+                    "{} can't read images and no text was found in the capture",
+                    provider.name()
+                )));
+            }
+            Ok(text)
+        };
+
+        let result = decode_image_data(&source)
+            .and_then(|bytes| Ok(image::load_from_memory(&bytes)?))
+            .and_then(|image| ai::prepare_input(&transport, provider.as_ref(), &image, recognize))
+            .and_then(|input| {
+                ai::run_action(
+                    &transport,
+                    provider.as_ref(),
+                    action,
+                    &language,
+                    &input,
+                    &mut |delta| emit(delta, false, None),
+                )
+            });
+
+        match &result {
+            Ok(_) => emit("", true, None),
+            Err(e) => emit("", true, Some(e.to_string())),
+        }
+        result
+    })
+    .await?
+}
+
+/// Export several history items to one or more targets
+///
+/// Emits `batch:progress` after each output and returns a report with a
+/// result (or error) for every item/target pair that was attempted.
+#[tauri::command]
+pub async fn batch_export(
+    request: BatchExportRequest,
+    app: AppHandle,
+    prefs: State<'_, PreferencesStore>,
+    history: State<'_, HistoryStore>,
+    jobs: State<'_, BatchJobs>,
+) -> Result<BatchExportReport, GrabError> {
+    let preferences = prefs.get();
+    let mut targets = batch::resolve_targets(&request.targets, &preferences.export_presets)?;
+    for target in &mut targets {
+        watermark::use_default(&mut target.options, &preferences.watermark);
+    }
+
+    let mut sources = Vec::new();
+    let mut missing = Vec::new();
+    for id in &request.item_ids {
+        match history.get(id)? {
+            Some(item) => sources.push(batch::BatchSource {
+                item_id: item.id.clone(),
+                path: PathBuf::from(&item.file_path),
+                mode: item.mode,
+                timestamp: item.timestamp.clone(),
+                annotations: request.annotations.get(id).cloned().unwrap_or_default(),
+            }),
+            None => missing.push(BatchItemResult {
+                item_id: id.clone(),
+                source_path: String::new(),
+                target: String::new(),
+                output_path: None,
+                error: Some(GrabError::SourceNotFound(id.clone()).to_string()),
+            }),
+        }
+    }
+
+    let job_id = request
+        .job_id
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let cancel = jobs.register(&job_id);
+
+    let handle = app.clone();
+    let worker_job_id = job_id.clone();
+    let output_dir = PathBuf::from(request.output_dir);
+    let naming_template = request.naming_template;
+    let strip_metadata = preferences.strip_metadata;
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        batch::run_batch(
+            &worker_job_id,
+            &sources,
+            &targets,
+            &output_dir,
+            &naming_template,
+            strip_metadata,
+            &cancel,
+            &|progress| {
+                if let Some(window) = handle.get_webview_window("main") {
+                    window.emit("batch:progress", progress).ok();
+                }
+            },
+        )
+    })
+    .await;
+    jobs.finish(&job_id);
+
+    let mut report = result??;
+    report.failed += missing.len();
+    report.results.extend(missing);
+
+    Ok(report)
+}
+
+/// Cancel a running batch export
+#[tauri::command]
+pub fn cancel_batch_export(job_id: String, jobs: State<'_, BatchJobs>) -> bool {
+    jobs.cancel(&job_id)
+}
+
+/// Bundle history items into a single PDF, in the order given
+#[tauri::command]
+pub async fn export_pdf(
+    request: PdfExportRequest,
+    app: AppHandle,
+    history: State<'_, HistoryStore>,
+) -> Result<Option<String>, GrabError> {
+    let selected = request
+        .item_ids
+        .iter()
+        .map(|id| history.get(id)?.ok_or_else(|| GrabError::SourceNotFound(id.clone())))
+        .collect::<GrabResult<Vec<HistoryItem>>>()?;
+
+    let file_path = match request.output_path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            use std::sync::mpsc;
+
+            let (tx, rx) = mpsc::channel();
+            app.dialog()
+                .file()
+                .set_title("Export PDF")
+                .set_file_name("captures.pdf")
+                .add_filter("PDF", &["pdf"])
+                .save_file(move |result| {
+                    let _ = tx.send(result);
+                });
+
+            match rx.recv() {
+                Ok(Some(path)) => PathBuf::from(path.to_string()),
+                _ => return Ok(None),
+            }
+        }
+    };
+
+    let options = request.options;
+    let output = file_path.clone();
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<()> {
+        let images = selected
+            .iter()
+            .map(|item| {
+                let path = PathBuf::from(&item.file_path);
+                Ok(pdf::PdfImage {
+                    image: image::open(&path)?,
+                    caption: pdf::caption_for(options.caption, &path, Some(&item.timestamp)),
+                })
+            })
+            .collect::<GrabResult<Vec<_>>>()?;
+
+        fs::write(&output, pdf::write_pdf(&images, &options)?)?;
+        Ok(())
+    })
+    .await??;
+
+    Ok(Some(file_path.to_string_lossy().to_string()))
+}
+
+// ============================================================================
+// Project Commands
+// ============================================================================
+
+/// Open a `.grab` project for editing
+#[tauri::command]
+pub async fn open_project(path: String) -> Result<OpenedProject, GrabError> {
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<OpenedProject> {
+        let contents = project::read_project(&PathBuf::from(&path))?;
+        Ok(OpenedProject {
+            path,
+            image: format!(
+                "data:image/png;base64,{}",
+                base64::engine::general_purpose::STANDARD.encode(&contents.original_png)
+            ),
+            project: contents.project,
+        })
+    })
+    .await?
+}
+
+/// Save layers and crop to a project, creating it beside the capture if needed
+///
+/// Returns the project path. The capture's history entry is linked to it.
+#[tauri::command]
+pub async fn save_project(
+    request: SaveProjectRequest,
+    history: State<'_, HistoryStore>,
+) -> Result<String, GrabError> {
+    let project_path = match (&request.project_path, &request.source_path) {
+        (Some(path), _) => PathBuf::from(path),
+        (None, Some(source)) => project::project_path_for(&PathBuf::from(source)),
+        (None, None) => {
+            return Err(GrabError::InvalidRequest(
+                "Project needs a project or source path".to_string(),
+            ));
+        }
+    };
+    let history_item = match request.source_path {
+        Some(ref source) => history.get_by_path(source)?,
+        None => None,
+    };
+
+    let path = project_path.clone();
+    let source_path = request.source_path.clone();
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<()> {
+        let (original, metadata) = if path.is_file() {
+            let contents = project::read_project(&path)?;
+            (contents.original()?, contents.project.metadata)
+        } else {
+            let source = source_path.as_ref().ok_or_else(|| {
+                GrabError::SourceNotFound(path.to_string_lossy().to_string())
+            })?;
+            let bytes = fs::read(source)?;
+            let original = image::load_from_memory(&bytes)?.to_rgba8();
+            let metadata = metadata::read_embedded(&bytes).unwrap_or_else(|| {
+                fallback_metadata(&original, source, history_item.as_ref())
+            });
+            (original, metadata)
+        };
+
+        let grab_project = GrabProject {
+            metadata,
+            crop: request.crop,
+            redactions: request.redactions,
+            layers: request.layers,
+        };
+        project::write_project(&path, &grab_project, &original)
+    })
+    .await??;
+
+    let project_path = project_path.to_string_lossy().to_string();
+    if let Some(source) = request.source_path {
+        history.set_project_path(&source, Some(project_path.clone()))?;
+    }
+
+    Ok(project_path)
+}
+
+/// Flatten a project's visible layers and crop, then export it
+#[tauri::command]
+pub async fn export_project(
+    request: ProjectExportRequest,
+    app: AppHandle,
+    prefs: State<'_, PreferencesStore>,
+) -> Result<Option<String>, GrabError> {
+    let preferences = prefs.get();
+    let mut options = match (request.options, request.preset) {
+        (Some(options), _) => options,
+        (None, Some(name)) => export::find_preset(&preferences.export_presets, &name)
+            .map(|p| p.options.clone())
+            .ok_or_else(|| GrabError::InvalidRequest(format!("Unknown export preset: {}", name)))?,
+        (None, None) => ExportOptions::default(),
+    };
+    if let Some(ref name) = request.beautify_style {
+        beautify::use_style(&mut options, &preferences.beautify_styles, name)?;
+    }
+    watermark::use_default(&mut options, &preferences.watermark);
+    let ext = options.format.extension();
+
+    let project_path = PathBuf::from(&request.project_path);
+    let file_path = match request.output_path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            use std::sync::mpsc;
+
+            let stem = project_path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "capture".to_string());
+            let (tx, rx) = mpsc::channel();
+            app.dialog()
+                .file()
+                .set_title("Export Project")
+                .set_file_name(format!("{}.{}", stem, ext))
+                .add_filter("Images", &[ext])
+                .save_file(move |result| {
+                    let _ = tx.send(result);
+                });
+
+            match rx.recv() {
+                Ok(Some(path)) => PathBuf::from(path.to_string()),
+                _ => return Ok(None),
+            }
+        }
+    };
+
+    let output = file_path.clone();
+    let strip_metadata = preferences.strip_metadata;
+    tauri::async_runtime::spawn_blocking(move || -> GrabResult<()> {
+        let contents = project::read_project(&project_path)?;
+        let flattened = project::flatten(&contents.project, &contents.original()?)?;
+        let metadata = redact::record(&contents.project.metadata, &contents.project.redactions);
+        export::export_to_file(
+            &image::DynamicImage::ImageRgba8(flattened),
+            &options,
+            Some(&metadata).filter(|_| !strip_metadata),
+            metadata.scale_factor,
+            &output,
+        )
+    })
+    .await??;
+
     Ok(Some(file_path.to_string_lossy().to_string()))
 }
 
@@ -377,11 +1403,152 @@ pub fn export_capture(
 // Internal Helper Functions
 // ============================================================================
 
+/// Read image bytes from a base64 data URL or a file path
+fn decode_image_data(data: &str) -> GrabResult<Vec<u8>> {
+    if data.starts_with("data:") {
+        let base64_data = data
+            .split(',')
+            .nth(1)
+            .ok_or_else(|| GrabError::InvalidRequest("Invalid data URL".to_string()))?;
+
+        base64::engine::general_purpose::STANDARD
+            .decode(base64_data)
+            .map_err(|e| GrabError::InvalidRequest(e.to_string()))
+    } else {
+        Ok(fs::read(data)?)
+    }
+}
+
+/// Encode an image as a PNG data URL for the UI
+fn encode_png_data_url(image: &RgbaImage) -> GrabResult<String> {
+    let mut png = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png)
+    ))
+}
+
+/// File behind a request naming a history item or a file
+fn source_path(
+    history: &HistoryStore,
+    item_id: Option<&str>,
+    file_path: Option<&str>,
+    operation: &str,
+) -> GrabResult<PathBuf> {
+    match (item_id, file_path) {
+        (Some(id), _) => history
+            .get(id)?
+            .map(|item| PathBuf::from(item.file_path))
+            .ok_or_else(|| GrabError::SourceNotFound(id.to_string())),
+        (None, Some(path)) => Ok(PathBuf::from(path)),
+        (None, None) => Err(GrabError::InvalidRequest(format!(
+            "{} needs a history item or file",
+            operation
+        ))),
+    }
+}
+
+/// Move a capture to the trash, or delete it, and drop it from history
+fn delete_capture(history: &HistoryStore, file_path: &str) -> GrabResult<bool> {
+    // Try to move to trash
+    let path = PathBuf::from(file_path);
+
+    if path.exists() {
+        // Use trash crate if available, otherwise just delete
+        #[cfg(feature = "trash")]
+        {
+            trash::delete(&path).map_err(|e| GrabError::ExportFailed(e.to_string()))?;
+        }
+        #[cfg(not(feature = "trash"))]
+        {
+            fs::remove_file(&path)?;
+        }
+
+        // Remove from history
+        history.remove(file_path)?;
+
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Queue writing the library's tags into the captures' files
+///
+/// Skipped when metadata is stripped. The optimizer does the writing, so it
+/// never swaps a file in over a tagged one. Returns the items.
+fn embed_tags(
+    history: &HistoryStore,
+    optimizer: &OptimizeWorker,
+    item_ids: &[String],
+    preferences: &CapturePreferences,
+) -> GrabResult<Vec<HistoryItem>> {
+    let mut items = Vec::new();
+    for id in item_ids {
+        items.push(history.get(id)?.ok_or_else(|| GrabError::SourceNotFound(id.clone()))?);
+    }
+    if preferences.strip_metadata {
+        return Ok(items);
+    }
+
+    for item in &items {
+        let fallback_item = item.clone();
+        optimizer.queue_tags(
+            PathBuf::from(&item.file_path),
+            item.tags.clone(),
+            Box::new(move |bytes| {
+                let image = image::load_from_memory(bytes)?.to_rgba8();
+                Ok(fallback_metadata(&image, &fallback_item.file_path, Some(&fallback_item)))
+            }),
+        );
+    }
+    Ok(items)
+}
+
+/// Scale factor of a capture: the override, else what it was captured at
+fn scale_factor(path: &Path, requested: Option<f64>) -> f64 {
+    requested
+        .or_else(|| metadata::read_from_file(path).map(|m| m.scale_factor))
+        .unwrap_or(1.0)
+}
+
+/// Metadata for a capture that doesn't carry its own, e.g. an imported image
+fn fallback_metadata(
+    image: &RgbaImage,
+    source_path: &str,
+    item: Option<&HistoryItem>,
+) -> CaptureMetadata {
+    CaptureMetadata {
+        mode: item.and_then(|i| i.mode).unwrap_or(CaptureMode::Region),
+        display_id: None,
+        window_id: None,
+        bounds: RegionBounds {
+            x: 0,
+            y: 0,
+            width: image.width(),
+            height: image.height(),
+        },
+        timestamp: item
+            .map(|i| i.timestamp.clone())
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+        scale_factor: 1.0,
+        file_name: PathBuf::from(source_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string()),
+        window_title: item.and_then(|i| i.window_title.clone()),
+        app_name: item.and_then(|i| i.app_name.clone()),
+        redactions: Vec::new(),
+        trim: None,
+        tags: item.map(|i| i.tags.clone()).unwrap_or_default(),
+    }
+}
+
 /// Save capture and process (clipboard, notifications, etc.)
 async fn save_and_process_capture(
     app: &AppHandle,
     image: &RgbaImage,
-    mut metadata: crate::types::CaptureMetadata,
+    mut metadata: CaptureMetadata,
     preferences: &CapturePreferences,
     history: &State<'_, HistoryStore>,
 ) -> GrabResult<CaptureResult> {
@@ -390,8 +1557,46 @@ async fn save_and_process_capture(
     let mut file_path: Option<String> = None;
     let mut copied_to_clipboard = false;
 
+    // Trimmed first, so the file, clipboard and exports all agree
+    let trimmed = match preferences.auto_trim {
+        Some(ref options) => trim::trim(image, options, metadata.scale_factor),
+        None => None,
+    };
+    if let Some((_, ref bounds)) = trimmed {
+        metadata = trim::record(&metadata, bounds);
+    }
+    let image = trimmed.as_ref().map(|(image, _)| image).unwrap_or(image);
+
+    // Everything from here on, the clipboard included, gets the stamped image
+    let stamped = if preferences.watermark.on_save {
+        watermark::apply(
+            image,
+            &preferences.watermark.watermark,
+            Some(&metadata),
+            metadata.scale_factor,
+        )
+        .map_err(|e| eprintln!("Watermark failed: {}", e))
+        .ok()
+    } else {
+        None
+    };
+    let image = stamped.as_ref().unwrap_or(image);
+
+    // A repeat of the last capture points at the earlier file instead
+    let hashes = preferences.save_to_disk.then(|| dedupe::hash(image));
+    let repeat_of = match hashes {
+        Some(ref hashes) if preferences.skip_identical_captures => history
+            .get_latest()?
+            .filter(|item| item.content_hash.as_deref() == Some(hashes.content.as_str()))
+            .filter(|item| Path::new(&item.file_path).exists()),
+        _ => None,
+    };
+    if let Some(ref previous) = repeat_of {
+        file_path = Some(previous.file_path.clone());
+    }
+
     // Save to disk if enabled
-    if preferences.save_to_disk {
+    if let Some(hashes) = hashes.filter(|_| repeat_of.is_none()) {
         let save_start = Instant::now();
         let output_folder = PathBuf::from(&preferences.output_folder);
 
@@ -401,19 +1606,43 @@ async fn save_and_process_capture(
         // Generate filename
         let filename = capture::generate_filename(&preferences.naming_template, metadata.mode);
         let full_path = output_folder.join(format!("{}.png", filename));
+        metadata.file_name = Some(filename);
 
-        // Save image
-        capture::save_image(image, &full_path)?;
+        // Save image, embedding metadata unless the user opted out
+        let embedded = (!preferences.strip_metadata).then_some(&metadata);
+        capture::save_image(image, &full_path, embedded)?;
         eprintln!("[PERF] save_and_process: save_image took {:?}", save_start.elapsed());
 
         let path_str = full_path.to_string_lossy().to_string();
-        metadata.file_name = Some(filename);
         file_path = Some(path_str.clone());
 
         // Add to history
-        history.add(path_str)?;
+        history.add(path_str, &metadata, image.dimensions(), hashes)?;
+        history.prune(preferences.history_limit)?;
+
+        if preferences.ocr.index_captures {
+            app.state::<SearchIndexer>().queue(full_path.clone(), preferences.ocr.clone());
+        }
+
+        // Shrink the file in the background; the fast PNG is already usable
+        if preferences.optimize.enabled {
+            app.state::<OptimizeWorker>().queue(full_path.clone(), preferences.optimize.clone());
+        }
+
+        if let Some(ref preset_name) = preferences.auto_export_preset {
+            auto_export(image, &metadata, preferences, preset_name, &full_path);
+        }
     }
 
+    // The framed copy is what gets pasted; the saved capture stays raw
+    let framed = match preferences.auto_beautify_style {
+        Some(ref name) => {
+            auto_beautify(image, &metadata, preferences, name, file_path.as_deref()).await
+        }
+        None => None,
+    };
+    let clipboard_source = framed.as_ref().unwrap_or(image);
+
     // IMMEDIATELY emit history refresh so UI updates while clipboard copies
     // This makes the app feel much more responsive
     let emit_start = Instant::now();
@@ -430,14 +1659,18 @@ async fn save_and_process_capture(
     }
     eprintln!("[PERF] save_and_process: emit events took {:?}", emit_start.elapsed());
 
+    if preferences.scan_codes_after_capture {
+        offer_decoded_codes(app, image, file_path.clone());
+    }
+
     // Copy to clipboard AFTER UI is updated (user sees result immediately)
     if preferences.copy_to_clipboard {
         let clipboard_start = Instant::now();
         // Use new_owned with the raw buffer - Tauri handles this efficiently
         let clipboard_img = tauri::image::Image::new_owned(
-            image.as_raw().clone(),
-            image.width(),
-            image.height(),
+            clipboard_source.as_raw().clone(),
+            clipboard_source.width(),
+            clipboard_source.height(),
         );
         app.clipboard()
             .write_image(&clipboard_img)
@@ -457,7 +1690,11 @@ async fn save_and_process_capture(
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_default();
-            message = format!("Saved to {}", filename);
+            message = if repeat_of.is_some() {
+                format!("Same as {}", filename)
+            } else {
+                format!("Saved to {}", filename)
+            };
         }
 
         if copied_to_clipboard {
@@ -484,6 +1721,128 @@ async fn save_and_process_capture(
     })
 }
 
+/// Look for codes in a new capture in the background
+///
+/// Emits `capture:codes` when any are found so the UI can offer to copy
+/// their text.
+fn offer_decoded_codes(app: &AppHandle, image: &RgbaImage, file_path: Option<String>) {
+    let handle = app.clone();
+    let image = image.clone();
+
+    tauri::async_runtime::spawn_blocking(move || match barcode::scan(&image, None) {
+        Ok(codes) if !codes.is_empty() => {
+            if let Some(window) = handle.get_webview_window("main") {
+                window
+                    .emit("capture:codes", CapturedCodes { file_path, codes })
+                    .ok();
+            }
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Code scan failed: {}", e),
+    });
+}
+
+/// Export a new capture with the auto-export preset, in the background
+///
+/// Files go to an `exports` folder next to the capture so they don't show
+/// up in history as separate captures.
+fn auto_export(
+    image: &RgbaImage,
+    metadata: &CaptureMetadata,
+    preferences: &CapturePreferences,
+    preset_name: &str,
+    capture_path: &std::path::Path,
+) {
+    let Some(preset) = export::find_preset(&preferences.export_presets, preset_name) else {
+        eprintln!("Auto-export preset not found: {}", preset_name);
+        return;
+    };
+
+    let stem = capture_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let export_path = capture_path.with_file_name("exports").join(format!(
+        "{}-{}.{}",
+        stem,
+        export::preset_slug(&preset.name),
+        preset.options.format.extension()
+    ));
+
+    let image = image::DynamicImage::ImageRgba8(image.clone());
+    let mut options = preset.options.clone();
+    // Already stamped when watermarking on save
+    if !preferences.watermark.on_save {
+        watermark::use_default(&mut options, &preferences.watermark);
+    }
+    let embedded = (!preferences.strip_metadata).then(|| metadata.clone());
+    let scale_factor = metadata.scale_factor;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) =
+            export::export_to_file(&image, &options, embedded.as_ref(), scale_factor, &export_path)
+        {
+            eprintln!("Auto-export failed for {}: {}", export_path.display(), e);
+        }
+    });
+}
+
+/// Frame a new capture with the auto-beautify style
+///
+/// The framed copy is written to the `exports` folder next to the capture,
+/// when there is one, and returned for the clipboard. Failures are logged
+/// and leave the capture as it is.
+async fn auto_beautify(
+    image: &RgbaImage,
+    metadata: &CaptureMetadata,
+    preferences: &CapturePreferences,
+    style_name: &str,
+    capture_path: Option<&str>,
+) -> Option<RgbaImage> {
+    let Some(style) = beautify::find_style(&preferences.beautify_styles, style_name) else {
+        eprintln!("Auto-beautify style not found: {}", style_name);
+        return None;
+    };
+
+    let export_path = capture_path.map(|path| {
+        let path = PathBuf::from(path);
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        path.with_file_name("exports")
+            .join(format!("{}-{}.png", stem, export::preset_slug(&style.name)))
+    });
+
+    let image = image.clone();
+    let style = style.clone();
+    let embedded = (!preferences.strip_metadata).then(|| metadata.clone());
+    let scale_factor = metadata.scale_factor;
+
+    let framed = tauri::async_runtime::spawn_blocking(move || -> GrabResult<RgbaImage> {
+        let framed = beautify::apply(&image, &style, scale_factor)?;
+        if let Some(ref path) = export_path {
+            export::export_to_file(
+                &image::DynamicImage::ImageRgba8(framed.clone()),
+                &ExportOptions::default(),
+                embedded.as_ref(),
+                scale_factor,
+                path,
+            )?;
+        }
+        Ok(framed)
+    })
+    .await;
+
+    match framed.map_err(GrabError::from).and_then(|framed| framed) {
+        Ok(framed) => Some(framed),
+        Err(e) => {
+            eprintln!("Auto-beautify failed: {}", e);
+            None
+        }
+    }
+}
+
 /// Trigger full screen capture (called from shortcuts/tray)
 pub async fn trigger_capture_full_screen(app: &AppHandle) -> GrabResult<()> {
     let prefs = app.state::<PreferencesStore>();
@@ -497,6 +1856,45 @@ pub async fn trigger_capture_full_screen(app: &AppHandle) -> GrabResult<()> {
     Ok(())
 }
 
+/// Capture the screen and decode the codes on it (called from shortcuts)
+///
+/// The text of every code found is copied to the clipboard, one per line.
+/// Nothing is saved; the codes are also sent to the UI as `codes:scanned`.
+pub async fn trigger_scan_code(app: &AppHandle) -> GrabResult<()> {
+    let (image, _) = capture::capture_full_screen()?;
+    let codes = tauri::async_runtime::spawn_blocking(move || barcode::scan(&image, None)).await??;
+    let show_notifications = app.state::<PreferencesStore>().get().show_notifications;
+
+    let (title, body) = if codes.is_empty() {
+        ("No Code Found", "No QR code or barcode is visible on screen".to_string())
+    } else {
+        let text = codes.iter().map(|c| c.text.as_str()).collect::<Vec<_>>().join("\n");
+        app.clipboard()
+            .write_text(text)
+            .map_err(|e| GrabError::ClipboardFailed(e.to_string()))?;
+
+        if let Some(window) = app.get_webview_window("main") {
+            let scanned = CapturedCodes {
+                file_path: None,
+                codes: codes.clone(),
+            };
+            window.emit("codes:scanned", scanned).ok();
+        }
+
+        let preview: String = codes[0].text.chars().take(80).collect();
+        match codes.len() {
+            1 => ("Code Copied", preview),
+            n => ("Codes Copied", format!("{} and {} more", preview, n - 1)),
+        }
+    };
+
+    if show_notifications {
+        app.notification().builder().title(title).body(&body).show().ok();
+    }
+
+    Ok(())
+}
+
 /// Trigger window capture (called from shortcuts/tray)
 pub async fn trigger_capture_window(app: &AppHandle) -> GrabResult<()> {
     // For window capture, we need user to select a window
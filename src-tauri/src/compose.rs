@@ -0,0 +1,301 @@
+//! Collages of several captures
+//!
+//! Lays captures out in a row, a column or a grid with gutters and optional
+//! labels, e.g. for before/after or step-by-step composites. Images of
+//! different sizes are scaled to a common height (or width) so they line up.
+
+use crate::annotate;
+use crate::error::{GrabError, GrabResult};
+use crate::types::{
+    Annotation, Color, CompositionAlign, CompositionLayout, CompositionOptions, CompositionSizing,
+};
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use std::borrow::Cow;
+
+/// Largest composite, to stay well within memory
+const MAX_PIXELS: u64 = 100_000_000;
+
+/// Label font size, in logical pixels
+const LABEL_FONT_SIZE: f32 = 16.0;
+
+/// One image of a composite
+pub struct CompositionImage {
+    pub image: RgbaImage,
+    pub label: Option<String>,
+}
+
+/// Lay images out into one new image
+///
+/// `scale_factor` sizes the gutters, padding and labels, so a composite of
+/// HiDPI captures doesn't end up with hairline gutters.
+pub fn compose(
+    images: &[CompositionImage],
+    options: &CompositionOptions,
+    scale_factor: f64,
+) -> GrabResult<RgbaImage> {
+    if images.is_empty() {
+        return Err(GrabError::InvalidRequest(
+            "No images to compose".to_string(),
+        ));
+    }
+    let scale = if scale_factor.is_finite() && scale_factor > 0.0 {
+        scale_factor as f32
+    } else {
+        1.0
+    };
+
+    let count = images.len();
+    let columns = match options.layout {
+        CompositionLayout::Horizontal => count,
+        CompositionLayout::Vertical => 1,
+        CompositionLayout::Grid => options
+            .columns
+            .map(|c| c as usize)
+            .unwrap_or_else(|| (count as f64).sqrt().ceil() as usize)
+            .clamp(1, count),
+    };
+    let rows = count.div_ceil(columns);
+
+    let sized = fit(images, options.sizing, options.layout);
+
+    let gutter = (options.gutter as f32 * scale).round() as u32;
+    let padding = (options.padding as f32 * scale).round() as u32;
+    let font_size = LABEL_FONT_SIZE * scale;
+    let labels: Vec<Option<&str>> = images
+        .iter()
+        .map(|i| i.label.as_deref().filter(|l| !l.trim().is_empty()))
+        .collect();
+    // One band for labels under every row, so they line up
+    let label_gap = (font_size * 0.5).round() as u32;
+    let label_height = if labels.iter().any(Option::is_some) {
+        annotate::text_size("Ag", font_size, false, false).1.ceil() as u32 + label_gap
+    } else {
+        0
+    };
+
+    let mut widths = vec![0; columns];
+    let mut heights = vec![0; rows];
+    for (i, image) in sized.iter().enumerate() {
+        let label_width = labels[i]
+            .map(|l| annotate::text_size(l, font_size, false, false).0.ceil() as u32)
+            .unwrap_or(0);
+        widths[i % columns] = widths[i % columns].max(image.width()).max(label_width);
+        heights[i / columns] = heights[i / columns].max(image.height() + label_height);
+    }
+
+    let width = 2 * padding + widths.iter().sum::<u32>() + gutter * (columns as u32 - 1);
+    let height = 2 * padding + heights.iter().sum::<u32>() + gutter * (rows as u32 - 1);
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(GrabError::InvalidRequest(format!(
+            "Composite would be {}x{}, which is too large",
+            width, height
+        )));
+    }
+
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba(options.background.0));
+    let text_color = {
+        let [r, g, b, _] = options.background.0;
+        if 299 * r as u32 + 587 * g as u32 + 114 * b as u32 > 128_000 {
+            Color([32, 32, 32, 255])
+        } else {
+            Color([240, 240, 240, 255])
+        }
+    };
+
+    let mut annotations = Vec::new();
+    let mut y = padding;
+    for (row, row_height) in heights.iter().enumerate() {
+        let mut x = padding;
+        for (column, column_width) in widths.iter().enumerate() {
+            let index = row * columns + column;
+            let Some(image) = sized.get(index) else {
+                break;
+            };
+
+            let image_height = row_height - label_height;
+            let left = x + offset(options.align, column_width - image.width());
+            let top = y + offset(options.align, image_height - image.height());
+            imageops::overlay(&mut canvas, image.as_ref(), left as i64, top as i64);
+
+            if let Some(label) = labels[index] {
+                let label_width = annotate::text_size(label, font_size, false, false).0;
+                annotations.push(Annotation::Text {
+                    x: x as f32 + (*column_width as f32 - label_width) / 2.0,
+                    y: (y + image_height + label_gap) as f32,
+                    text: label.to_string(),
+                    color: text_color,
+                    font_size,
+                    bold: false,
+                    background: None,
+                });
+            }
+            x += column_width + gutter;
+        }
+        y += row_height + gutter;
+    }
+
+    if annotations.is_empty() {
+        Ok(canvas)
+    } else {
+        annotate::render(&canvas, &annotations)
+    }
+}
+
+/// Scale images down to a common height, or width for a column
+fn fit(
+    images: &[CompositionImage],
+    sizing: CompositionSizing,
+    layout: CompositionLayout,
+) -> Vec<Cow<'_, RgbaImage>> {
+    if sizing == CompositionSizing::Original {
+        return images.iter().map(|i| Cow::Borrowed(&i.image)).collect();
+    }
+
+    let by_width = layout == CompositionLayout::Vertical;
+    let target = images
+        .iter()
+        .map(|i| {
+            if by_width {
+                i.image.width()
+            } else {
+                i.image.height()
+            }
+        })
+        .min()
+        .unwrap_or(0)
+        .max(1);
+
+    images
+        .iter()
+        .map(|i| {
+            let (w, h) = i.image.dimensions();
+            let (width, height) = if by_width {
+                (
+                    target,
+                    (h as f64 * target as f64 / w as f64).round().max(1.0) as u32,
+                )
+            } else {
+                (
+                    (w as f64 * target as f64 / h as f64).round().max(1.0) as u32,
+                    target,
+                )
+            };
+            if (width, height) == (w, h) {
+                Cow::Borrowed(&i.image)
+            } else {
+                Cow::Owned(imageops::resize(
+                    &i.image,
+                    width,
+                    height,
+                    FilterType::Lanczos3,
+                ))
+            }
+        })
+        .collect()
+}
+
+fn offset(align: CompositionAlign, space: u32) -> u32 {
+    match align {
+        CompositionAlign::Start => 0,
+        CompositionAlign::Center => space / 2,
+        CompositionAlign::End => space,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    fn image(width: u32, height: u32, color: Rgba<u8>) -> CompositionImage {
+        CompositionImage {
+            image: RgbaImage::from_pixel(width, height, color),
+            label: None,
+        }
+    }
+
+    #[test]
+    fn test_compose_layouts() {
+        let images = [image(100, 50, RED), image(50, 100, BLUE)];
+        let options = CompositionOptions {
+            gutter: 10,
+            padding: 10,
+            ..Default::default()
+        };
+
+        // Side by side at a common height: the tall image is halved
+        let row = compose(&images, &options, 1.0).unwrap();
+        assert_eq!(row.dimensions(), (155, 70));
+        assert_eq!(*row.get_pixel(60, 35), RED);
+        assert_eq!(*row.get_pixel(132, 35), BLUE);
+        assert_eq!(*row.get_pixel(115, 35), WHITE);
+
+        // Stacked at a common width, with spacing scaled for HiDPI
+        let column = compose(
+            &images,
+            &CompositionOptions {
+                layout: CompositionLayout::Vertical,
+                ..options.clone()
+            },
+            2.0,
+        )
+        .unwrap();
+        assert_eq!(column.dimensions(), (90, 185));
+        assert_eq!(*column.get_pixel(45, 40), RED);
+        assert_eq!(*column.get_pixel(45, 120), BLUE);
+
+        // Original sizes in a grid, aligned to the end of each cell
+        let images = [
+            image(100, 50, RED),
+            image(50, 100, BLUE),
+            image(40, 40, RED),
+        ];
+        let grid = compose(
+            &images,
+            &CompositionOptions {
+                layout: CompositionLayout::Grid,
+                sizing: CompositionSizing::Original,
+                align: CompositionAlign::End,
+                ..options.clone()
+            },
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(grid.dimensions(), (180, 170));
+        assert_eq!(*grid.get_pixel(50, 20), WHITE);
+        assert_eq!(*grid.get_pixel(50, 100), RED);
+        assert_eq!(*grid.get_pixel(90, 140), RED);
+        assert_eq!(*grid.get_pixel(60, 140), WHITE);
+
+        assert!(compose(&[], &options, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_compose_labels() {
+        let mut before = image(100, 50, RED);
+        before.label = Some("Before".to_string());
+        let mut after = image(100, 50, BLUE);
+        after.label = Some("After".to_string());
+
+        let options = CompositionOptions {
+            gutter: 10,
+            padding: 10,
+            background: Color([0, 0, 0, 255]),
+            ..Default::default()
+        };
+        let plain = compose(&[image(100, 50, RED), image(100, 50, BLUE)], &options, 1.0).unwrap();
+        let labelled = compose(&[before, after], &options, 1.0).unwrap();
+        assert_eq!(labelled.width(), plain.width());
+        assert!(labelled.height() > plain.height() + 16);
+
+        // Light text on the dark background, under the images
+        let band = (60..labelled.height() - 10)
+            .flat_map(|y| (10..110).map(move |x| (x, y)))
+            .any(|(x, y)| labelled.get_pixel(x, y).0[0] > 128);
+        assert!(band);
+    }
+}
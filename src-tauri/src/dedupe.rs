@@ -0,0 +1,331 @@
+//! Perceptual hashing and duplicate detection
+//!
+//! A difference hash (dHash) sums up an image's structure in 64 bits: shrink
+//! it to 9x8 in greyscale and note whether each pixel is brighter than its
+//! right-hand neighbour. Near-identical captures differ in a few bits and
+//! unrelated ones in about half. A digest of the pixels tells exact copies
+//! apart from captures that merely look alike.
+//!
+//! New captures are hashed as they're saved. Files the library picks up by
+//! scanning the output folder are hashed by a worker thread, since that
+//! means decoding each one.
+
+use crate::error::GrabResult;
+use crate::history::HistoryStore;
+use crate::types::{DuplicateGroup, HistoryItem};
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+use tauri::{AppHandle, Manager};
+
+/// Bits two hashes may differ by and still count as near-duplicates
+pub const DEFAULT_THRESHOLD: u32 = 4;
+
+/// Hashes recorded for a capture in history
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageHashes {
+    /// dHash, as 16 hex digits
+    pub perceptual: String,
+    /// Digest of the size and pixels, as hex
+    pub content: String,
+}
+
+/// Worker that hashes captures recorded without hashes
+pub struct HashWorker {
+    sender: Mutex<Sender<()>>,
+}
+
+impl HashWorker {
+    /// Spawn the worker thread
+    pub fn new(app_handle: &AppHandle) -> GrabResult<Self> {
+        let (sender, receiver) = mpsc::channel::<()>();
+        let handle = app_handle.clone();
+
+        thread::Builder::new()
+            .name("grab-hash".to_string())
+            .spawn(move || {
+                for () in receiver {
+                    backfill(&handle.state::<HistoryStore>());
+                }
+            })?;
+
+        Ok(HashWorker {
+            sender: Mutex::new(sender),
+        })
+    }
+
+    /// Queue a pass over the captures without hashes
+    pub fn queue_unhashed(&self) {
+        if self.sender.lock().unwrap().send(()).is_err() {
+            eprintln!("Hash worker is not running");
+        }
+    }
+}
+
+/// Hash every capture in the library that has no hashes yet
+///
+/// Files that can't be decoded stay unhashed and are tried again next time.
+pub fn backfill(history: &HistoryStore) {
+    let paths = match history.unhashed() {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("Failed to list captures to hash: {}", e);
+            return;
+        }
+    };
+    for path in paths {
+        let Some(hashes) = hash_file(Path::new(&path)) else {
+            continue;
+        };
+        if let Err(e) = history.set_hashes(&path, &hashes) {
+            eprintln!("Failed to record hashes of {}: {}", path, e);
+        }
+    }
+}
+
+pub fn hash(image: &RgbaImage) -> ImageHashes {
+    ImageHashes {
+        perceptual: perceptual_hash(image),
+        content: content_hash(image),
+    }
+}
+
+/// Hash an image file, or `None` if it can't be read
+pub fn hash_file(path: &Path) -> Option<ImageHashes> {
+    image::open(path).ok().map(|image| hash(&image.to_rgba8()))
+}
+
+pub fn perceptual_hash(image: &RgbaImage) -> String {
+    let small = imageops::resize(image, 9, 8, FilterType::Triangle);
+    let mut bits = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            bits <<= 1;
+            if luma(small.get_pixel(x, y)) > luma(small.get_pixel(x + 1, y)) {
+                bits |= 1;
+            }
+        }
+    }
+    format!("{:016x}", bits)
+}
+
+pub fn content_hash(image: &RgbaImage) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(image.width().to_le_bytes());
+    hasher.update(image.height().to_le_bytes());
+    hasher.update(image.as_raw());
+    // Half the digest is plenty to tell captures apart
+    hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Number of bits two perceptual hashes differ by
+pub fn distance(a: &str, b: &str) -> Option<u32> {
+    let a = u64::from_str_radix(a, 16).ok()?;
+    let b = u64::from_str_radix(b, 16).ok()?;
+    Some((a ^ b).count_ones())
+}
+
+/// Group items whose hashes are within `threshold` bits of each other
+///
+/// Similarity is chained, so a group can hold two items further apart than
+/// the threshold if others link them. Items without hashes are skipped.
+/// Groups and the items in them are newest first.
+pub fn find_duplicates(items: &[HistoryItem], threshold: u32) -> Vec<DuplicateGroup> {
+    let hashed: Vec<&HistoryItem> = items
+        .iter()
+        .filter(|item| item.perceptual_hash.is_some())
+        .collect();
+    let similar = |a: &HistoryItem, b: &HistoryItem| {
+        let same_content = a.content_hash.is_some() && a.content_hash == b.content_hash;
+        same_content
+            || matches!(
+                (&a.perceptual_hash, &b.perceptual_hash),
+                (Some(a), Some(b)) if distance(a, b).is_some_and(|d| d <= threshold)
+            )
+    };
+
+    let mut group_of: Vec<Option<usize>> = vec![None; hashed.len()];
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for start in 0..hashed.len() {
+        if group_of[start].is_some() {
+            continue;
+        }
+        let id = groups.len();
+        group_of[start] = Some(id);
+        let mut members = vec![start];
+        let mut next = 0;
+        while next < members.len() {
+            let current = members[next];
+            for other in 0..hashed.len() {
+                if group_of[other].is_none() && similar(hashed[current], hashed[other]) {
+                    group_of[other] = Some(id);
+                    members.push(other);
+                }
+            }
+            next += 1;
+        }
+        groups.push(members);
+    }
+
+    let mut found: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let mut group: Vec<HistoryItem> = members.iter().map(|&i| hashed[i].clone()).collect();
+            group.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+            let exact = group.iter().all(|item| {
+                item.content_hash.is_some() && item.content_hash == group[0].content_hash
+            });
+            let mut widest = 0;
+            for (i, a) in group.iter().enumerate() {
+                for b in &group[i + 1..] {
+                    if let (Some(a), Some(b)) = (&a.perceptual_hash, &b.perceptual_hash) {
+                        widest = widest.max(distance(a, b).unwrap_or(0));
+                    }
+                }
+            }
+            DuplicateGroup {
+                items: group,
+                exact,
+                distance: widest,
+            }
+        })
+        .collect();
+    found.sort_by(|a, b| b.items[0].timestamp.cmp(&a.items[0].timestamp));
+    found
+}
+
+/// Items of a group that duplicate its newest, within `threshold` bits
+///
+/// Groups are chained, so the rest may not look like the newest at all.
+pub fn duplicates_of_newest(group: &DuplicateGroup, threshold: u32) -> Vec<&HistoryItem> {
+    let Some((newest, rest)) = group.items.split_first() else {
+        return Vec::new();
+    };
+    rest.iter()
+        .filter(|item| {
+            let same_content =
+                item.content_hash.is_some() && item.content_hash == newest.content_hash;
+            same_content
+                || matches!(
+                    (&item.perceptual_hash, &newest.perceptual_hash),
+                    (Some(a), Some(b)) if distance(a, b).is_some_and(|d| d <= threshold)
+                )
+        })
+        .collect()
+}
+
+fn luma(pixel: &Rgba<u8>) -> u32 {
+    let [r, g, b, _] = pixel.0;
+    299 * r as u32 + 587 * g as u32 + 114 * b as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Light page with dark lines of "text"
+    fn page(lines: u32) -> RgbaImage {
+        RgbaImage::from_fn(320, 240, |x, y| {
+            let line = y / 20;
+            let text = line < lines && y % 20 < 8 && x > 20 && x < 40 + line * 25;
+            if text {
+                Rgba([30, 30, 30, 255])
+            } else {
+                Rgba([250, 250, 250, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn test_hashes() {
+        let original = page(8);
+        let mut retouched = original.clone();
+        retouched.put_pixel(200, 200, Rgba([0, 0, 0, 255]));
+        let different = page(3);
+
+        let a = hash(&original);
+        let b = hash(&retouched);
+        let c = hash(&different);
+        assert_eq!(a.perceptual.len(), 16);
+        assert_eq!(a.content.len(), 32);
+
+        // One pixel changes the digest but barely the dHash
+        assert_ne!(a.content, b.content);
+        assert!(distance(&a.perceptual, &b.perceptual).unwrap() <= 1);
+        assert!(distance(&a.perceptual, &c.perceptual).unwrap() > DEFAULT_THRESHOLD);
+        assert_eq!(hash(&original.clone()), a);
+
+        assert_eq!(distance("ff", "0f"), Some(4));
+        assert_eq!(distance("ff", "nope"), None);
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let item = |id: &str, timestamp: &str, perceptual: &str, content: &str| HistoryItem {
+            id: id.to_string(),
+            file_path: format!("/tmp/{}.png", id),
+            timestamp: timestamp.to_string(),
+            thumbnail: None,
+            mode: None,
+            window_title: None,
+            app_name: None,
+            original_size: None,
+            optimized_size: None,
+            project_path: None,
+            perceptual_hash: Some(perceptual.to_string()),
+            content_hash: Some(content.to_string()),
+            width: None,
+            height: None,
+            file_size: None,
+            tags: Vec::new(),
+            pinned: false,
+        };
+        let items = vec![
+            item("a", "2024-01-01T10:00:00Z", "00000000000000ff", "1"),
+            item("b", "2024-01-01T12:00:00Z", "00000000000000ff", "1"),
+            item("c", "2024-01-01T11:00:00Z", "0000000000000fff", "2"),
+            item("d", "2024-01-01T09:00:00Z", "ffffffff00000000", "3"),
+            item("e", "2024-01-01T08:00:00Z", "ffffffff00000001", "4"),
+            item("f", "2024-01-01T07:00:00Z", "0f0f0f0f0f0f0f0f", "5"),
+        ];
+
+        let groups = find_duplicates(&items, DEFAULT_THRESHOLD);
+        assert_eq!(groups.len(), 2);
+        let ids: Vec<&str> = groups[0].items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["b", "c", "a"]);
+        assert!(!groups[0].exact);
+        assert_eq!(groups[0].distance, 4);
+        assert_eq!(groups[1].distance, 1);
+
+        // Only exact copies
+        let exact = find_duplicates(&items, 0);
+        assert_eq!(exact.len(), 1);
+        assert!(exact[0].exact);
+        assert_eq!(exact[0].items.len(), 2);
+
+        // A chain of scroll steps: each near the next, the ends far apart
+        let chain = vec![
+            item("a", "2024-01-01T12:00:00Z", "000000000000000f", "1"),
+            item("b", "2024-01-01T11:00:00Z", "00000000000000ff", "2"),
+            item("c", "2024-01-01T10:00:00Z", "0000000000000fff", "3"),
+            item("d", "2024-01-01T09:00:00Z", "0000000000000fff", "1"),
+        ];
+        let groups = find_duplicates(&chain, DEFAULT_THRESHOLD);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].items.len(), 4);
+        let ids: Vec<&str> = duplicates_of_newest(&groups[0], DEFAULT_THRESHOLD)
+            .iter()
+            .map(|i| i.id.as_str())
+            .collect();
+        assert_eq!(ids, ["b", "d"]);
+    }
+}
@@ -0,0 +1,639 @@
+//! Visual diff between two captures
+//!
+//! Compares captures pixel by pixel, optionally after undoing a shift
+//! between them such as a page scrolled a little between shots. Changed
+//! pixels are grouped into regions so that a reworded label reads as one
+//! box rather than a few hundred scattered pixels.
+
+use crate::annotate;
+use crate::compose::{self, CompositionImage};
+use crate::error::{GrabError, GrabResult};
+use crate::types::{
+    Annotation, Color, CompositionAlign, CompositionOptions, CompositionSizing, DiffOptions,
+    DiffOutput, DiffResult, RegionBounds,
+};
+use image::{Rgba, RgbaImage};
+use std::collections::VecDeque;
+
+/// Largest area compared, to stay well within memory
+const MAX_PIXELS: u64 = 100_000_000;
+
+/// Size of the cells changes are grouped by; changes up to a cell apart
+/// end up in the same region
+const CELL: u32 = 8;
+
+/// Most regions reported, keeping the largest
+const MAX_REGIONS: usize = 256;
+
+/// A comparison and the image showing it
+pub struct Diff {
+    pub result: DiffResult,
+    pub image: RgbaImage,
+}
+
+/// Compare two captures
+///
+/// Pixels covered by only one of the captures, e.g. when their sizes
+/// differ, count as changed. Pixels in ignored regions aren't compared at
+/// all.
+pub fn diff(before: &RgbaImage, after: &RgbaImage, options: &DiffOptions) -> GrabResult<Diff> {
+    if before.width() == 0 || before.height() == 0 || after.width() == 0 || after.height() == 0 {
+        return Err(GrabError::InvalidRequest(
+            "Can't compare an empty image".to_string(),
+        ));
+    }
+
+    let (offset_x, offset_y) = if options.align {
+        find_offset(before, after, options.max_offset)
+    } else {
+        (0, 0)
+    };
+    let frame = Frame::new(before, after, offset_x, offset_y);
+    if frame.width as u64 * frame.height as u64 > MAX_PIXELS {
+        return Err(GrabError::InvalidRequest(format!(
+            "Comparison would cover {}x{}, which is too large",
+            frame.width, frame.height
+        )));
+    }
+
+    // Ignored regions are in before-capture coordinates too
+    let mut ignored = vec![false; frame.width as usize * frame.height as usize];
+    for region in &options.ignore {
+        // In i64, so a huge region can't overflow before it's clamped
+        let span = |start: i32, origin: i32, length: u32, limit: u32| {
+            let start = start as i64 - origin as i64;
+            let clamp = |v: i64| v.clamp(0, limit as i64) as u32;
+            (clamp(start), clamp(start + length as i64))
+        };
+        let (left, right) = span(region.x, frame.left, region.width, frame.width);
+        let (top, bottom) = span(region.y, frame.top, region.height, frame.height);
+        for y in top..bottom {
+            ignored[(y * frame.width + left) as usize..(y * frame.width + right) as usize]
+                .fill(true);
+        }
+    }
+
+    let mut mask = vec![false; frame.width as usize * frame.height as usize];
+    let (mut compared, mut changed) = (0u64, 0u64);
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            if ignored[(y * frame.width + x) as usize] {
+                continue;
+            }
+            let different = match (frame.before(before, x, y), frame.after(after, x, y)) {
+                (Some(a), Some(b)) => !similar(a, b, options.tolerance),
+                (None, None) => continue,
+                _ => true,
+            };
+            compared += 1;
+            if different {
+                changed += 1;
+                mask[(y * frame.width + x) as usize] = true;
+            }
+        }
+    }
+
+    let regions: Vec<RegionBounds> = regions(&mask, frame.width, frame.height)
+        .into_iter()
+        .map(|r| RegionBounds {
+            x: r.x + frame.left,
+            y: r.y + frame.top,
+            ..r
+        })
+        .collect();
+
+    let image = match options.output {
+        DiffOutput::Highlight => highlight(
+            before,
+            after,
+            &frame,
+            &mask,
+            &ignored,
+            &regions,
+            options.color,
+        )?,
+        DiffOutput::SideBySide => side_by_side(before, after, &frame, &regions, options.color)?,
+        DiffOutput::Slider => slider(before, after, &frame, options),
+    };
+
+    let percentage = if compared == 0 {
+        0.0
+    } else {
+        changed as f64 * 100.0 / compared as f64
+    };
+    Ok(Diff {
+        result: DiffResult {
+            offset_x,
+            offset_y,
+            compared_pixels: compared,
+            changed_pixels: changed,
+            changed_percentage: (percentage * 100.0).round() / 100.0,
+            regions,
+            image: None,
+        },
+        image,
+    })
+}
+
+/// Find where `after` best lines up over `before`
+///
+/// Compares the average brightness of rows, then of columns, at every shift
+/// up to `max_offset`. Cheap, and good at the vertical and horizontal
+/// scrolling that moves content between captures. Returns no shift when
+/// none matches better.
+pub fn find_offset(before: &RgbaImage, after: &RgbaImage, max_offset: u32) -> (i32, i32) {
+    let before_rows = profile(before, Axis::Rows, 0..before.height());
+    let after_rows = profile(after, Axis::Rows, 0..after.height());
+    let dy = best_shift(&before_rows, &after_rows, max_offset);
+
+    // Columns only over the rows both captures show
+    let top = dy.max(0) as u32;
+    let bottom = (dy + after.height() as i32)
+        .min(before.height() as i32)
+        .max(0) as u32;
+    if top >= bottom {
+        return (0, dy);
+    }
+    let before_columns = profile(before, Axis::Columns, top..bottom);
+    let after_columns = profile(
+        after,
+        Axis::Columns,
+        (top as i32 - dy) as u32..(bottom as i32 - dy) as u32,
+    );
+    (best_shift(&before_columns, &after_columns, max_offset), dy)
+}
+
+/// The area both captures cover once `after` is placed at the offset,
+/// in before-capture coordinates
+struct Frame {
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+    offset_x: i32,
+    offset_y: i32,
+}
+
+impl Frame {
+    fn new(before: &RgbaImage, after: &RgbaImage, offset_x: i32, offset_y: i32) -> Self {
+        let left = offset_x.min(0);
+        let top = offset_y.min(0);
+        let right = (before.width() as i32).max(offset_x + after.width() as i32);
+        let bottom = (before.height() as i32).max(offset_y + after.height() as i32);
+        Frame {
+            left,
+            top,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+            offset_x,
+            offset_y,
+        }
+    }
+
+    fn before<'a>(&self, image: &'a RgbaImage, x: u32, y: u32) -> Option<&'a Rgba<u8>> {
+        pixel(image, self.left + x as i32, self.top + y as i32)
+    }
+
+    fn after<'a>(&self, image: &'a RgbaImage, x: u32, y: u32) -> Option<&'a Rgba<u8>> {
+        pixel(
+            image,
+            self.left + x as i32 - self.offset_x,
+            self.top + y as i32 - self.offset_y,
+        )
+    }
+}
+
+fn pixel(image: &RgbaImage, x: i32, y: i32) -> Option<&Rgba<u8>> {
+    if x < 0 || y < 0 || x as u32 >= image.width() || y as u32 >= image.height() {
+        None
+    } else {
+        Some(image.get_pixel(x as u32, y as u32))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Axis {
+    Rows,
+    Columns,
+}
+
+/// Average brightness of each row, or of each column over some rows
+fn profile(image: &RgbaImage, axis: Axis, rows: std::ops::Range<u32>) -> Vec<f64> {
+    let (len, count) = match axis {
+        Axis::Rows => (image.height(), image.width()),
+        Axis::Columns => (image.width(), rows.len() as u32),
+    };
+    let mut sums = vec![0.0; len as usize];
+    for y in rows {
+        for x in 0..image.width() {
+            let [r, g, b, _] = image.get_pixel(x, y).0;
+            let luma = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+            match axis {
+                Axis::Rows => sums[y as usize] += luma,
+                Axis::Columns => sums[x as usize] += luma,
+            }
+        }
+    }
+    sums.iter().map(|sum| sum / count.max(1) as f64).collect()
+}
+
+/// Shift of `after` along `before` with the closest profiles
+///
+/// Smaller shifts win ties, and a shift must overlap at least half of the
+/// shorter profile to count.
+fn best_shift(before: &[f64], after: &[f64], max_offset: u32) -> i32 {
+    let min_overlap = (before.len().min(after.len()) / 2).max(1);
+    let cost = |shift: i32| -> Option<f64> {
+        let start = (-shift).max(0) as usize;
+        let end = (before.len() as i32 - shift).min(after.len() as i32);
+        if end <= start as i32 || (end as usize - start) < min_overlap {
+            return None;
+        }
+        let total: f64 = (start..end as usize)
+            .map(|i| (before[(i as i32 + shift) as usize] - after[i]).abs())
+            .sum();
+        Some(total / (end as usize - start) as f64)
+    };
+
+    let mut best = (0, cost(0).unwrap_or(f64::INFINITY));
+    for distance in 1..=max_offset as i32 {
+        for shift in [distance, -distance] {
+            if let Some(cost) = cost(shift) {
+                // Needs to be clearly better, so noise doesn't move a match
+                if cost < best.1 - 0.01 {
+                    best = (shift, cost);
+                }
+            }
+        }
+    }
+    best.0
+}
+
+fn similar(a: &Rgba<u8>, b: &Rgba<u8>, tolerance: u8) -> bool {
+    a.0.iter()
+        .zip(b.0.iter())
+        .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+}
+
+/// Bounding boxes of groups of changed pixels, in frame coordinates
+fn regions(mask: &[bool], width: u32, height: u32) -> Vec<RegionBounds> {
+    let (columns, rows) = (
+        width.div_ceil(CELL) as usize,
+        height.div_ceil(CELL) as usize,
+    );
+
+    // Extent of the changes in each cell: left, top, right, bottom
+    let mut cells: Vec<Option<[u32; 4]>> = vec![None; columns * rows];
+    for y in 0..height {
+        for x in 0..width {
+            if !mask[(y * width + x) as usize] {
+                continue;
+            }
+            let cell = &mut cells[(y / CELL) as usize * columns + (x / CELL) as usize];
+            *cell = Some(match *cell {
+                Some([l, t, r, b]) => [l.min(x), t.min(y), r.max(x), b.max(y)],
+                None => [x, y, x, y],
+            });
+        }
+    }
+
+    let mut seen = vec![false; cells.len()];
+    let mut found = Vec::new();
+    for start in 0..cells.len() {
+        let Some(mut extent) = cells[start] else {
+            continue;
+        };
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+
+        let mut queue = VecDeque::from([start]);
+        while let Some(index) = queue.pop_front() {
+            let (cx, cy) = (index % columns, index / columns);
+            // Reach over one empty cell so nearby changes join up
+            for ny in cy.saturating_sub(2)..=(cy + 2).min(rows - 1) {
+                for nx in cx.saturating_sub(2)..=(cx + 2).min(columns - 1) {
+                    let next = ny * columns + nx;
+                    if seen[next] {
+                        continue;
+                    }
+                    if let Some([l, t, r, b]) = cells[next] {
+                        seen[next] = true;
+                        extent = [
+                            extent[0].min(l),
+                            extent[1].min(t),
+                            extent[2].max(r),
+                            extent[3].max(b),
+                        ];
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        let [left, top, right, bottom] = extent;
+        found.push(RegionBounds {
+            x: left as i32,
+            y: top as i32,
+            width: right - left + 1,
+            height: bottom - top + 1,
+        });
+    }
+
+    if found.len() > MAX_REGIONS {
+        found.sort_by_key(|r| std::cmp::Reverse(r.width as u64 * r.height as u64));
+        found.truncate(MAX_REGIONS);
+    }
+    found.sort_by_key(|r| (r.y, r.x));
+    found
+}
+
+/// Outlines around regions, shifted into another image's coordinates
+fn outlines(regions: &[RegionBounds], dx: i32, dy: i32, color: Color) -> Vec<Annotation> {
+    regions
+        .iter()
+        .map(|r| Annotation::Rectangle {
+            x: (r.x + dx) as f32 - 2.0,
+            y: (r.y + dy) as f32 - 2.0,
+            width: r.width as f32 + 4.0,
+            height: r.height as f32 + 4.0,
+            color,
+            stroke_width: 2.0,
+            fill: None,
+            corner_radius: 0.0,
+        })
+        .collect()
+}
+
+/// The after capture faded to grey, with changes painted in `color` and
+/// ignored areas hatched
+fn highlight(
+    before: &RgbaImage,
+    after: &RgbaImage,
+    frame: &Frame,
+    mask: &[bool],
+    ignored: &[bool],
+    regions: &[RegionBounds],
+    color: Color,
+) -> GrabResult<RgbaImage> {
+    let mut canvas = RgbaImage::new(frame.width, frame.height);
+    for (x, y, out) in canvas.enumerate_pixels_mut() {
+        // Removed content only exists in the before capture
+        let Some(source) = frame
+            .after(after, x, y)
+            .or_else(|| frame.before(before, x, y))
+        else {
+            continue;
+        };
+        let [r, g, b, _] = source.0;
+        let index = (y * frame.width + x) as usize;
+        *out = if ignored[index] {
+            let shade = if (x + y) / 4 % 2 == 0 { 190 } else { 215 };
+            Rgba([shade, shade, shade, 255])
+        } else if mask[index] {
+            let mix = |c: u8, h: u8| (c as f32 * 0.3 + h as f32 * 0.7).round() as u8;
+            Rgba([
+                mix(r, color.0[0]),
+                mix(g, color.0[1]),
+                mix(b, color.0[2]),
+                255,
+            ])
+        } else {
+            let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+            let faded = (255.0 - (255.0 - luma) * 0.3).round() as u8;
+            Rgba([faded, faded, faded, source.0[3]])
+        };
+    }
+
+    if regions.is_empty() {
+        return Ok(canvas);
+    }
+    annotate::render(&canvas, &outlines(regions, -frame.left, -frame.top, color))
+}
+
+/// Both captures next to each other with their changed regions outlined
+fn side_by_side(
+    before: &RgbaImage,
+    after: &RgbaImage,
+    frame: &Frame,
+    regions: &[RegionBounds],
+    color: Color,
+) -> GrabResult<RgbaImage> {
+    let outlined = |image: &RgbaImage, dx: i32, dy: i32| -> GrabResult<RgbaImage> {
+        if regions.is_empty() {
+            Ok(image.clone())
+        } else {
+            annotate::render(image, &outlines(regions, dx, dy, color))
+        }
+    };
+    let images = [
+        CompositionImage {
+            image: outlined(before, 0, 0)?,
+            label: Some("Before".to_string()),
+        },
+        CompositionImage {
+            image: outlined(after, -frame.offset_x, -frame.offset_y)?,
+            label: Some("After".to_string()),
+        },
+    ];
+    compose::compose(
+        &images,
+        &CompositionOptions {
+            // Unscaled and top-aligned, so pixels can be compared by eye
+            sizing: CompositionSizing::Original,
+            align: CompositionAlign::Start,
+            ..Default::default()
+        },
+        1.0,
+    )
+}
+
+/// Before on the left of a divider and after on the right
+fn slider(
+    before: &RgbaImage,
+    after: &RgbaImage,
+    frame: &Frame,
+    options: &DiffOptions,
+) -> RgbaImage {
+    let split = (frame.width as f32 * options.slider_position.clamp(0.0, 1.0)).round() as u32;
+    let mut canvas = RgbaImage::new(frame.width, frame.height);
+    for (x, y, out) in canvas.enumerate_pixels_mut() {
+        let source = if x < split {
+            frame.before(before, x, y)
+        } else {
+            frame.after(after, x, y)
+        };
+        // Two pixels wide, straddling the split
+        if x + 1 == split || x == split {
+            *out = Rgba(options.color.0);
+        } else if let Some(source) = source {
+            *out = *source;
+        }
+    }
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    fn fill(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+        for y in y..y + height {
+            for x in x..x + width {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    #[test]
+    fn test_diff_stats_and_regions() {
+        let before = RgbaImage::from_pixel(200, 100, WHITE);
+        let options = DiffOptions {
+            align: false,
+            ..Default::default()
+        };
+
+        let same = diff(&before, &before, &options).unwrap();
+        assert_eq!(same.result.changed_pixels, 0);
+        assert!(same.result.regions.is_empty());
+
+        let mut after = before.clone();
+        // Two changes a few pixels apart make one region
+        fill(&mut after, 10, 10, 20, 10, Rgba([0, 0, 0, 255]));
+        fill(&mut after, 34, 10, 6, 10, Rgba([0, 0, 0, 255]));
+        fill(&mut after, 150, 80, 5, 5, Rgba([0, 0, 0, 255]));
+        // Within the tolerance
+        after.put_pixel(100, 50, Rgba([250, 250, 250, 255]));
+
+        let changed = diff(&before, &after, &options).unwrap();
+        assert_eq!(changed.result.compared_pixels, 20_000);
+        assert_eq!(changed.result.changed_pixels, 285);
+        assert!((changed.result.changed_percentage - 1.43).abs() < 0.011);
+        assert_eq!(
+            changed.result.regions,
+            vec![
+                RegionBounds {
+                    x: 10,
+                    y: 10,
+                    width: 30,
+                    height: 10
+                },
+                RegionBounds {
+                    x: 150,
+                    y: 80,
+                    width: 5,
+                    height: 5
+                },
+            ]
+        );
+
+        // Changes painted over a faded copy
+        assert_eq!(changed.image.dimensions(), (200, 100));
+        let [r, g, _, _] = changed.image.get_pixel(15, 15).0;
+        assert!(r > 150 && g < 50);
+        assert_eq!(*changed.image.get_pixel(100, 30), WHITE);
+
+        let side_by_side = diff(
+            &before,
+            &after,
+            &DiffOptions {
+                output: DiffOutput::SideBySide,
+                ..options.clone()
+            },
+        )
+        .unwrap();
+        assert_eq!(side_by_side.image.width(), 16 + 200 + 16 + 200 + 16);
+
+        let slider = diff(
+            &before,
+            &after,
+            &DiffOptions {
+                output: DiffOutput::Slider,
+                slider_position: 0.1,
+                ..options.clone()
+            },
+        )
+        .unwrap();
+        assert_eq!(*slider.image.get_pixel(15, 15), WHITE);
+        assert_eq!(
+            slider.image.get_pixel(20, 50).0,
+            DiffOptions::default().color.0
+        );
+        assert_eq!(slider.image.get_pixel(35, 15).0, [0, 0, 0, 255]);
+
+        // Ignoring the first change leaves the second
+        let ignoring = diff(
+            &before,
+            &after,
+            &DiffOptions {
+                ignore: vec![RegionBounds {
+                    x: 0,
+                    y: 0,
+                    width: 50,
+                    height: 50,
+                }],
+                ..options.clone()
+            },
+        )
+        .unwrap();
+        assert_eq!(ignoring.result.compared_pixels, 20_000 - 2500);
+        assert_eq!(ignoring.result.changed_pixels, 25);
+        assert_eq!(ignoring.result.regions.len(), 1);
+
+        // Regions far larger than the frame are clamped to it
+        let huge = |x: i32| RegionBounds {
+            x,
+            y: -10,
+            width: u32::MAX,
+            height: u32::MAX,
+        };
+        let ignoring_all = diff(
+            &before,
+            &after,
+            &DiffOptions {
+                ignore: vec![huge(-10), huge(i32::MAX)],
+                ..options
+            },
+        )
+        .unwrap();
+        assert_eq!(ignoring_all.result.compared_pixels, 0);
+
+        assert!(diff(&RgbaImage::new(0, 0), &after, &DiffOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_diff_aligns_scrolled_captures() {
+        // Rows and columns with distinct brightness
+        let page = |x: u32, y: u32| Rgba([(y * 37 % 251) as u8, (x * 53 % 241) as u8, 0, 255]);
+        let before = RgbaImage::from_fn(120, 200, page);
+        // Scrolled down by 12 and right by 5
+        let after = RgbaImage::from_fn(120, 200, |x, y| page(x + 5, y + 12));
+
+        assert_eq!(find_offset(&before, &after, 64), (5, 12));
+        assert_eq!(find_offset(&before, &before, 64), (0, 0));
+
+        let aligned = diff(&before, &after, &DiffOptions::default()).unwrap();
+        assert_eq!((aligned.result.offset_x, aligned.result.offset_y), (5, 12));
+        // Only the content scrolled in or out differs
+        let overlap = 115 * 188;
+        assert_eq!(aligned.result.compared_pixels, 2 * 120 * 200 - overlap);
+        assert_eq!(
+            aligned.result.changed_pixels,
+            aligned.result.compared_pixels - overlap
+        );
+
+        let unaligned = diff(
+            &before,
+            &after,
+            &DiffOptions {
+                align: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(unaligned.result.changed_pixels > aligned.result.changed_pixels * 3);
+    }
+}
@@ -27,6 +27,15 @@ pub enum GrabError {
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
 
+    #[error("Text recognition failed: {0}")]
+    OcrFailed(String),
+
+    #[error("AI request failed: {0}")]
+    AiFailed(String),
+
+    #[error("AI provider unavailable: {0}")]
+    AiUnavailable(String),
+
     #[error("Operation cancelled")]
     Cancelled,
 
@@ -41,6 +50,9 @@ pub enum GrabError {
 
     #[error("Image error: {0}")]
     Image(#[from] image::ImageError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
 }
 
 impl GrabError {
@@ -53,11 +65,15 @@ impl GrabError {
             GrabError::ExportFailed(_) => CaptureErrorCode::ExportFailed,
             GrabError::ClipboardFailed(_) => CaptureErrorCode::ClipboardFailed,
             GrabError::InvalidRequest(_) => CaptureErrorCode::InvalidRequest,
+            GrabError::OcrFailed(_) => CaptureErrorCode::CaptureFailed,
+            GrabError::AiFailed(_) => CaptureErrorCode::CaptureFailed,
+            GrabError::AiUnavailable(_) => CaptureErrorCode::CaptureFailed,
             GrabError::Cancelled => CaptureErrorCode::Cancelled,
             GrabError::Io(_) => CaptureErrorCode::ExportFailed,
             GrabError::Serialization(_) => CaptureErrorCode::ExportFailed,
             GrabError::Tauri(_) => CaptureErrorCode::CaptureFailed,
             GrabError::Image(_) => CaptureErrorCode::CaptureFailed,
+            GrabError::Database(_) => CaptureErrorCode::CaptureFailed,
         }
     }
 }
@@ -97,6 +113,9 @@ impl Clone for GrabError {
             GrabError::ExportFailed(s) => GrabError::ExportFailed(s.clone()),
             GrabError::ClipboardFailed(s) => GrabError::ClipboardFailed(s.clone()),
             GrabError::InvalidRequest(s) => GrabError::InvalidRequest(s.clone()),
+            GrabError::OcrFailed(s) => GrabError::OcrFailed(s.clone()),
+            GrabError::AiFailed(s) => GrabError::AiFailed(s.clone()),
+            GrabError::AiUnavailable(s) => GrabError::AiUnavailable(s.clone()),
             GrabError::Cancelled => GrabError::Cancelled,
             GrabError::Io(e) => GrabError::Io(std::io::Error::new(e.kind(), e.to_string())),
             GrabError::Serialization(e) => {
@@ -104,6 +123,7 @@ impl Clone for GrabError {
             }
             GrabError::Tauri(e) => GrabError::CaptureFailed(format!("Tauri error: {}", e)),
             GrabError::Image(e) => GrabError::CaptureFailed(format!("Image error: {}", e)),
+            GrabError::Database(e) => GrabError::CaptureFailed(format!("Database error: {}", e)),
         }
     }
 }
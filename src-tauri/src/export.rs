@@ -0,0 +1,286 @@
+//! Export encoding with watermarks, beautify styles, resize, DPI and presets
+//!
+//! Used by the editor's export command, post-capture auto-export and the
+//! `grab export` CLI so all three produce identical files.
+
+use crate::beautify;
+use crate::error::{GrabError, GrabResult};
+use crate::metadata;
+use crate::trim;
+use crate::types::{
+    CaptureMetadata, Color, ExportFormat, ExportOptions, ExportPreset, FitMode, ResampleFilter,
+    ResizeOptions,
+};
+use crate::watermark;
+use image::codecs::jpeg::{JpegEncoder, PixelDensity};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbImage, RgbaImage};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+/// JPEG quality when none is given
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// Find a preset by name (case-insensitive)
+pub fn find_preset<'a>(presets: &'a [ExportPreset], name: &str) -> Option<&'a ExportPreset> {
+    presets.iter().find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Turn a preset name into something safe for file names
+pub fn preset_slug(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    slug.split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Watermark, beautify, resize, encode and embed metadata, returning the
+/// file bytes
+///
+/// `metadata` is embedded when given. `scale_factor` is the capture's display
+/// scale, used to size the watermark and frame and by the logical-pixel resize.
+pub fn export_image(
+    image: &DynamicImage,
+    options: &ExportOptions,
+    metadata: Option<&CaptureMetadata>,
+    scale_factor: f64,
+) -> GrabResult<Vec<u8>> {
+    let trimmed = match options.trim {
+        Some(ref trim) => trim::trim(&image.to_rgba8(), trim, scale_factor),
+        None => None,
+    };
+    let recorded = match (&trimmed, metadata) {
+        (Some((_, bounds)), Some(metadata)) => Some(trim::record(metadata, bounds)),
+        _ => None,
+    };
+    let metadata = recorded.as_ref().or(metadata);
+    let trimmed = trimmed.map(|(image, _)| DynamicImage::ImageRgba8(image));
+    let image = trimmed.as_ref().unwrap_or(image);
+
+    let stamped = match options.watermark {
+        Some(ref mark) => Some(DynamicImage::ImageRgba8(watermark::apply(
+            &image.to_rgba8(),
+            mark,
+            metadata,
+            scale_factor,
+        )?)),
+        None => None,
+    };
+    let image = stamped.as_ref().unwrap_or(image);
+
+    let framed = match options.beautify {
+        Some(ref style) => Some(DynamicImage::ImageRgba8(beautify::apply(
+            &image.to_rgba8(),
+            style,
+            scale_factor,
+        )?)),
+        None => None,
+    };
+    let image = framed.as_ref().unwrap_or(image);
+
+    let resized = match options.resize {
+        Some(ref resize) => resize_image(image, resize, options, scale_factor)?,
+        None => image.clone(),
+    };
+
+    let encoded = encode(&resized, options)?;
+    metadata::embed(&encoded, options.format.image_format(), metadata, options.dpi)
+}
+
+/// Export an image straight to a file
+pub fn export_to_file(
+    image: &DynamicImage,
+    options: &ExportOptions,
+    metadata: Option<&CaptureMetadata>,
+    scale_factor: f64,
+    path: &Path,
+) -> GrabResult<()> {
+    let bytes = export_image(image, options, metadata, scale_factor)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Apply a resize to an image
+pub fn resize_image(
+    image: &DynamicImage,
+    resize: &ResizeOptions,
+    options: &ExportOptions,
+    scale_factor: f64,
+) -> GrabResult<DynamicImage> {
+    let filter = filter_type(options.filter);
+    let (width, height) = (image.width(), image.height());
+
+    let resized = match *resize {
+        ResizeOptions::MaxSize {
+            max_width,
+            max_height,
+        } => {
+            let scale_x = max_width.map(|w| w as f64 / width as f64).unwrap_or(1.0);
+            let scale_y = max_height.map(|h| h as f64 / height as f64).unwrap_or(1.0);
+            scale_by(image, scale_x.min(scale_y).min(1.0), filter)
+        }
+        ResizeOptions::Scale { percent } => {
+            if percent.is_nan() || percent <= 0.0 {
+                return Err(GrabError::InvalidRequest(format!(
+                    "Invalid scale: {}%",
+                    percent
+                )));
+            }
+            scale_by(image, percent as f64 / 100.0, filter)
+        }
+        ResizeOptions::Logical => scale_by(image, 1.0 / scale_factor.max(1.0), filter),
+        ResizeOptions::Exact {
+            width: target_width,
+            height: target_height,
+            fit,
+        } => {
+            if target_width == 0 || target_height == 0 {
+                return Err(GrabError::InvalidRequest(format!(
+                    "Invalid size: {}x{}",
+                    target_width, target_height
+                )));
+            }
+            match fit {
+                FitMode::Fit => image.resize(target_width, target_height, filter),
+                FitMode::Fill => image.resize_to_fill(target_width, target_height, filter),
+                FitMode::Pad => {
+                    let fitted = image.resize(target_width, target_height, filter).to_rgba8();
+                    let background = options.background.unwrap_or(Color::TRANSPARENT);
+                    let mut canvas =
+                        RgbaImage::from_pixel(target_width, target_height, image::Rgba(background.0));
+                    let x = (target_width - fitted.width()) / 2;
+                    let y = (target_height - fitted.height()) / 2;
+                    imageops::overlay(&mut canvas, &fitted, x as i64, y as i64);
+                    DynamicImage::ImageRgba8(canvas)
+                }
+            }
+        }
+    };
+
+    Ok(resized)
+}
+
+fn scale_by(image: &DynamicImage, scale: f64, filter: FilterType) -> DynamicImage {
+    if (scale - 1.0).abs() < 1e-6 {
+        return image.clone();
+    }
+    let width = ((image.width() as f64 * scale).round() as u32).max(1);
+    let height = ((image.height() as f64 * scale).round() as u32).max(1);
+    image.resize_exact(width, height, filter)
+}
+
+fn filter_type(filter: ResampleFilter) -> FilterType {
+    match filter {
+        ResampleFilter::Nearest => FilterType::Nearest,
+        ResampleFilter::Bilinear => FilterType::Triangle,
+        ResampleFilter::CatmullRom => FilterType::CatmullRom,
+        ResampleFilter::Gaussian => FilterType::Gaussian,
+        ResampleFilter::Lanczos3 => FilterType::Lanczos3,
+    }
+}
+
+fn encode(image: &DynamicImage, options: &ExportOptions) -> GrabResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    match options.format {
+        ExportFormat::Jpeg => {
+            let background = options.background.unwrap_or(Color::WHITE);
+            let rgb = flatten(&image.to_rgba8(), background);
+            let quality = options.quality.unwrap_or(DEFAULT_JPEG_QUALITY).clamp(1, 100);
+
+            let mut encoder = JpegEncoder::new_with_quality(&mut bytes, quality);
+            if let Some(dpi) = options.dpi {
+                encoder.set_pixel_density(PixelDensity::dpi(dpi.min(u16::MAX as u32) as u16));
+            }
+            encoder
+                .encode_image(&rgb)
+                .map_err(|e| GrabError::ExportFailed(e.to_string()))?;
+        }
+        ExportFormat::Png | ExportFormat::Webp => {
+            // The WebP encoder is lossless-only and wants RGB(A)8
+            let image = DynamicImage::ImageRgba8(image.to_rgba8());
+            image
+                .write_to(&mut Cursor::new(&mut bytes), options.format.image_format())
+                .map_err(|e| GrabError::ExportFailed(e.to_string()))?;
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Composite an image over a solid background, dropping alpha
+pub fn flatten(image: &RgbaImage, background: Color) -> RgbImage {
+    let [br, bg, bb, _] = background.0;
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let blend = |src: u8, dst: u8| {
+            ((src as u32 * a as u32 + dst as u32 * (255 - a as u32) + 127) / 255) as u8
+        };
+        image::Rgb([blend(r, br), blend(g, bg), blend(b, bb)])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_resize_modes() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(400, 200, Rgba([0, 0, 0, 255])));
+        let options = ExportOptions::default();
+        let size = |resize: ResizeOptions, scale_factor: f64| {
+            let out = resize_image(&image, &resize, &options, scale_factor).unwrap();
+            (out.width(), out.height())
+        };
+
+        let max = |w, h| ResizeOptions::MaxSize {
+            max_width: w,
+            max_height: h,
+        };
+        assert_eq!(size(max(Some(200), None), 1.0), (200, 100));
+        assert_eq!(size(max(Some(1200), None), 1.0), (400, 200)); // never enlarges
+        assert_eq!(size(ResizeOptions::Scale { percent: 25.0 }, 1.0), (100, 50));
+        assert_eq!(size(ResizeOptions::Logical, 2.0), (200, 100));
+
+        let exact = |fit| ResizeOptions::Exact {
+            width: 100,
+            height: 100,
+            fit,
+        };
+        assert_eq!(size(exact(FitMode::Fit), 1.0), (100, 50));
+        assert_eq!(size(exact(FitMode::Fill), 1.0), (100, 100));
+        assert_eq!(size(exact(FitMode::Pad), 1.0), (100, 100));
+    }
+
+    #[test]
+    fn test_png_export_writes_dpi() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, Rgba([10, 20, 30, 255])));
+        let options = ExportOptions {
+            dpi: Some(144),
+            ..Default::default()
+        };
+
+        let bytes = export_image(&image, &options, None, 1.0).unwrap();
+        let reader = png::Decoder::new(Cursor::new(bytes)).read_info().unwrap();
+        let dims = reader.info().pixel_dims.unwrap();
+        assert_eq!(dims.xppu, 5669);
+        assert!(matches!(dims.unit, png::Unit::Meter));
+    }
+
+    #[test]
+    fn test_preset_lookup() {
+        let presets = crate::types::CapturePreferences::default().export_presets;
+        let preset = find_preset(&presets, "retina→1X").unwrap();
+        assert_eq!(preset.options.resize, Some(ResizeOptions::Logical));
+        assert_eq!(preset_slug(&preset.name), "retina-1x");
+    }
+}
@@ -1,25 +1,183 @@
-//! History storage for tracking recent captures
+//! Capture library
 //!
-//! Manages the list of recent captures and provides persistence.
+//! Captures are recorded in an SQLite database in the app data directory,
+//! indexed so history can be paged, sorted and filtered without loading it
+//! all. The `history.json` of earlier versions is imported once and set
+//! aside.
 
+use crate::dedupe::ImageHashes;
 use crate::error::{GrabError, GrabResult};
-use crate::types::HistoryItem;
+use crate::metadata;
+use crate::project;
+use crate::search;
+use crate::types::{
+    CaptureMetadata, CaptureMode, Collection, HistoryItem, HistoryPage, HistoryQuery, HistorySort,
+    SearchHit, SearchRequest, SearchResults, TagCount,
+};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, Row};
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::time::Duration;
 use tauri::{AppHandle, Manager};
 
-const HISTORY_FILE: &str = "history.json";
-const MAX_HISTORY_ITEMS: usize = 50;
+const DATABASE_FILE: &str = "library.db";
+const LEGACY_HISTORY_FILE: &str = "history.json";
 
-/// History store for tracking recent captures
+/// Schema changes in order; the database's `user_version` counts those applied
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE captures (
+        id TEXT PRIMARY KEY,
+        file_path TEXT NOT NULL UNIQUE,
+        timestamp TEXT NOT NULL,
+        -- Milliseconds since the epoch, for sorting and date ranges
+        captured_at INTEGER NOT NULL,
+        mode TEXT,
+        width INTEGER,
+        height INTEGER,
+        window_title TEXT,
+        app_name TEXT,
+        file_size INTEGER,
+        original_size INTEGER,
+        optimized_size INTEGER,
+        project_path TEXT,
+        perceptual_hash TEXT,
+        content_hash TEXT,
+        ocr_text TEXT
+    );
+    CREATE INDEX captures_captured_at ON captures (captured_at);
+    CREATE INDEX captures_mode ON captures (mode, captured_at);
+    CREATE INDEX captures_app_name ON captures (app_name, captured_at);
+    CREATE INDEX captures_file_size ON captures (file_size);
+    CREATE INDEX captures_dimensions ON captures (width, height);
+    CREATE INDEX captures_content_hash ON captures (content_hash);
+
+    CREATE TABLE capture_tags (
+        capture_id TEXT NOT NULL REFERENCES captures (id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (capture_id, tag)
+    );
+    CREATE INDEX capture_tags_tag ON capture_tags (tag);
+",
+    "
+    ALTER TABLE captures ADD COLUMN file_name TEXT;
+
+    -- Full-text index over the captures table, kept in step by triggers.
+    -- It refers to rows by rowid, which only VACUUM would renumber.
+    CREATE VIRTUAL TABLE capture_search USING fts5 (
+        file_name, window_title, app_name, ocr_text,
+        content = 'captures',
+        tokenize = 'unicode61 remove_diacritics 2',
+        prefix = '2 3'
+    );
+    CREATE TRIGGER captures_search_insert AFTER INSERT ON captures BEGIN
+        INSERT INTO capture_search (rowid, file_name, window_title, app_name, ocr_text)
+        VALUES (new.rowid, new.file_name, new.window_title, new.app_name, new.ocr_text);
+    END;
+    CREATE TRIGGER captures_search_delete AFTER DELETE ON captures BEGIN
+        INSERT INTO capture_search
+            (capture_search, rowid, file_name, window_title, app_name, ocr_text)
+        VALUES ('delete', old.rowid, old.file_name, old.window_title, old.app_name, old.ocr_text);
+    END;
+    CREATE TRIGGER captures_search_update
+    AFTER UPDATE OF file_name, window_title, app_name, ocr_text ON captures BEGIN
+        INSERT INTO capture_search
+            (capture_search, rowid, file_name, window_title, app_name, ocr_text)
+        VALUES ('delete', old.rowid, old.file_name, old.window_title, old.app_name, old.ocr_text);
+        INSERT INTO capture_search (rowid, file_name, window_title, app_name, ocr_text)
+        VALUES (new.rowid, new.file_name, new.window_title, new.app_name, new.ocr_text);
+    END;
+    INSERT INTO capture_search (capture_search) VALUES ('rebuild');
+",
+    "
+    ALTER TABLE captures ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+
+    CREATE TABLE collections (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE collection_items (
+        collection_id TEXT NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+        capture_id TEXT NOT NULL REFERENCES captures (id) ON DELETE CASCADE,
+        PRIMARY KEY (collection_id, capture_id)
+    );
+    CREATE INDEX collection_items_capture ON collection_items (capture_id);
+",
+    "
+    -- Tags, space separated, for the index; kept in step with capture_tags
+    ALTER TABLE captures ADD COLUMN tags TEXT;
+    UPDATE captures SET tags =
+        (SELECT group_concat(tag, ' ') FROM capture_tags WHERE capture_id = captures.id);
+    CREATE TRIGGER capture_tags_insert AFTER INSERT ON capture_tags BEGIN
+        UPDATE captures SET tags =
+            (SELECT group_concat(tag, ' ') FROM capture_tags WHERE capture_id = new.capture_id)
+        WHERE id = new.capture_id;
+    END;
+    CREATE TRIGGER capture_tags_delete AFTER DELETE ON capture_tags BEGIN
+        UPDATE captures SET tags =
+            (SELECT group_concat(tag, ' ') FROM capture_tags WHERE capture_id = old.capture_id)
+        WHERE id = old.capture_id;
+    END;
+
+    -- FTS5 tables can't gain columns, so the index is made anew
+    DROP TRIGGER captures_search_insert;
+    DROP TRIGGER captures_search_delete;
+    DROP TRIGGER captures_search_update;
+    DROP TABLE capture_search;
+    CREATE VIRTUAL TABLE capture_search USING fts5 (
+        file_name, window_title, app_name, ocr_text, tags,
+        content = 'captures',
+        tokenize = 'unicode61 remove_diacritics 2',
+        prefix = '2 3'
+    );
+    CREATE TRIGGER captures_search_insert AFTER INSERT ON captures BEGIN
+        INSERT INTO capture_search (rowid, file_name, window_title, app_name, ocr_text, tags)
+        VALUES (new.rowid, new.file_name, new.window_title, new.app_name, new.ocr_text, new.tags);
+    END;
+    CREATE TRIGGER captures_search_delete AFTER DELETE ON captures BEGIN
+        INSERT INTO capture_search
+            (capture_search, rowid, file_name, window_title, app_name, ocr_text, tags)
+        VALUES ('delete', old.rowid, old.file_name, old.window_title, old.app_name,
+            old.ocr_text, old.tags);
+    END;
+    CREATE TRIGGER captures_search_update
+    AFTER UPDATE OF file_name, window_title, app_name, ocr_text, tags ON captures BEGIN
+        INSERT INTO capture_search
+            (capture_search, rowid, file_name, window_title, app_name, ocr_text, tags)
+        VALUES ('delete', old.rowid, old.file_name, old.window_title, old.app_name,
+            old.ocr_text, old.tags);
+        INSERT INTO capture_search (rowid, file_name, window_title, app_name, ocr_text, tags)
+        VALUES (new.rowid, new.file_name, new.window_title, new.app_name, new.ocr_text, new.tags);
+    END;
+    INSERT INTO capture_search (capture_search) VALUES ('rebuild');
+",
+];
+
+/// Columns read by `item_from_row`, in order
+const ITEM_COLUMNS: &str = "captures.id, captures.file_path, captures.timestamp, \
+    captures.mode, captures.window_title, captures.app_name, captures.original_size, \
+    captures.optimized_size, captures.project_path, captures.perceptual_hash, \
+    captures.content_hash, captures.width, captures.height, captures.file_size, \
+    (SELECT group_concat(tag, char(31)) FROM capture_tags WHERE capture_id = captures.id), \
+    captures.pinned";
+
+/// Weights of the indexed columns when ranking matches
+const SEARCH_WEIGHTS: &str = "4.0, 8.0, 4.0, 1.0, 8.0";
+
+/// Tokens of context on either side of a snippet's matches
+const SNIPPET_TOKENS: u32 = 16;
+
+/// Library of captures
 pub struct HistoryStore {
-    items: Mutex<Vec<HistoryItem>>,
-    file_path: PathBuf,
+    conn: Mutex<Connection>,
 }
 
 impl HistoryStore {
-    /// Create a new history store
+    /// Open the library in the app data directory
     pub fn new(app_handle: &AppHandle) -> GrabResult<Self> {
         let app_data_dir = app_handle
             .path()
@@ -28,168 +186,889 @@ impl HistoryStore {
 
         fs::create_dir_all(&app_data_dir)?;
 
-        let file_path = app_data_dir.join(HISTORY_FILE);
+        let store = HistoryStore::open(&app_data_dir.join(DATABASE_FILE))?;
+        match store.import_legacy(&app_data_dir.join(LEGACY_HISTORY_FILE)) {
+            Ok(0) => {}
+            Ok(count) => eprintln!("Imported {} captures into the library", count),
+            Err(e) => eprintln!("Failed to import history: {}", e),
+        }
+        Ok(store)
+    }
 
-        // Load existing history or create empty
-        let items = if file_path.exists() {
-            match fs::read_to_string(&file_path) {
-                Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-                Err(_) => Vec::new(),
-            }
-        } else {
-            Vec::new()
-        };
+    /// Open or create a library database, bringing its schema up to date
+    pub fn open(path: &Path) -> GrabResult<Self> {
+        let mut conn = Connection::open(path)?;
+        // Another instance may be writing; wait for it rather than fail
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        backfill_file_names(&conn)?;
 
         Ok(HistoryStore {
-            items: Mutex::new(items),
-            file_path,
+            conn: Mutex::new(conn),
         })
     }
 
-    /// Add a new item to history
-    pub fn add(&self, file_path: String) -> GrabResult<()> {
+    /// Import the `history.json` of earlier versions
+    ///
+    /// The file is renamed afterwards, so it is kept but not read again. A
+    /// file that can't be parsed is an error and stays where it is, to be
+    /// tried again. Entries whose files are gone are dropped, as history
+    /// never showed them. Returns the number of captures imported.
+    pub fn import_legacy(&self, path: &Path) -> GrabResult<usize> {
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let content = fs::read_to_string(path)?;
+        let items: Vec<HistoryItem> = serde_json::from_str(&content)?;
+        let items: Vec<HistoryItem> = items
+            .into_iter()
+            .filter_map(|item| {
+                let file = fs::metadata(&item.file_path).ok()?;
+                let (width, height) = image::image_dimensions(&item.file_path).ok().unzip();
+                Some(HistoryItem {
+                    width,
+                    height,
+                    file_size: Some(file.len()),
+                    ..item
+                })
+            })
+            .collect();
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut imported = 0;
+        for item in &items {
+            imported += insert(&tx, item)?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        let mut migrated = path.as_os_str().to_owned();
+        migrated.push(".migrated");
+        fs::rename(path, migrated)?;
+
+        Ok(imported)
+    }
+
+    /// Add a new capture
+    ///
+    /// A capture saved over an earlier file replaces its entry.
+    pub fn add(
+        &self,
+        file_path: String,
+        metadata: &CaptureMetadata,
+        dimensions: (u32, u32),
+        hashes: ImageHashes,
+    ) -> GrabResult<()> {
+        let now = chrono::Utc::now();
         let item = HistoryItem {
-            id: chrono::Utc::now().timestamp_millis().to_string(),
+            id: format!("{}{}", now.timestamp_millis(), rand_suffix()),
+            file_size: fs::metadata(&file_path).ok().map(|m| m.len()),
             file_path,
-            timestamp: chrono::Utc::now().to_rfc3339(),
+            timestamp: now.to_rfc3339(),
             thumbnail: None,
+            mode: Some(metadata.mode),
+            window_title: metadata.window_title.clone(),
+            app_name: metadata.app_name.clone(),
+            original_size: None,
+            optimized_size: None,
+            project_path: None,
+            perceptual_hash: Some(hashes.perceptual),
+            content_hash: Some(hashes.content),
+            width: Some(dimensions.0),
+            height: Some(dimensions.1),
+            tags: clean_tags(&metadata.tags),
+            pinned: false,
         };
 
-        let mut items = self.items.lock().unwrap();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM captures WHERE file_path = ?1",
+            [&item.file_path],
+        )?;
+        insert(&tx, &item)?;
+        tx.commit()?;
+        Ok(())
+    }
 
-        // Add to beginning
-        items.insert(0, item);
+    /// Get all captures, newest first
+    pub fn get_all(&self) -> GrabResult<Vec<HistoryItem>> {
+        self.select("ORDER BY captured_at DESC", [])
+    }
 
-        // Limit size
-        if items.len() > MAX_HISTORY_ITEMS {
-            items.truncate(MAX_HISTORY_ITEMS);
-        }
+    /// Get the latest capture
+    pub fn get_latest(&self) -> GrabResult<Option<HistoryItem>> {
+        Ok(self
+            .select("ORDER BY captured_at DESC LIMIT 1", [])?
+            .into_iter()
+            .next())
+    }
 
-        drop(items);
-        self.save()
+    /// Get a capture by id
+    pub fn get(&self, id: &str) -> GrabResult<Option<HistoryItem>> {
+        Ok(self.select("WHERE id = ?1", [id])?.into_iter().next())
     }
 
-    /// Get all history items
-    pub fn get_all(&self) -> Vec<HistoryItem> {
-        let items = self.items.lock().unwrap();
+    /// Get the capture saved at a path
+    pub fn get_by_path(&self, file_path: &str) -> GrabResult<Option<HistoryItem>> {
+        Ok(self
+            .select("WHERE file_path = ?1", [file_path])?
+            .into_iter()
+            .next())
+    }
 
-        // Filter out files that no longer exist
-        items
-            .iter()
-            .filter(|item| std::path::Path::new(&item.file_path).exists())
-            .cloned()
-            .collect()
+    /// Get a page of captures matching a query
+    pub fn query(&self, query: &HistoryQuery) -> GrabResult<HistoryPage> {
+        let (conditions, mut values) = filter(query)?;
+        let filter = where_clause(&conditions);
+        let order = match query.sort {
+            HistorySort::Newest => "captured_at DESC",
+            HistorySort::Oldest => "captured_at ASC",
+            HistorySort::Largest => "file_size DESC, captured_at DESC",
+            HistorySort::Smallest => "file_size IS NULL, file_size ASC, captured_at DESC",
+        };
+
+        let conn = self.conn.lock().unwrap();
+        let total: u64 = conn.query_row(
+            &format!("SELECT count(*) FROM captures {}", filter),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )?;
+
+        values.push(Value::Integer(query.limit.into()));
+        values.push(Value::Integer(query.offset.into()));
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM captures {} ORDER BY {} LIMIT ? OFFSET ?",
+            ITEM_COLUMNS, filter, order
+        ))?;
+        let items = statement
+            .query_map(params_from_iter(values.iter()), item_from_row)?
+            .collect::<rusqlite::Result<Vec<HistoryItem>>>()?;
+
+        Ok(HistoryPage { items, total })
     }
 
-    /// Get the latest history item
-    pub fn get_latest(&self) -> Option<HistoryItem> {
-        self.get_all().into_iter().next()
+    /// Search the text of captures, best matches first
+    ///
+    /// Without any terms, the captures in the date range are listed newest
+    /// first.
+    pub fn search(&self, request: &SearchRequest) -> GrabResult<SearchResults> {
+        let filters = HistoryQuery {
+            offset: request.offset,
+            limit: request.limit,
+            from: request.from.clone(),
+            to: request.to.clone(),
+            ..Default::default()
+        };
+        let Some(expression) = search::match_expression(&request.query) else {
+            let page = self.query(&filters)?;
+            return Ok(SearchResults {
+                hits: page
+                    .items
+                    .into_iter()
+                    .map(|item| SearchHit {
+                        item,
+                        score: 0.0,
+                        snippet: Vec::new(),
+                    })
+                    .collect(),
+                total: page.total,
+            });
+        };
+
+        let (mut conditions, filter_values) = filter(&filters)?;
+        conditions.insert(0, "capture_search MATCH ?");
+        let filter = where_clause(&conditions);
+        let mut values = vec![Value::Text(expression)];
+        values.extend(filter_values);
+
+        let conn = self.conn.lock().unwrap();
+        let total: u64 = conn.query_row(
+            &format!(
+                "SELECT count(*) FROM capture_search
+                 JOIN captures ON captures.rowid = capture_search.rowid {}",
+                filter
+            ),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )?;
+
+        values.push(Value::Integer(request.limit.into()));
+        values.push(Value::Integer(request.offset.into()));
+        let mut statement = conn.prepare(&format!(
+            "SELECT {}, snippet(capture_search, -1, '{}', '{}', '…', {}),
+                bm25(capture_search, {}) AS rank
+             FROM capture_search JOIN captures ON captures.rowid = capture_search.rowid
+             {} ORDER BY rank, captures.captured_at DESC LIMIT ? OFFSET ?",
+            ITEM_COLUMNS,
+            search::HIGHLIGHT_START,
+            search::HIGHLIGHT_END,
+            SNIPPET_TOKENS,
+            SEARCH_WEIGHTS,
+            filter
+        ))?;
+        let hits = statement
+            .query_map(params_from_iter(values.iter()), |row| {
+                let snippet: String = row.get(16)?;
+                let rank: f64 = row.get(17)?;
+                Ok(SearchHit {
+                    item: item_from_row(row)?,
+                    // bm25 is lower for better matches
+                    score: -rank,
+                    snippet: search::snippet_parts(&snippet),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<SearchHit>>>()?;
+
+        Ok(SearchResults { hits, total })
+    }
+
+    /// Files of captures recorded without hashes, newest first
+    pub fn unhashed(&self) -> GrabResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT file_path FROM captures WHERE perceptual_hash IS NULL
+             ORDER BY captured_at DESC",
+        )?;
+        let paths = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(paths)
+    }
+
+    /// Record the hashes of a capture
+    pub fn set_hashes(&self, file_path: &str, hashes: &ImageHashes) -> GrabResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE captures SET perceptual_hash = ?2, content_hash = ?3 WHERE file_path = ?1",
+            params![file_path, hashes.perceptual, hashes.content],
+        )?;
+        Ok(updated > 0)
     }
 
-    /// Remove an item from history
+    /// Files of captures whose text hasn't been read yet, newest first
+    pub fn unindexed(&self) -> GrabResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT file_path FROM captures WHERE ocr_text IS NULL ORDER BY captured_at DESC",
+        )?;
+        let paths = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(paths)
+    }
+
+    /// Remove a capture from the library
     pub fn remove(&self, file_path: &str) -> GrabResult<bool> {
-        let mut items = self.items.lock().unwrap();
-        let initial_len = items.len();
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM captures WHERE file_path = ?1", [file_path])?;
+        Ok(removed > 0)
+    }
 
-        items.retain(|item| item.file_path != file_path);
+    /// Link a capture to its `.grab` project
+    pub fn set_project_path(
+        &self,
+        file_path: &str,
+        project_path: Option<String>,
+    ) -> GrabResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE captures SET project_path = ?2 WHERE file_path = ?1",
+            params![file_path, project_path],
+        )?;
+        Ok(updated > 0)
+    }
 
-        let removed = items.len() < initial_len;
+    /// Record the current size of a capture's file
+    pub fn set_file_size(&self, file_path: &str, file_size: u64) -> GrabResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE captures SET file_size = ?2 WHERE file_path = ?1",
+            params![file_path, file_size],
+        )?;
+        Ok(updated > 0)
+    }
 
-        drop(items);
+    /// Record the before/after sizes of the optimization pass
+    pub fn set_file_sizes(
+        &self,
+        file_path: &str,
+        original_size: u64,
+        optimized_size: u64,
+    ) -> GrabResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE captures
+             SET original_size = ?2, optimized_size = ?3, file_size = ?3
+             WHERE file_path = ?1",
+            params![file_path, original_size, optimized_size],
+        )?;
+        Ok(updated > 0)
+    }
 
-        if removed {
-            self.save()?;
+    /// Record the text recognized in a capture
+    pub fn set_ocr_text(&self, file_path: &str, text: &str) -> GrabResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE captures SET ocr_text = ?2 WHERE file_path = ?1",
+            params![file_path, text],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Drop all but the newest `limit` captures from the library
+    ///
+    /// Pinned captures are kept and don't count towards the limit. The
+    /// files are left alone. Returns how many entries were dropped.
+    pub fn prune(&self, limit: Option<u32>) -> GrabResult<usize> {
+        let Some(limit) = limit else {
+            return Ok(0);
+        };
+        let conn = self.conn.lock().unwrap();
+        let pruned = conn.execute(
+            "DELETE FROM captures WHERE NOT pinned AND id NOT IN
+             (SELECT id FROM captures WHERE NOT pinned ORDER BY captured_at DESC LIMIT ?1)",
+            [limit],
+        )?;
+        Ok(pruned)
+    }
+
+    /// Tag captures; returns how many tags were added
+    pub fn add_tags(&self, ids: &[String], tags: &[String]) -> GrabResult<usize> {
+        let tags = clean_tags(tags);
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut added = 0;
+        for id in ids {
+            for tag in &tags {
+                added += tx.execute(
+                    "INSERT OR IGNORE INTO capture_tags (capture_id, tag)
+                     SELECT id, ?2 FROM captures WHERE id = ?1",
+                    params![id, tag],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// Untag captures; returns how many tags were removed
+    pub fn remove_tags(&self, ids: &[String], tags: &[String]) -> GrabResult<usize> {
+        let tags = clean_tags(tags);
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut removed = 0;
+        for id in ids {
+            for tag in &tags {
+                removed += tx.execute(
+                    "DELETE FROM capture_tags WHERE capture_id = ?1 AND tag = ?2",
+                    params![id, tag],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// All tags in use, most used first
+    pub fn tags(&self) -> GrabResult<Vec<TagCount>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT tag, count(*) FROM capture_tags GROUP BY tag ORDER BY count(*) DESC, tag",
+        )?;
+        let tags = statement
+            .query_map([], |row| {
+                Ok(TagCount {
+                    tag: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<TagCount>>>()?;
+        Ok(tags)
+    }
+
+    /// Pin or unpin captures; returns how many changed
+    pub fn set_pinned(&self, ids: &[String], pinned: bool) -> GrabResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut changed = 0;
+        for id in ids {
+            changed += tx.execute(
+                "UPDATE captures SET pinned = ?2 WHERE id = ?1 AND pinned != ?2",
+                params![id, pinned],
+            )?;
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// All collections, by name
+    pub fn collections(&self) -> GrabResult<Vec<Collection>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM collections ORDER BY name",
+            COLLECTION_COLUMNS
+        ))?;
+        let collections = statement
+            .query_map([], collection_from_row)?
+            .collect::<rusqlite::Result<Vec<Collection>>>()?;
+        Ok(collections)
+    }
+
+    /// Create an empty collection
+    pub fn create_collection(&self, name: &str) -> GrabResult<Collection> {
+        let name = collection_name(name)?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO collections (id, name, created_at) VALUES (?1, ?2, ?3)",
+            params![id, name, chrono::Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| name_taken(e, &name))?;
+        collection(&conn, &id)
+    }
+
+    pub fn rename_collection(&self, id: &str, name: &str) -> GrabResult<Collection> {
+        let name = collection_name(name)?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE collections SET name = ?2 WHERE id = ?1",
+            params![id, name],
+        )
+        .map_err(|e| name_taken(e, &name))?;
+        collection(&conn, id)
+    }
+
+    /// Delete a collection; the captures in it are kept
+    pub fn delete_collection(&self, id: &str) -> GrabResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM collections WHERE id = ?1", [id])?;
+        Ok(deleted > 0)
+    }
+
+    /// Add captures to a collection; returns how many weren't in it yet
+    pub fn add_to_collection(&self, id: &str, item_ids: &[String]) -> GrabResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        collection(&conn, id)?;
+        let tx = conn.transaction()?;
+        let mut added = 0;
+        for item_id in item_ids {
+            added += tx.execute(
+                "INSERT OR IGNORE INTO collection_items (collection_id, capture_id)
+                 SELECT ?1, id FROM captures WHERE id = ?2",
+                params![id, item_id],
+            )?;
         }
+        tx.commit()?;
+        Ok(added)
+    }
 
+    /// Take captures out of a collection; returns how many were in it
+    pub fn remove_from_collection(&self, id: &str, item_ids: &[String]) -> GrabResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        collection(&conn, id)?;
+        let tx = conn.transaction()?;
+        let mut removed = 0;
+        for item_id in item_ids {
+            removed += tx.execute(
+                "DELETE FROM collection_items WHERE collection_id = ?1 AND capture_id = ?2",
+                params![id, item_id],
+            )?;
+        }
+        tx.commit()?;
         Ok(removed)
     }
 
-    /// Scan a directory and add any images not already in history
+    /// Bring the library in line with a directory
+    ///
+    /// Adds images not yet recorded and drops entries whose files are gone.
+    /// Only the listing and file headers are read; new entries have no
+    /// hashes until `dedupe::HashWorker` catches up. Returns the number of
+    /// captures added.
     pub fn scan_directory(&self, directory: &PathBuf) -> GrabResult<usize> {
         if !directory.exists() {
             return Ok(0);
         }
 
-        let entries = fs::read_dir(directory)?;
-        let mut new_count = 0;
+        let on_disk: HashSet<String> = fs::read_dir(directory)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| is_image(path))
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
 
-        let mut items = self.items.lock().unwrap();
+        // Entries for this directory
+        let recorded: Vec<String> = {
+            let conn = self.conn.lock().unwrap();
+            let mut statement = conn.prepare("SELECT file_path FROM captures")?;
+            let rows = statement
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            rows.into_iter()
+                .filter(|path| Path::new(path).parent() == Some(directory.as_path()))
+                .collect()
+        };
+        let known: HashSet<&str> = recorded.iter().map(|path| path.as_str()).collect();
 
-        for entry in entries.flatten() {
-            let path = entry.path();
+        let mut new_items = Vec::new();
+        for path in on_disk.iter().filter(|path| !known.contains(path.as_str())) {
+            new_items.push(scanned_item(Path::new(path))?);
+        }
+        let missing: Vec<&str> = recorded
+            .iter()
+            .map(|path| path.as_str())
+            .filter(|path| !on_disk.contains(*path))
+            .collect();
 
-            // Only process image files
-            if let Some(ext) = path.extension() {
-                let ext = ext.to_string_lossy().to_lowercase();
-                if !["png", "jpg", "jpeg"].contains(&ext.as_str()) {
-                    continue;
-                }
-            } else {
-                continue;
-            }
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        // A capture may have been added while the lock was released, in
+        // which case its entry wins
+        let mut added = 0;
+        for item in &new_items {
+            added += insert(&tx, item)?;
+        }
+        for path in &missing {
+            tx.execute("DELETE FROM captures WHERE file_path = ?1", [path])?;
+        }
+        tx.commit()?;
 
-            let path_str = path.to_string_lossy().to_string();
+        Ok(added)
+    }
 
-            // Check if already in history
-            if items.iter().any(|item| item.file_path == path_str) {
-                continue;
-            }
+    /// Run a query for items, given what follows `FROM captures`
+    fn select<P: rusqlite::Params>(
+        &self,
+        clauses: &str,
+        params: P,
+    ) -> GrabResult<Vec<HistoryItem>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM captures {}",
+            ITEM_COLUMNS, clauses
+        ))?;
+        let items = statement
+            .query_map(params, item_from_row)?
+            .collect::<rusqlite::Result<Vec<HistoryItem>>>()?;
+        Ok(items)
+    }
+}
 
-            // Get file metadata for timestamp
-            let metadata = fs::metadata(&path)?;
-            let timestamp = metadata
-                .created()
-                .or_else(|_| metadata.modified())
-                .map(|t| {
-                    chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()
-                })
-                .unwrap_or_else(|_| chrono::Utc::now().to_rfc3339());
-
-            let item = HistoryItem {
-                id: format!(
-                    "{}{}",
-                    metadata
-                        .created()
-                        .map(|t| t
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_millis())
-                        .unwrap_or(0),
-                    rand_suffix()
-                ),
-                file_path: path_str,
-                timestamp,
-                thumbnail: None,
-            };
+/// Apply the migrations the database hasn't seen yet
+fn migrate(conn: &mut Connection) -> GrabResult<()> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let tx = conn.transaction()?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version.max(0) as usize) {
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index as i64 + 1)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Name the captures recorded before file names were kept
+fn backfill_file_names(conn: &Connection) -> GrabResult<()> {
+    let mut statement =
+        conn.prepare("SELECT id, file_path FROM captures WHERE file_name IS NULL")?;
+    let rows = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+    for (id, path) in rows {
+        conn.execute(
+            "UPDATE captures SET file_name = ?2 WHERE id = ?1",
+            params![id, file_name(&path)],
+        )?;
+    }
+    Ok(())
+}
+
+/// Columns read by `collection_from_row`, in order
+const COLLECTION_COLUMNS: &str = "id, name, created_at, \
+    (SELECT count(*) FROM collection_items WHERE collection_id = collections.id)";
 
-            items.push(item);
-            new_count += 1;
+fn collection_from_row(row: &Row) -> rusqlite::Result<Collection> {
+    Ok(Collection {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_at: row.get(2)?,
+        item_count: row.get(3)?,
+    })
+}
+
+fn collection(conn: &Connection, id: &str) -> GrabResult<Collection> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM collections WHERE id = ?1",
+            COLLECTION_COLUMNS
+        ),
+        [id],
+        collection_from_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            GrabError::SourceNotFound(format!("collection {}", id))
         }
+        e => e.into(),
+    })
+}
 
-        // Sort by timestamp (newest first)
-        items.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+fn collection_name(name: &str) -> GrabResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(GrabError::InvalidRequest(
+            "Collection name is empty".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
 
-        // Trim to max size
-        if items.len() > MAX_HISTORY_ITEMS {
-            items.truncate(MAX_HISTORY_ITEMS);
+/// Report a clash of collection names, which are unique ignoring case
+fn name_taken(error: rusqlite::Error, name: &str) -> GrabError {
+    match error.sqlite_error_code() {
+        Some(rusqlite::ErrorCode::ConstraintViolation) => {
+            GrabError::InvalidRequest(format!("A collection named {} already exists", name))
         }
+        _ => error.into(),
+    }
+}
 
-        drop(items);
+/// Tags trimmed, without blanks or repeats
+fn clean_tags(tags: &[String]) -> Vec<String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim()) {
+        if !tag.is_empty() && !cleaned.iter().any(|t| t == tag) {
+            cleaned.push(tag.to_string());
+        }
+    }
+    cleaned
+}
 
-        if new_count > 0 {
-            self.save()?;
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Record an item unless its file already is; returns the rows added
+fn insert(conn: &Connection, item: &HistoryItem) -> GrabResult<usize> {
+    let added = conn.execute(
+        "INSERT OR IGNORE INTO captures (
+            id, file_path, timestamp, captured_at, mode, width, height, window_title,
+            app_name, file_size, original_size, optimized_size, project_path,
+            perceptual_hash, content_hash, file_name, pinned
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        params![
+            item.id,
+            item.file_path,
+            item.timestamp,
+            timestamp_millis(&item.timestamp),
+            item.mode.and_then(mode_name),
+            item.width,
+            item.height,
+            item.window_title,
+            item.app_name,
+            item.file_size,
+            item.original_size,
+            item.optimized_size,
+            item.project_path,
+            item.perceptual_hash,
+            item.content_hash,
+            file_name(&item.file_path),
+            item.pinned,
+        ],
+    )?;
+    if added > 0 {
+        for tag in &item.tags {
+            conn.execute(
+                "INSERT OR IGNORE INTO capture_tags (capture_id, tag) VALUES (?1, ?2)",
+                [&item.id, tag],
+            )?;
         }
+    }
+    Ok(added)
+}
+
+fn item_from_row(row: &Row) -> rusqlite::Result<HistoryItem> {
+    let mode: Option<String> = row.get(3)?;
+    let tags: Option<String> = row.get(14)?;
+    let mut tags: Vec<String> = tags
+        .map(|tags| tags.split('\u{1f}').map(str::to_string).collect())
+        .unwrap_or_default();
+    tags.sort();
+
+    Ok(HistoryItem {
+        id: row.get(0)?,
+        file_path: row.get(1)?,
+        timestamp: row.get(2)?,
+        thumbnail: None,
+        mode: mode.and_then(|name| serde_json::from_value(serde_json::Value::String(name)).ok()),
+        window_title: row.get(4)?,
+        app_name: row.get(5)?,
+        original_size: row.get(6)?,
+        optimized_size: row.get(7)?,
+        project_path: row.get(8)?,
+        perceptual_hash: row.get(9)?,
+        content_hash: row.get(10)?,
+        width: row.get(11)?,
+        height: row.get(12)?,
+        file_size: row.get(13)?,
+        tags,
+        pinned: row.get(15)?,
+    })
+}
+
+/// Conditions for a query's filters, with their parameters
+fn filter(query: &HistoryQuery) -> GrabResult<(Vec<&'static str>, Vec<Value>)> {
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+
+    if let Some(mode) = query.mode.and_then(mode_name) {
+        conditions.push("captures.mode = ?");
+        values.push(Value::Text(mode));
+    }
+    if let Some(ref app_name) = query.app_name {
+        conditions.push("captures.app_name = ?");
+        values.push(Value::Text(app_name.clone()));
+    }
+    if let Some(ref title) = query.window_title {
+        conditions.push("instr(lower(captures.window_title), lower(?)) > 0");
+        values.push(Value::Text(title.clone()));
+    }
+    if let Some(ref tag) = query.tag {
+        conditions
+            .push("EXISTS (SELECT 1 FROM capture_tags WHERE capture_id = captures.id AND tag = ?)");
+        values.push(Value::Text(tag.clone()));
+    }
+    if let Some(pinned) = query.pinned {
+        conditions.push("captures.pinned = ?");
+        values.push(Value::Integer(pinned.into()));
+    }
+    if let Some(ref collection) = query.collection {
+        conditions.push(
+            "EXISTS (SELECT 1 FROM collection_items
+             WHERE capture_id = captures.id AND collection_id = ?)",
+        );
+        values.push(Value::Text(collection.clone()));
+    }
+    if let Some(ref from) = query.from {
+        conditions.push("captures.captured_at >= ?");
+        values.push(Value::Integer(parse_time(from, false)?));
+    }
+    if let Some(ref to) = query.to {
+        conditions.push("captures.captured_at <= ?");
+        values.push(Value::Integer(parse_time(to, true)?));
+    }
+    if let Some(width) = query.min_width {
+        conditions.push("captures.width >= ?");
+        values.push(Value::Integer(width.into()));
+    }
+    if let Some(height) = query.min_height {
+        conditions.push("captures.height >= ?");
+        values.push(Value::Integer(height.into()));
+    }
 
-        Ok(new_count)
+    Ok((conditions, values))
+}
+
+fn where_clause(conditions: &[&str]) -> String {
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
     }
+}
 
-    /// Save history to disk
-    fn save(&self) -> GrabResult<()> {
-        let items = self.items.lock().unwrap();
-        let content = serde_json::to_string_pretty(&*items)?;
-        fs::write(&self.file_path, content)?;
-        Ok(())
+/// Milliseconds since the epoch for an RFC 3339 time, or a local date
+///
+/// A bare date means its start, or its end for `end_of_day`.
+fn parse_time(text: &str, end_of_day: bool) -> GrabResult<i64> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(text) {
+        return Ok(time.timestamp_millis());
+    }
+    let date = chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map_err(|_| GrabError::InvalidRequest(format!("Invalid date: {}", text)))?;
+    let time = if end_of_day {
+        date.and_hms_milli_opt(23, 59, 59, 999)
+    } else {
+        date.and_hms_opt(0, 0, 0)
     }
+    .unwrap();
+    Ok(time
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|t| t.timestamp_millis())
+        .unwrap_or_else(|| time.and_utc().timestamp_millis()))
+}
+
+fn timestamp_millis(timestamp: &str) -> i64 {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.timestamp_millis())
+        .unwrap_or_else(|_| chrono::Utc::now().timestamp_millis())
+}
+
+fn mode_name(mode: CaptureMode) -> Option<String> {
+    serde_json::to_value(mode)
+        .ok()?
+        .as_str()
+        .map(str::to_string)
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| ["png", "jpg", "jpeg", "webp"].contains(&ext.as_str()))
+}
+
+/// Library entry for an image found on disk
+fn scanned_item(path: &Path) -> GrabResult<HistoryItem> {
+    // Captures saved by grab carry their own metadata
+    let embedded = metadata::read_from_file(path);
+
+    // Get file metadata for timestamp
+    let metadata = fs::metadata(path)?;
+    let file_timestamp = metadata
+        .created()
+        .or_else(|_| metadata.modified())
+        .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+        .unwrap_or_else(|_| chrono::Utc::now().to_rfc3339());
+    let timestamp = embedded
+        .as_ref()
+        .map(|m| m.timestamp.clone())
+        .unwrap_or(file_timestamp);
+    let (width, height) = image::image_dimensions(path).ok().unzip();
+
+    Ok(HistoryItem {
+        id: format!(
+            "{}{}",
+            metadata
+                .created()
+                .map(|t| t.duration_since(std::time::UNIX_EPOCH).unwrap().as_millis())
+                .unwrap_or(0),
+            rand_suffix()
+        ),
+        file_path: path.to_string_lossy().to_string(),
+        timestamp,
+        thumbnail: None,
+        mode: embedded.as_ref().map(|m| m.mode),
+        window_title: embedded.as_ref().and_then(|m| m.window_title.clone()),
+        app_name: embedded.as_ref().and_then(|m| m.app_name.clone()),
+        original_size: None,
+        optimized_size: None,
+        project_path: project::existing_project_for(path).map(|p| p.to_string_lossy().to_string()),
+        perceptual_hash: None,
+        content_hash: None,
+        width,
+        height,
+        file_size: Some(metadata.len()),
+        // Tags travel with the file
+        tags: embedded.map(|m| clean_tags(&m.tags)).unwrap_or_default(),
+        pinned: false,
+    })
 }
 
 /// Generate a random suffix for unique IDs
@@ -201,3 +1080,403 @@ fn rand_suffix() -> String {
         .subsec_nanos();
     format!("{:05}", nanos % 100000)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedupe;
+    use image::{Rgba, RgbaImage};
+
+    fn item(id: &str, timestamp: &str, mode: CaptureMode, app: &str, size: u64) -> HistoryItem {
+        HistoryItem {
+            id: id.to_string(),
+            file_path: format!("/captures/{}.png", id),
+            timestamp: timestamp.to_string(),
+            thumbnail: None,
+            mode: Some(mode),
+            window_title: Some(format!("{} - Window", app)),
+            app_name: Some(app.to_string()),
+            original_size: None,
+            optimized_size: None,
+            project_path: None,
+            perceptual_hash: None,
+            content_hash: None,
+            width: Some(size as u32 / 10),
+            height: Some(100),
+            file_size: Some(size),
+            tags: Vec::new(),
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn test_query() {
+        let store = HistoryStore::open(Path::new(":memory:")).unwrap();
+        {
+            let conn = store.conn.lock().unwrap();
+            insert(
+                &conn,
+                &item(
+                    "a",
+                    "2024-03-01T09:00:00Z",
+                    CaptureMode::Window,
+                    "Safari",
+                    3000,
+                ),
+            )
+            .unwrap();
+            insert(
+                &conn,
+                &item(
+                    "b",
+                    "2024-03-02T09:00:00Z",
+                    CaptureMode::Region,
+                    "Code",
+                    1000,
+                ),
+            )
+            .unwrap();
+            insert(
+                &conn,
+                &item(
+                    "c",
+                    "2024-03-03T09:00:00Z",
+                    CaptureMode::Window,
+                    "Code",
+                    2000,
+                ),
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO capture_tags VALUES ('c', 'bug'), ('c', 'api')",
+                [],
+            )
+            .unwrap();
+        }
+
+        let ids = |page: HistoryPage| page.items.into_iter().map(|i| i.id).collect::<Vec<_>>();
+        let all = store.query(&HistoryQuery::default()).unwrap();
+        assert_eq!(all.total, 3);
+        assert_eq!(all.items[0].tags, ["api", "bug"]);
+        assert_eq!(ids(all), ["c", "b", "a"]);
+
+        let page = store
+            .query(&HistoryQuery {
+                offset: 1,
+                limit: 1,
+                sort: HistorySort::Largest,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(ids(page), ["c"]);
+
+        let filtered = store
+            .query(&HistoryQuery {
+                mode: Some(CaptureMode::Window),
+                window_title: Some("code".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(ids(filtered), ["c"]);
+
+        let tagged = HistoryQuery {
+            tag: Some("bug".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(store.query(&tagged).unwrap()), ["c"]);
+
+        let dated = store
+            .query(&HistoryQuery {
+                from: Some("2024-03-01T12:00:00Z".to_string()),
+                to: Some("2024-03-02T12:00:00+00:00".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(ids(dated), ["b"]);
+        assert!(store
+            .query(&HistoryQuery {
+                from: Some("yesterday".to_string()),
+                ..Default::default()
+            })
+            .is_err());
+
+        // Removing a capture drops its tags with it
+        assert!(store.remove("/captures/c.png").unwrap());
+        assert_eq!(store.query(&tagged).unwrap().total, 0);
+
+        assert_eq!(store.prune(Some(1)).unwrap(), 1);
+        assert_eq!(ids(store.query(&HistoryQuery::default()).unwrap()), ["b"]);
+        assert_eq!(store.get_latest().unwrap().unwrap().id, "b");
+        assert!(store.get("a").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_search() {
+        let store = HistoryStore::open(Path::new(":memory:")).unwrap();
+        {
+            let conn = store.conn.lock().unwrap();
+            insert(
+                &conn,
+                &item(
+                    "a",
+                    "2024-03-04T09:00:00Z",
+                    CaptureMode::Window,
+                    "Terminal",
+                    10,
+                ),
+            )
+            .unwrap();
+            insert(
+                &conn,
+                &item(
+                    "b",
+                    "2024-03-05T09:00:00Z",
+                    CaptureMode::Window,
+                    "Safari",
+                    10,
+                ),
+            )
+            .unwrap();
+            insert(
+                &conn,
+                &item(
+                    "c",
+                    "2024-03-06T09:00:00Z",
+                    CaptureMode::Region,
+                    "Notes",
+                    10,
+                ),
+            )
+            .unwrap();
+        }
+        store
+            .set_ocr_text(
+                "/captures/a.png",
+                "thread 'main' panicked at src/parser.rs:42\nstack backtrace:",
+            )
+            .unwrap();
+        store
+            .set_ocr_text("/captures/c.png", "stack of papers, trace the outline")
+            .unwrap();
+
+        let search = |query: &str, from: Option<&str>| {
+            store
+                .search(&SearchRequest {
+                    query: query.to_string(),
+                    from: from.map(str::to_string),
+                    to: None,
+                    offset: 0,
+                    limit: 10,
+                })
+                .unwrap()
+        };
+        let ids = |results: SearchResults| {
+            results
+                .hits
+                .into_iter()
+                .map(|hit| hit.item.id)
+                .collect::<Vec<_>>()
+        };
+
+        let results = search("stack backtrace", None);
+        assert_eq!(results.total, 1);
+        let snippet = &results.hits[0].snippet;
+        assert!(snippet.iter().any(|p| p.highlight && p.text == "backtrace"));
+        assert!(results.hits[0].score > 0.0);
+
+        assert_eq!(ids(search("\"stack backtrace\"", None)), ["a"]);
+        assert_eq!(ids(search("\"stack trace\"", None)), Vec::<String>::new());
+        assert_eq!(ids(search("pars*", None)).len(), 1);
+        // Titles, app names, file names and tags are indexed as well
+        assert_eq!(ids(search("safari", None)), ["b"]);
+        assert_eq!(ids(search("c.png", None)), ["c"]);
+        store
+            .add_tags(&["b".to_string()], &["invoice".to_string()])
+            .unwrap();
+        assert_eq!(ids(search("invoice", None)), ["b"]);
+        store
+            .remove_tags(&["b".to_string()], &["invoice".to_string()])
+            .unwrap();
+        assert_eq!(search("invoice", None).total, 0);
+        assert_eq!(ids(search("stack", Some("2024-03-05T00:00:00Z"))), ["c"]);
+        assert_eq!(search("", Some("2024-03-05T00:00:00Z")).total, 2);
+
+        // The index follows updates and removals
+        store
+            .set_ocr_text("/captures/c.png", "nothing here")
+            .unwrap();
+        assert_eq!(ids(search("stack", None)), ["a"]);
+        store.remove("/captures/a.png").unwrap();
+        assert_eq!(search("stack", None).total, 0);
+    }
+
+    #[test]
+    fn test_import_and_scan() {
+        let dir = std::env::temp_dir().join(format!("grab-history-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let image = |name: &str, width: u32| {
+            let path = dir.join(name);
+            RgbaImage::from_pixel(width, 20, Rgba([width as u8, 0, 0, 255]))
+                .save(&path)
+                .unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let kept = image("kept.png", 30);
+        let legacy = vec![
+            HistoryItem {
+                file_path: kept.clone(),
+                ..item("1", "2024-01-01T00:00:00Z", CaptureMode::Region, "Code", 0)
+            },
+            HistoryItem {
+                file_path: dir.join("gone.png").to_string_lossy().to_string(),
+                ..item("2", "2024-01-02T00:00:00Z", CaptureMode::Region, "Code", 0)
+            },
+        ];
+        let json = dir.join(LEGACY_HISTORY_FILE);
+        let store = HistoryStore::open(&dir.join(DATABASE_FILE)).unwrap();
+
+        // A corrupt file is left for the next attempt
+        fs::write(&json, "[{\"id\": ").unwrap();
+        assert!(store.import_legacy(&json).is_err());
+        assert!(json.exists());
+
+        fs::write(&json, serde_json::to_string(&legacy).unwrap()).unwrap();
+        assert_eq!(store.import_legacy(&json).unwrap(), 1);
+        assert!(!json.exists());
+        assert!(dir.join("history.json.migrated").exists());
+        assert_eq!(store.import_legacy(&json).unwrap(), 0);
+
+        let imported = store.get("1").unwrap().unwrap();
+        assert_eq!((imported.width, imported.height), (Some(30), Some(20)));
+        assert!(imported.perceptual_hash.is_none());
+
+        // Scanning picks up new files and drops gone ones, leaving the
+        // hashing to the worker
+        let added = image("added.png", 40);
+        assert_eq!(store.scan_directory(&dir).unwrap(), 1);
+        assert_eq!(store.scan_directory(&dir).unwrap(), 0);
+        assert_eq!(store.unhashed().unwrap(), [added.clone(), kept.clone()]);
+        dedupe::backfill(&store);
+        assert!(store.unhashed().unwrap().is_empty());
+        assert!(store.get("1").unwrap().unwrap().perceptual_hash.is_some());
+
+        fs::remove_file(&kept).unwrap();
+        store.scan_directory(&dir).unwrap();
+        let all = store.get_all().unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].file_path, added);
+        assert_eq!(all[0].width, Some(40));
+
+        // Reopening keeps the library
+        drop(store);
+        let reopened = HistoryStore::open(&dir.join(DATABASE_FILE)).unwrap();
+        assert_eq!(reopened.get_all().unwrap().len(), 1);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_tags_pins_and_collections() {
+        let store = HistoryStore::open(Path::new(":memory:")).unwrap();
+        {
+            let conn = store.conn.lock().unwrap();
+            for (id, day) in [("a", 1), ("b", 2), ("c", 3)] {
+                let timestamp = format!("2024-03-0{}T09:00:00Z", day);
+                insert(
+                    &conn,
+                    &item(id, &timestamp, CaptureMode::Region, "Code", 1000),
+                )
+                .unwrap();
+            }
+        }
+        let ids = |page: HistoryPage| page.items.into_iter().map(|i| i.id).collect::<Vec<_>>();
+        let all = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        let tags = all(&[" Bug ", "api", "bug", ""]);
+        assert_eq!(store.add_tags(&all(&["a", "b"]), &tags).unwrap(), 6);
+        assert_eq!(store.add_tags(&all(&["a"]), &tags).unwrap(), 0);
+        assert_eq!(store.get("a").unwrap().unwrap().tags, ["Bug", "api", "bug"]);
+        assert_eq!(
+            store
+                .remove_tags(&all(&["b"]), &all(&["Bug", "bug"]))
+                .unwrap(),
+            2
+        );
+        let counts: Vec<_> = store
+            .tags()
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.tag, t.count))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("api".to_string(), 2),
+                ("Bug".to_string(), 1),
+                ("bug".to_string(), 1)
+            ]
+        );
+
+        // Pinned captures survive trimming to the limit
+        assert_eq!(store.set_pinned(&all(&["a"]), true).unwrap(), 1);
+        assert_eq!(store.set_pinned(&all(&["a", "missing"]), true).unwrap(), 0);
+        let pinned = HistoryQuery {
+            pinned: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(ids(store.query(&pinned).unwrap()), ["a"]);
+
+        let shots = store.create_collection(" Release shots ").unwrap();
+        assert_eq!(shots.name, "Release shots");
+        assert!(store.create_collection("release SHOTS").is_err());
+        assert!(store.create_collection("  ").is_err());
+        let other = store.create_collection("Other").unwrap();
+        assert!(store.rename_collection(&other.id, "Release Shots").is_err());
+        assert_eq!(
+            store.rename_collection(&other.id, "Bugs").unwrap().name,
+            "Bugs"
+        );
+
+        assert_eq!(
+            store
+                .add_to_collection(&shots.id, &all(&["b", "c", "c"]))
+                .unwrap(),
+            2
+        );
+        assert!(store.add_to_collection("missing", &all(&["b"])).is_err());
+        let in_shots = HistoryQuery {
+            collection: Some(shots.id.clone()),
+            ..Default::default()
+        };
+        assert_eq!(ids(store.query(&in_shots).unwrap()), ["c", "b"]);
+        let counts: Vec<_> = store
+            .collections()
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.name, c.item_count))
+            .collect();
+        assert_eq!(
+            counts,
+            [("Bugs".to_string(), 0), ("Release shots".to_string(), 2)]
+        );
+
+        assert_eq!(store.prune(Some(1)).unwrap(), 1);
+        assert_eq!(
+            ids(store.query(&HistoryQuery::default()).unwrap()),
+            ["c", "a"]
+        );
+        assert_eq!(ids(store.query(&in_shots).unwrap()), ["c"]);
+        assert_eq!(
+            store
+                .remove_from_collection(&shots.id, &all(&["c"]))
+                .unwrap(),
+            1
+        );
+
+        // Deleting a collection keeps its captures
+        assert!(store.delete_collection(&shots.id).unwrap());
+        assert!(!store.delete_collection(&shots.id).unwrap());
+        assert_eq!(store.get_all().unwrap().len(), 2);
+    }
+}
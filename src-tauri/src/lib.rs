@@ -3,17 +3,46 @@
 //!
 //! Migrated from Electron to Tauri for better performance and smaller bundle size.
 
+mod ai;
+mod annotate;
+mod barcode;
+mod batch;
+mod beautify;
 mod capture;
+mod cli;
+mod color;
 mod commands;
+mod compose;
+mod dedupe;
+mod diff;
 mod error;
+mod export;
 mod history;
+mod measure;
+mod metadata;
+mod ocr;
+mod optimize;
+mod pdf;
 mod preferences;
+mod project;
+mod redact;
+mod search;
+mod secrets;
 mod tray;
+mod trim;
 mod types;
+mod watermark;
 
 use tauri::{Manager, RunEvent};
 use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
 
+/// Run a command-line command, if the arguments name one
+///
+/// Returns the process exit code, or `None` to start the GUI.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    cli::run(args)
+}
+
 /// Configure and run the Tauri application
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -32,9 +61,32 @@ pub fn run() {
             // Initialize history
             let history_store = history::HistoryStore::new(app.handle())?;
 
+            // Start the background PNG optimizer
+            let optimize_worker = optimize::OptimizeWorker::new(app.handle())?;
+
+            // Start hashing captures found by scanning, for duplicate detection
+            let hash_worker = dedupe::HashWorker::new(app.handle())?;
+
+            // Start reading the text of captures for search
+            let search_indexer = search::SearchIndexer::new(app.handle())?;
+            let ocr_settings = preferences.get().ocr;
+
             // Store state
             app.manage(preferences);
             app.manage(history_store);
+            app.manage(optimize_worker);
+            app.manage(batch::BatchJobs::new());
+            app.manage(ocr::OcrCache::new(app.handle())?);
+
+            // Catch up on captures the index hasn't read
+            if ocr_settings.index_captures {
+                search_indexer.queue_unindexed(ocr_settings);
+            }
+            app.manage(search_indexer);
+
+            // Catch up on captures recorded without hashes
+            hash_worker.queue_unhashed();
+            app.manage(hash_worker);
 
             // Setup system tray
             tray::setup_tray(app.handle())?;
@@ -55,6 +107,18 @@ pub fn run() {
             commands::get_window_sources,
             // History commands
             commands::get_history,
+            commands::query_history,
+            commands::search_history,
+            commands::add_tags,
+            commands::remove_tags,
+            commands::list_tags,
+            commands::set_pinned,
+            commands::list_collections,
+            commands::create_collection,
+            commands::rename_collection,
+            commands::delete_collection,
+            commands::add_to_collection,
+            commands::remove_from_collection,
             commands::remove_from_history,
             commands::scan_directory,
             // Preferences commands
@@ -66,8 +130,34 @@ pub fn run() {
             commands::save_image,
             commands::copy_to_clipboard,
             commands::delete_screenshot,
+            commands::find_duplicates,
+            commands::delete_captures,
+            commands::keep_newest_duplicates,
             commands::reveal_in_folder,
             commands::export_capture,
+            commands::batch_export,
+            commands::cancel_batch_export,
+            commands::export_pdf,
+            commands::compose_captures,
+            commands::diff_captures,
+            commands::render_annotations,
+            commands::redact_image,
+            commands::beautify_image,
+            commands::detect_secrets,
+            commands::extract_text,
+            commands::get_ocr_languages,
+            commands::run_smart_action,
+            commands::scan_codes,
+            commands::copy_text,
+            commands::pick_color,
+            commands::extract_palette,
+            commands::measure_element,
+            commands::measure_distance,
+            commands::measurement_annotations,
+            // Project commands
+            commands::open_project,
+            commands::save_project,
+            commands::export_project,
         ])
         .build(tauri::generate_context!())
         .expect("Error while building Tauri application");
@@ -97,6 +187,7 @@ fn register_global_shortcuts(app: &tauri::App) -> Result<(), Box<dyn std::error:
     let full_screen_shortcut: Shortcut = preferences.shortcuts.full_screen.parse()?;
     let region_shortcut: Shortcut = preferences.shortcuts.region.parse()?;
     let window_shortcut: Shortcut = preferences.shortcuts.window.parse()?;
+    let scan_code_shortcut: Shortcut = preferences.shortcuts.scan_code.parse()?;
 
     // Register shortcuts
     app.handle().plugin(
@@ -122,6 +213,12 @@ fn register_global_shortcuts(app: &tauri::App) -> Result<(), Box<dyn std::error:
                                 eprintln!("Window capture failed: {}", e);
                             }
                         });
+                    } else if shortcut == &scan_code_shortcut {
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(e) = commands::trigger_scan_code(&handle).await {
+                                eprintln!("Code scan failed: {}", e);
+                            }
+                        });
                     }
                 }
             })
@@ -138,6 +235,9 @@ fn register_global_shortcuts(app: &tauri::App) -> Result<(), Box<dyn std::error:
     if let Err(e) = app.global_shortcut().register(window_shortcut) {
         eprintln!("Warning: Could not register window shortcut: {}", e);
     }
+    if let Err(e) = app.global_shortcut().register(scan_code_shortcut) {
+        eprintln!("Warning: Could not register scan code shortcut: {}", e);
+    }
 
     Ok(())
 }
@@ -0,0 +1,290 @@
+//! Pixel measurement over a frozen capture
+//!
+//! Works like the smart guides in design tools: from a point, scan outwards
+//! until the colour changes to find the element under it, or measure the
+//! distance between two points. Captures are in physical pixels, so results
+//! are also given in logical pixels using the monitor's scale factor.
+
+use crate::error::{GrabError, GrabResult};
+use crate::types::{
+    Annotation, Color, DistanceMeasurement, ElementMeasurement, Measurement, Point, RegionBounds,
+};
+use image::{Rgba, RgbaImage};
+
+/// Colour used for exported measurements when none is given
+pub const DEFAULT_COLOR: Color = Color([255, 45, 122, 255]);
+
+/// Find the area of uniform colour around a point
+///
+/// Scans left, right, up and down from the point and stops at the first
+/// pixel that differs from the colour under the point by more than
+/// `tolerance` in any channel. The image edge also counts as a boundary.
+pub fn probe(
+    image: &RgbaImage,
+    x: u32,
+    y: u32,
+    tolerance: u8,
+    scale_factor: f64,
+) -> GrabResult<ElementMeasurement> {
+    if x >= image.width() || y >= image.height() {
+        return Err(GrabError::InvalidRequest(format!(
+            "Point {},{} lies outside the {}x{} image",
+            x,
+            y,
+            image.width(),
+            image.height()
+        )));
+    }
+
+    let reference = *image.get_pixel(x, y);
+    let same = |px: u32, py: u32| similar(image.get_pixel(px, py), &reference, tolerance);
+
+    let mut left = x;
+    while left > 0 && same(left - 1, y) {
+        left -= 1;
+    }
+    let mut right = x;
+    while right + 1 < image.width() && same(right + 1, y) {
+        right += 1;
+    }
+    let mut top = y;
+    while top > 0 && same(x, top - 1) {
+        top -= 1;
+    }
+    let mut bottom = y;
+    while bottom + 1 < image.height() && same(x, bottom + 1) {
+        bottom += 1;
+    }
+
+    let scale = valid_scale(scale_factor);
+    let (width, height) = (right - left + 1, bottom - top + 1);
+    Ok(ElementMeasurement {
+        x,
+        y,
+        bounds: RegionBounds {
+            x: left as i32,
+            y: top as i32,
+            width,
+            height,
+        },
+        scale_factor: scale,
+        logical_width: round(width as f64 / scale),
+        logical_height: round(height as f64 / scale),
+    })
+}
+
+/// Distance between two points given in physical pixels
+pub fn distance(from: Point, to: Point, scale_factor: f64) -> DistanceMeasurement {
+    let scale = valid_scale(scale_factor);
+    let dx = (to.x - from.x).abs() as f64;
+    let dy = (to.y - from.y).abs() as f64;
+    let distance = dx.hypot(dy);
+
+    DistanceMeasurement {
+        from,
+        to,
+        dx: round(dx),
+        dy: round(dy),
+        distance: round(distance),
+        scale_factor: scale,
+        logical_dx: round(dx / scale),
+        logical_dy: round(dy / scale),
+        logical_distance: round(distance / scale),
+    }
+}
+
+/// Turn measurements into annotations that can be added to a project
+///
+/// Distances become a line with end ticks and a label at its middle;
+/// elements become an outline with a size label above it. Labels show
+/// logical pixels as well when the scale factor is not 1.
+pub fn to_annotations(measurements: &[Measurement], color: Color) -> Vec<Annotation> {
+    let mut annotations = Vec::new();
+    for measurement in measurements {
+        match measurement {
+            Measurement::Distance(m) => {
+                let scale = m.scale_factor as f32;
+                let stroke_width = scale;
+                annotations.push(Annotation::Line {
+                    from: m.from,
+                    to: m.to,
+                    color,
+                    stroke_width,
+                    dashed: false,
+                });
+
+                // Ticks perpendicular to the line, like on a ruler
+                let (dx, dy) = (m.to.x - m.from.x, m.to.y - m.from.y);
+                let length = dx.hypot(dy);
+                if length > 0.0 {
+                    let tick = 6.0 * scale;
+                    let (nx, ny) = (-dy / length * tick, dx / length * tick);
+                    for end in [m.from, m.to] {
+                        annotations.push(Annotation::Line {
+                            from: Point {
+                                x: end.x - nx,
+                                y: end.y - ny,
+                            },
+                            to: Point {
+                                x: end.x + nx,
+                                y: end.y + ny,
+                            },
+                            color,
+                            stroke_width,
+                            dashed: false,
+                        });
+                    }
+                }
+
+                annotations.push(label(
+                    (m.from.x + m.to.x) / 2.0 + 4.0 * scale,
+                    (m.from.y + m.to.y) / 2.0 + 4.0 * scale,
+                    length_label(m.distance, m.logical_distance, m.scale_factor),
+                    color,
+                    scale,
+                ));
+            }
+            Measurement::Element(m) => {
+                let scale = m.scale_factor as f32;
+                let bounds = &m.bounds;
+                annotations.push(Annotation::Rectangle {
+                    x: bounds.x as f32,
+                    y: bounds.y as f32,
+                    width: bounds.width as f32,
+                    height: bounds.height as f32,
+                    color,
+                    stroke_width: scale,
+                    fill: None,
+                    corner_radius: 0.0,
+                });
+
+                let text = if m.scale_factor == 1.0 {
+                    format!("{} × {}", bounds.width, bounds.height)
+                } else {
+                    format!(
+                        "{} × {} ({} × {} pt)",
+                        bounds.width, bounds.height, m.logical_width, m.logical_height
+                    )
+                };
+                // Above the outline, or inside it at the top of the image
+                let font_size = 12.0 * scale;
+                let y = bounds.y as f32 - font_size * 1.6;
+                let y = if y < 0.0 {
+                    bounds.y as f32 + 4.0 * scale
+                } else {
+                    y
+                };
+                annotations.push(label(bounds.x as f32, y, text, color, scale));
+            }
+        }
+    }
+    annotations
+}
+
+fn label(x: f32, y: f32, text: String, color: Color, scale: f32) -> Annotation {
+    Annotation::Text {
+        x,
+        y,
+        text,
+        color: Color([255, 255, 255, 255]),
+        font_size: 12.0 * scale,
+        bold: true,
+        background: Some(color),
+    }
+}
+
+fn length_label(physical: f64, logical: f64, scale_factor: f64) -> String {
+    if scale_factor == 1.0 {
+        format!("{} px", physical)
+    } else {
+        format!("{} px ({} pt)", physical, logical)
+    }
+}
+
+fn similar(a: &Rgba<u8>, b: &Rgba<u8>, tolerance: u8) -> bool {
+    a.0.iter()
+        .zip(b.0.iter())
+        .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+}
+
+/// Scale factors from metadata can be missing or nonsensical
+fn valid_scale(scale_factor: f64) -> f64 {
+    if scale_factor.is_finite() && scale_factor > 0.0 {
+        scale_factor
+    } else {
+        1.0
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_finds_element_bounds() {
+        let mut image = RgbaImage::from_pixel(200, 120, Rgba([255, 255, 255, 255]));
+        for y in 20..60 {
+            for x in 30..130 {
+                // Slight noise stays within the tolerance
+                let shade = if (x + y) % 2 == 0 { 240 } else { 236 };
+                image.put_pixel(x, y, Rgba([37, 99, shade, 255]));
+            }
+        }
+
+        let element = probe(&image, 50, 40, 16, 2.0).unwrap();
+        assert_eq!(
+            element.bounds,
+            RegionBounds {
+                x: 30,
+                y: 20,
+                width: 100,
+                height: 40
+            }
+        );
+        assert_eq!(
+            (element.logical_width, element.logical_height),
+            (50.0, 20.0)
+        );
+
+        // The background reaches the image edges
+        let background = probe(&image, 150, 40, 16, 1.0).unwrap();
+        assert_eq!(background.bounds.x, 130);
+        assert_eq!(background.bounds.width, 70);
+        assert_eq!(background.bounds.y, 0);
+        assert_eq!(background.bounds.height, 120);
+
+        assert!(probe(&image, 200, 0, 16, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_distance_and_annotations() {
+        let m = distance(Point { x: 10.0, y: 10.0 }, Point { x: 40.0, y: 50.0 }, 2.0);
+        assert_eq!((m.dx, m.dy, m.distance), (30.0, 40.0, 50.0));
+        assert_eq!(m.logical_distance, 25.0);
+
+        let element = probe(&RgbaImage::new(10, 10), 5, 5, 0, 0.0).unwrap();
+        assert_eq!(element.scale_factor, 1.0);
+
+        let annotations = to_annotations(
+            &[Measurement::Distance(m), Measurement::Element(element)],
+            DEFAULT_COLOR,
+        );
+        // Line, two ticks and a label; outline and a label
+        assert_eq!(annotations.len(), 6);
+        match &annotations[3] {
+            Annotation::Text { text, .. } => assert_eq!(text, "50 px (25 pt)"),
+            other => panic!("expected a label, got {:?}", other),
+        }
+        match &annotations[5] {
+            Annotation::Text { text, y, .. } => {
+                assert_eq!(text, "10 × 10");
+                assert!(*y >= 0.0);
+            }
+            other => panic!("expected a label, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,766 @@
+//! Capture metadata embedded in saved images
+//!
+//! `CaptureMetadata` travels with the file as an XMP packet under the grab
+//! namespace: an iTXt chunk for PNG, an APP1 segment for JPEG and an `XMP `
+//! chunk for WebP. A few basics are also written where other tools look for
+//! them (PNG tEXt, JPEG/WebP EXIF), and tags go in `dc:subject` like any
+//! photo keywords.
+
+use crate::error::{GrabError, GrabResult};
+use crate::optimize;
+use crate::types::CaptureMetadata;
+use image::ImageFormat;
+use std::fs;
+use std::path::Path;
+
+/// XML namespace for grab properties
+pub const GRAB_NAMESPACE: &str = "https://github.com/akv004/grab/ns/1.0/";
+
+/// Keyword of the PNG iTXt chunk holding XMP
+const PNG_XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+/// PNG text keywords written (and replaced) by grab
+const PNG_OWNED_KEYWORDS: &[&str] = &[PNG_XMP_KEYWORD, "Software", "Creation Time", "Title"];
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const JPEG_EXIF_HEADER: &[u8] = b"Exif\0\0";
+const JPEG_APP0: u8 = 0xE0;
+const JPEG_APP1: u8 = 0xE1;
+const JPEG_SOS: u8 = 0xDA;
+
+const WEBP_FLAG_ALPHA: u8 = 0x10;
+const WEBP_FLAG_EXIF: u8 = 0x08;
+const WEBP_FLAG_XMP: u8 = 0x04;
+
+/// A PNG text chunk to write alongside the image data
+pub struct PngTextChunk {
+    pub keyword: String,
+    pub text: String,
+    /// Written as iTXt (UTF-8) rather than tEXt (Latin-1)
+    pub utf8: bool,
+}
+
+/// Name and version recorded as the creating software
+pub fn software() -> String {
+    format!("grab {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Text chunks describing a capture, for the PNG encoder
+pub fn png_text_chunks(metadata: &CaptureMetadata) -> Vec<PngTextChunk> {
+    let mut chunks = vec![
+        PngTextChunk {
+            keyword: "Software".to_string(),
+            text: software(),
+            utf8: false,
+        },
+        PngTextChunk {
+            keyword: "Creation Time".to_string(),
+            text: metadata.timestamp.clone(),
+            utf8: false,
+        },
+    ];
+
+    if let Some(ref title) = metadata.window_title {
+        chunks.push(PngTextChunk {
+            keyword: "Title".to_string(),
+            text: title.clone(),
+            utf8: true,
+        });
+    }
+
+    chunks.push(PngTextChunk {
+        keyword: PNG_XMP_KEYWORD.to_string(),
+        text: build_xmp(metadata),
+        utf8: true,
+    });
+
+    chunks
+}
+
+/// Build an XMP packet carrying the capture metadata
+pub fn build_xmp(metadata: &CaptureMetadata) -> String {
+    let mode = serde_json::to_value(metadata.mode)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    let mut attributes = vec![
+        ("xmp:CreatorTool", software()),
+        ("xmp:CreateDate", metadata.timestamp.clone()),
+        ("grab:Mode", mode),
+    ];
+    if let Some(ref app) = metadata.app_name {
+        attributes.push(("grab:AppName", app.clone()));
+    }
+    if let Some(ref title) = metadata.window_title {
+        attributes.push(("grab:WindowTitle", title.clone()));
+    }
+
+    let attributes: String = attributes
+        .iter()
+        .map(|(name, value)| format!("\n    {}=\"{}\"", name, escape_xml(value)))
+        .collect();
+
+    let subject = if metadata.tags.is_empty() {
+        String::new()
+    } else {
+        let items: String = metadata
+            .tags
+            .iter()
+            .map(|tag| format!("     <rdf:li>{}</rdf:li>\n", escape_xml(tag)))
+            .collect();
+        format!("   <dc:subject>\n    <rdf:Bag>\n{}    </rdf:Bag>\n   </dc:subject>\n", items)
+    };
+
+    // Full metadata as JSON so readers don't depend on the attribute layout
+    let json = serde_json::to_string(metadata).unwrap_or_default();
+
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+            " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+            "  <rdf:Description rdf:about=\"\"\n",
+            "    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n",
+            "    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n",
+            "    xmlns:grab=\"{ns}\"{attributes}>\n",
+            "{subject}",
+            "   <grab:Capture>{json}</grab:Capture>\n",
+            "  </rdf:Description>\n",
+            " </rdf:RDF>\n",
+            "</x:xmpmeta>\n",
+            "<?xpacket end=\"w\"?>"
+        ),
+        ns = GRAB_NAMESPACE,
+        attributes = attributes,
+        subject = subject,
+        json = escape_xml(&json),
+    )
+}
+
+/// Extract capture metadata from an XMP packet written by grab
+///
+/// Tags come from `dc:subject` when present, so keywords edited in other
+/// tools win over the copy grab keeps.
+pub fn parse_xmp(xmp: &str) -> Option<CaptureMetadata> {
+    let start = xmp.find("<grab:Capture>")? + "<grab:Capture>".len();
+    let end = start + xmp[start..].find("</grab:Capture>")?;
+    let mut metadata: CaptureMetadata =
+        serde_json::from_str(&unescape_xml(&xmp[start..end])).ok()?;
+    if let Some(tags) = parse_subject(xmp) {
+        metadata.tags = tags;
+    }
+    Some(metadata)
+}
+
+/// Items of the `dc:subject` bag, if there is one
+fn parse_subject(xmp: &str) -> Option<Vec<String>> {
+    let start = xmp.find("<dc:subject>")? + "<dc:subject>".len();
+    let end = start + xmp[start..].find("</dc:subject>")?;
+    let mut rest = &xmp[start..end];
+    let mut tags = Vec::new();
+    while let Some(open) = rest.find("<rdf:li") {
+        // Skip any attributes, e.g. a language
+        let Some(content) = rest[open..].find('>').map(|i| open + i + 1) else {
+            break;
+        };
+        let Some(close) = rest[content..].find("</rdf:li>").map(|i| content + i) else {
+            break;
+        };
+        let tag = unescape_xml(rest[content..close].trim());
+        if !tag.is_empty() {
+            tags.push(tag);
+        }
+        rest = &rest[close..];
+    }
+    Some(tags)
+}
+
+/// Value of a single EXIF tag
+enum ExifValue {
+    Ascii(String),
+    Short(u16),
+    Rational(u32, u32),
+}
+
+/// Build a little-endian EXIF (TIFF) block with the basic capture fields
+/// and, optionally, the image resolution
+pub fn build_exif(metadata: Option<&CaptureMetadata>, dpi: Option<u32>) -> Vec<u8> {
+    let mut entries: Vec<(u16, ExifValue)> = Vec::new();
+
+    if let Some(metadata) = metadata {
+        if let Some(ref title) = metadata.window_title {
+            entries.push((0x010E, ExifValue::Ascii(title.clone()))); // ImageDescription
+        }
+        entries.push((0x0131, ExifValue::Ascii(software()))); // Software
+        if let Ok(time) = chrono::DateTime::parse_from_rfc3339(&metadata.timestamp) {
+            let time = time.format("%Y:%m:%d %H:%M:%S").to_string();
+            entries.push((0x0132, ExifValue::Ascii(time))); // DateTime
+        }
+    }
+    if let Some(dpi) = dpi {
+        entries.push((0x011A, ExifValue::Rational(dpi, 1))); // XResolution
+        entries.push((0x011B, ExifValue::Rational(dpi, 1))); // YResolution
+        entries.push((0x0128, ExifValue::Short(2))); // ResolutionUnit: inches
+    }
+    entries.sort_by_key(|(tag, _)| *tag);
+
+    let ifd_offset = 8u32;
+    let ifd_len = 2 + entries.len() as u32 * 12 + 4;
+    let mut data_offset = ifd_offset + ifd_len;
+
+    let mut ifd = Vec::new();
+    let mut data = Vec::new();
+    ifd.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+
+    for (tag, value) in &entries {
+        let (kind, count, mut bytes) = match value {
+            ExifValue::Ascii(text) => {
+                let mut bytes = text.as_bytes().to_vec();
+                bytes.push(0);
+                (2u16, bytes.len() as u32, bytes)
+            }
+            ExifValue::Short(value) => (3u16, 1, value.to_le_bytes().to_vec()),
+            ExifValue::Rational(num, den) => {
+                let mut bytes = num.to_le_bytes().to_vec();
+                bytes.extend_from_slice(&den.to_le_bytes());
+                (5u16, 1, bytes)
+            }
+        };
+
+        ifd.extend_from_slice(&tag.to_le_bytes());
+        ifd.extend_from_slice(&kind.to_le_bytes());
+        ifd.extend_from_slice(&count.to_le_bytes());
+
+        // Values up to four bytes are stored inline
+        if bytes.len() <= 4 {
+            bytes.resize(4, 0);
+            ifd.extend_from_slice(&bytes);
+        } else {
+            ifd.extend_from_slice(&data_offset.to_le_bytes());
+            if bytes.len() % 2 == 1 {
+                bytes.push(0);
+            }
+            data_offset += bytes.len() as u32;
+            data.extend_from_slice(&bytes);
+        }
+    }
+    ifd.extend_from_slice(&0u32.to_le_bytes()); // No next IFD
+
+    let mut exif = b"II*\0".to_vec();
+    exif.extend_from_slice(&ifd_offset.to_le_bytes());
+    exif.extend_from_slice(&ifd);
+    exif.extend_from_slice(&data);
+    exif
+}
+
+/// Embed capture metadata and/or a DPI value into already-encoded image bytes
+///
+/// Any metadata previously written by grab is replaced. With no metadata
+/// (e.g. when the user strips it) only the resolution is written.
+pub fn embed(
+    bytes: &[u8],
+    format: ImageFormat,
+    metadata: Option<&CaptureMetadata>,
+    dpi: Option<u32>,
+) -> GrabResult<Vec<u8>> {
+    if metadata.is_none() && dpi.is_none() {
+        return Ok(bytes.to_vec());
+    }
+
+    let xmp = metadata.map(build_xmp);
+    match format {
+        ImageFormat::Png => {
+            let chunks = metadata.map(png_text_chunks).unwrap_or_default();
+            embed_png(bytes, &chunks, dpi)
+        }
+        ImageFormat::Jpeg => embed_jpeg(bytes, &build_exif(metadata, dpi), xmp.as_deref()),
+        ImageFormat::WebP => embed_webp(bytes, &build_exif(metadata, dpi), xmp.as_deref()),
+        _ => Ok(bytes.to_vec()),
+    }
+}
+
+/// Read capture metadata embedded by grab, if any
+pub fn read_embedded(bytes: &[u8]) -> Option<CaptureMetadata> {
+    let xmp = match image::guess_format(bytes).ok()? {
+        ImageFormat::Png => png_xmp(bytes),
+        ImageFormat::Jpeg => jpeg_xmp(bytes),
+        ImageFormat::WebP => webp_xmp(bytes),
+        _ => None,
+    }?;
+    parse_xmp(&xmp)
+}
+
+/// Read capture metadata embedded in a file on disk
+pub fn read_from_file(path: &Path) -> Option<CaptureMetadata> {
+    read_embedded(&fs::read(path).ok()?)
+}
+
+/// Replace the tags embedded in an image file
+///
+/// Files without grab metadata get what `fallback` makes from their bytes.
+/// Only the metadata is rewritten, and the file is swapped atomically.
+pub fn write_tags(
+    path: &Path,
+    tags: &[String],
+    fallback: impl FnOnce(&[u8]) -> GrabResult<CaptureMetadata>,
+) -> GrabResult<()> {
+    let bytes = fs::read(path)?;
+    let format = image::guess_format(&bytes)?;
+    let mut metadata = match read_embedded(&bytes) {
+        Some(metadata) => metadata,
+        None => fallback(&bytes)?,
+    };
+    if metadata.tags == tags {
+        return Ok(());
+    }
+    metadata.tags = tags.to_vec();
+    optimize::replace_atomically(path, &embed(&bytes, format, Some(&metadata), None)?)
+}
+
+// ============================================================================
+// PNG
+// ============================================================================
+
+/// Byte range of a chunk (or segment) and its payload within a file
+struct Block {
+    id: [u8; 4],
+    range: std::ops::Range<usize>,
+    data: std::ops::Range<usize>,
+}
+
+fn png_blocks(bytes: &[u8]) -> GrabResult<Vec<Block>> {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(GrabError::InvalidRequest("Not a PNG file".to_string()));
+    }
+
+    let mut blocks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 12 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+        let end = pos + 12 + len;
+        if end > bytes.len() {
+            return Err(GrabError::InvalidRequest("Truncated PNG chunk".to_string()));
+        }
+        blocks.push(Block {
+            id: bytes[pos + 4..pos + 8].try_into().unwrap(),
+            range: pos..end,
+            data: pos + 8..pos + 8 + len,
+        });
+        pos = end;
+    }
+    Ok(blocks)
+}
+
+fn png_chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(id);
+    hasher.update(data);
+
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(id);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&hasher.finalize().to_be_bytes());
+    chunk
+}
+
+fn png_text_keyword(data: &[u8]) -> &[u8] {
+    data.split(|b| *b == 0).next().unwrap_or_default()
+}
+
+fn embed_png(bytes: &[u8], chunks: &[PngTextChunk], dpi: Option<u32>) -> GrabResult<Vec<u8>> {
+    let blocks = png_blocks(bytes)?;
+    let mut out = Vec::with_capacity(bytes.len() + 4096);
+    out.extend_from_slice(PNG_SIGNATURE);
+
+    for block in &blocks {
+        let data = &bytes[block.data.clone()];
+        let owned = matches!(&block.id, b"tEXt" | b"iTXt" | b"zTXt")
+            && PNG_OWNED_KEYWORDS
+                .iter()
+                .any(|k| k.as_bytes() == png_text_keyword(data));
+        if owned || (dpi.is_some() && &block.id == b"pHYs") {
+            continue;
+        }
+
+        out.extend_from_slice(&bytes[block.range.clone()]);
+
+        if &block.id == b"IHDR" {
+            if let Some(dpi) = dpi {
+                // pHYs is in pixels per metre
+                let ppm = (dpi as f64 / 0.0254).round() as u32;
+                let mut data = ppm.to_be_bytes().to_vec();
+                data.extend_from_slice(&ppm.to_be_bytes());
+                data.push(1);
+                out.extend_from_slice(&png_chunk(b"pHYs", &data));
+            }
+            for chunk in chunks {
+                let mut data = chunk.keyword.as_bytes().to_vec();
+                data.push(0);
+                if chunk.utf8 {
+                    // Uncompressed, no language tag or translated keyword
+                    data.extend_from_slice(&[0, 0, 0, 0]);
+                    data.extend_from_slice(chunk.text.as_bytes());
+                    out.extend_from_slice(&png_chunk(b"iTXt", &data));
+                } else {
+                    data.extend(chunk.text.chars().map(|c| if (c as u32) < 256 { c as u8 } else { b'?' }));
+                    out.extend_from_slice(&png_chunk(b"tEXt", &data));
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn png_xmp(bytes: &[u8]) -> Option<String> {
+    for block in png_blocks(bytes).ok()? {
+        if &block.id == b"IDAT" {
+            break;
+        }
+        let data = &bytes[block.data];
+        if &block.id != b"iTXt" || png_text_keyword(data) != PNG_XMP_KEYWORD.as_bytes() {
+            continue;
+        }
+        // keyword \0 compression-flag method language \0 translated \0 text
+        let rest = data.get(PNG_XMP_KEYWORD.len() + 1..)?;
+        if rest.first() != Some(&0) {
+            return None; // Compressed XMP is not written by grab
+        }
+        let mut parts = rest.get(2..)?.splitn(3, |b| *b == 0);
+        let text = parts.nth(2)?;
+        return String::from_utf8(text.to_vec()).ok();
+    }
+    None
+}
+
+// ============================================================================
+// JPEG
+// ============================================================================
+
+fn jpeg_blocks(bytes: &[u8]) -> GrabResult<(Vec<Block>, usize)> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Err(GrabError::InvalidRequest("Not a JPEG file".to_string()));
+    }
+
+    let mut blocks = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() && bytes[pos] == 0xFF {
+        let marker = bytes[pos + 1];
+        if marker == JPEG_SOS {
+            break;
+        }
+        let len = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let end = pos + 2 + len;
+        if len < 2 || end > bytes.len() {
+            return Err(GrabError::InvalidRequest("Truncated JPEG segment".to_string()));
+        }
+        blocks.push(Block {
+            id: [marker, 0, 0, 0],
+            range: pos..end,
+            data: pos + 4..end,
+        });
+        pos = end;
+    }
+    Ok((blocks, pos))
+}
+
+fn jpeg_segment(marker: u8, header: &[u8], payload: &[u8]) -> GrabResult<Vec<u8>> {
+    let len = 2 + header.len() + payload.len();
+    if len > u16::MAX as usize {
+        return Err(GrabError::ExportFailed("Metadata too large for JPEG".to_string()));
+    }
+    let mut segment = vec![0xFF, marker];
+    segment.extend_from_slice(&(len as u16).to_be_bytes());
+    segment.extend_from_slice(header);
+    segment.extend_from_slice(payload);
+    Ok(segment)
+}
+
+fn embed_jpeg(bytes: &[u8], exif: &[u8], xmp: Option<&str>) -> GrabResult<Vec<u8>> {
+    let (blocks, scan_start) = jpeg_blocks(bytes)?;
+
+    let mut segments = jpeg_segment(JPEG_APP1, JPEG_EXIF_HEADER, exif)?;
+    if let Some(xmp) = xmp {
+        segments.extend_from_slice(&jpeg_segment(JPEG_APP1, JPEG_XMP_HEADER, xmp.as_bytes())?);
+    }
+
+    let mut out = Vec::with_capacity(bytes.len() + segments.len());
+    out.extend_from_slice(&[0xFF, 0xD8]);
+
+    // EXIF and XMP go after the JFIF header if there is one
+    let insert_after = blocks
+        .first()
+        .filter(|b| b.id[0] == JPEG_APP0)
+        .map(|b| b.range.end)
+        .unwrap_or(2);
+    if insert_after == 2 {
+        out.extend_from_slice(&segments);
+    }
+
+    for block in &blocks {
+        let data = &bytes[block.data.clone()];
+        let owned = block.id[0] == JPEG_APP1
+            && (data.starts_with(JPEG_EXIF_HEADER) || data.starts_with(JPEG_XMP_HEADER));
+        if owned {
+            continue;
+        }
+        out.extend_from_slice(&bytes[block.range.clone()]);
+        if block.range.end == insert_after {
+            out.extend_from_slice(&segments);
+        }
+    }
+
+    out.extend_from_slice(&bytes[scan_start..]);
+    Ok(out)
+}
+
+fn jpeg_xmp(bytes: &[u8]) -> Option<String> {
+    let (blocks, _) = jpeg_blocks(bytes).ok()?;
+    blocks.iter().find_map(|block| {
+        let data = &bytes[block.data.clone()];
+        (block.id[0] == JPEG_APP1 && data.starts_with(JPEG_XMP_HEADER))
+            .then(|| String::from_utf8_lossy(&data[JPEG_XMP_HEADER.len()..]).to_string())
+    })
+}
+
+// ============================================================================
+// WebP
+// ============================================================================
+
+fn webp_blocks(bytes: &[u8]) -> GrabResult<Vec<Block>> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WEBP" {
+        return Err(GrabError::InvalidRequest("Not a WebP file".to_string()));
+    }
+
+    let mut blocks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let data_end = pos + 8 + len;
+        if data_end > bytes.len() {
+            return Err(GrabError::InvalidRequest("Truncated WebP chunk".to_string()));
+        }
+        // Chunks are padded to an even length
+        let end = (data_end + (len & 1)).min(bytes.len());
+        blocks.push(Block {
+            id: bytes[pos..pos + 4].try_into().unwrap(),
+            range: pos..end,
+            data: pos + 8..data_end,
+        });
+        pos = end;
+    }
+    Ok(blocks)
+}
+
+fn riff_chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(data.len() + 9);
+    chunk.extend_from_slice(id);
+    chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+/// Canvas size and alpha usage from a simple (VP8/VP8L) bitstream
+fn webp_canvas(bytes: &[u8], blocks: &[Block]) -> Option<(u32, u32, bool)> {
+    let has_alph = blocks.iter().any(|b| &b.id == b"ALPH");
+    for block in blocks {
+        let data = &bytes[block.data.clone()];
+        match &block.id {
+            b"VP8L" if data.len() >= 5 && data[0] == 0x2F => {
+                let bits = u32::from_le_bytes(data[1..5].try_into().unwrap());
+                let width = (bits & 0x3FFF) + 1;
+                let height = ((bits >> 14) & 0x3FFF) + 1;
+                let alpha = (bits >> 28) & 1 == 1;
+                return Some((width, height, alpha));
+            }
+            b"VP8 " if data.len() >= 10 && data[3..6] == [0x9D, 0x01, 0x2A] => {
+                let width = u16::from_le_bytes([data[6], data[7]]) as u32 & 0x3FFF;
+                let height = u16::from_le_bytes([data[8], data[9]]) as u32 & 0x3FFF;
+                return Some((width, height, has_alph));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn embed_webp(bytes: &[u8], exif: &[u8], xmp: Option<&str>) -> GrabResult<Vec<u8>> {
+    let blocks = webp_blocks(bytes)?;
+    let flags = if xmp.is_some() {
+        WEBP_FLAG_EXIF | WEBP_FLAG_XMP
+    } else {
+        WEBP_FLAG_EXIF
+    };
+
+    let vp8x = match blocks.iter().find(|b| &b.id == b"VP8X") {
+        Some(block) => {
+            let mut data = bytes[block.data.clone()].to_vec();
+            if data.len() < 10 {
+                return Err(GrabError::InvalidRequest("Invalid VP8X chunk".to_string()));
+            }
+            data[0] = (data[0] & !(WEBP_FLAG_EXIF | WEBP_FLAG_XMP)) | flags;
+            data
+        }
+        None => {
+            let (width, height, alpha) = webp_canvas(bytes, &blocks)
+                .ok_or_else(|| GrabError::InvalidRequest("Unsupported WebP bitstream".to_string()))?;
+            let mut data = vec![flags, 0, 0, 0];
+            if alpha {
+                data[0] |= WEBP_FLAG_ALPHA;
+            }
+            data.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+            data.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+            data
+        }
+    };
+
+    let mut body = b"WEBP".to_vec();
+    body.extend_from_slice(&riff_chunk(b"VP8X", &vp8x));
+    for block in &blocks {
+        if !matches!(&block.id, b"VP8X" | b"EXIF" | b"XMP ") {
+            body.extend_from_slice(&bytes[block.range.clone()]);
+        }
+    }
+    // EXIF and XMP come after the image data
+    body.extend_from_slice(&riff_chunk(b"EXIF", exif));
+    if let Some(xmp) = xmp {
+        body.extend_from_slice(&riff_chunk(b"XMP ", xmp.as_bytes()));
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+fn webp_xmp(bytes: &[u8]) -> Option<String> {
+    let blocks = webp_blocks(bytes).ok()?;
+    blocks
+        .iter()
+        .find(|b| &b.id == b"XMP ")
+        .map(|b| String::from_utf8_lossy(&bytes[b.data.clone()]).to_string())
+}
+
+// ============================================================================
+// XML helpers
+// ============================================================================
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{CaptureMode, RegionBounds};
+    use image::{DynamicImage, RgbaImage};
+    use std::io::Cursor;
+
+    fn sample_metadata() -> CaptureMetadata {
+        CaptureMetadata {
+            mode: CaptureMode::Window,
+            display_id: None,
+            window_id: Some("42".to_string()),
+            bounds: RegionBounds {
+                x: 10,
+                y: 20,
+                width: 300,
+                height: 200,
+            },
+            timestamp: "2024-05-01T12:30:00+00:00".to_string(),
+            scale_factor: 2.0,
+            file_name: None,
+            window_title: Some("Tom & Jerry <draft>".to_string()),
+            app_name: Some("Notes".to_string()),
+            redactions: Vec::new(),
+            trim: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 6, image::Rgba([1, 2, 3, 255])));
+        let image = match format {
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()),
+            _ => image,
+        };
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), format).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_metadata_round_trips_through_each_format() {
+        let metadata = sample_metadata();
+
+        for format in [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::WebP] {
+            let bytes = embed(&encode(format), format, Some(&metadata), Some(144)).unwrap();
+            // Embedding twice must replace rather than duplicate
+            let bytes = embed(&bytes, format, Some(&metadata), None).unwrap();
+
+            let read = read_embedded(&bytes).expect("metadata should be readable");
+            assert_eq!(read.mode, CaptureMode::Window);
+            assert_eq!(read.window_title, metadata.window_title);
+            assert_eq!(read.app_name, metadata.app_name);
+            assert!(image::load_from_memory(&bytes).is_ok(), "{:?} still decodes", format);
+        }
+    }
+
+    #[test]
+    fn test_tags_are_written_as_dc_subject() {
+        let metadata = CaptureMetadata {
+            tags: vec!["bug".to_string(), "R&D".to_string()],
+            ..sample_metadata()
+        };
+        let xmp = build_xmp(&metadata);
+        assert!(xmp.contains("<rdf:li>R&amp;D</rdf:li>"));
+        assert_eq!(parse_xmp(&xmp).unwrap().tags, metadata.tags);
+
+        // Keywords edited elsewhere replace grab's copy
+        let edited = xmp.replace("<rdf:li>bug</rdf:li>", "<rdf:li xml:lang=\"en\">urgent</rdf:li>");
+        assert_eq!(parse_xmp(&edited).unwrap().tags, ["urgent", "R&D"]);
+        assert!(!build_xmp(&sample_metadata()).contains("dc:subject"));
+
+        let path = std::env::temp_dir().join(format!("grab-tags-{}.png", uuid::Uuid::new_v4()));
+        fs::write(&path, encode(ImageFormat::Png)).unwrap();
+        write_tags(&path, &metadata.tags, |_| Ok(sample_metadata())).unwrap();
+        assert_eq!(read_from_file(&path).unwrap().tags, metadata.tags);
+        write_tags(&path, &[], |_| unreachable!()).unwrap();
+        assert!(read_from_file(&path).unwrap().tags.is_empty());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_malformed_xmp_chunk_is_ignored() {
+        let png = encode(ImageFormat::Png);
+        // Right after IHDR
+        let split = PNG_SIGNATURE.len() + 25;
+        for tail in [&b""[..], b"\0", b"\0\0", b"\0\0\0", b"\0\0\0en"] {
+            let mut data = PNG_XMP_KEYWORD.as_bytes().to_vec();
+            data.extend_from_slice(tail);
+            let mut bytes = png[..split].to_vec();
+            bytes.extend_from_slice(&png_chunk(b"iTXt", &data));
+            bytes.extend_from_slice(&png[split..]);
+            assert!(read_embedded(&bytes).is_none(), "{:?}", tail);
+        }
+    }
+}
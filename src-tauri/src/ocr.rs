@@ -0,0 +1,389 @@
+//! Text recognition
+//!
+//! Engines implement [`OcrEngine`]. The built-in one shells out to a locally
+//! installed `tesseract`, so recognition works fully offline: the image is
+//! written to a temporary file and tesseract is asked for TSV output, which
+//! carries a box and confidence for every word.
+//!
+//! Results are cached on disk, keyed by a hash of the image file, the
+//! engine, the languages and the region.
+
+use crate::error::{GrabError, GrabResult};
+use crate::types::{OcrLine, OcrResult, OcrSettings, OcrWord, RegionBounds};
+use image::{imageops, RgbaImage};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::Command;
+use tauri::{AppHandle, Manager};
+
+/// Name of the tesseract executable on `PATH`
+const TESSERACT: &str = "tesseract";
+
+/// TSV `level` of a single word
+const WORD_LEVEL: &str = "5";
+
+/// Cache directory inside the app data directory
+const CACHE_DIR: &str = "ocr-cache";
+
+/// Cached results kept before the oldest are dropped
+const MAX_CACHE_ENTRIES: usize = 500;
+
+/// A text recognition backend
+pub trait OcrEngine: Send + Sync {
+    /// Short identifier, recorded in results and cache keys
+    fn name(&self) -> &str;
+
+    /// Recognize the text in an image using the given language codes
+    fn recognize(&self, image: &RgbaImage, languages: &[String]) -> GrabResult<OcrResult>;
+}
+
+/// OCR through the `tesseract` command-line tool
+pub struct Tesseract {
+    binary: PathBuf,
+}
+
+impl Tesseract {
+    pub fn new(settings: &OcrSettings) -> Self {
+        Tesseract {
+            binary: settings
+                .tesseract_path
+                .as_deref()
+                .filter(|path| !path.trim().is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(TESSERACT)),
+        }
+    }
+
+    /// Language codes with installed training data
+    pub fn languages(&self) -> GrabResult<Vec<String>> {
+        let stdout = self.run(Command::new(&self.binary).arg("--list-langs"))?;
+        // The first line is a header naming the tessdata directory
+        Ok(stdout
+            .lines()
+            .skip(1)
+            .map(str::trim)
+            .filter(|lang| !lang.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn run(&self, command: &mut Command) -> GrabResult<String> {
+        let output = command.output().map_err(|e| match e.kind() {
+            ErrorKind::NotFound => {
+                GrabError::OcrFailed(format!("{} is not installed", self.binary.display()))
+            }
+            _ => GrabError::OcrFailed(e.to_string()),
+        })?;
+        if !output.status.success() {
+            return Err(GrabError::OcrFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+impl OcrEngine for Tesseract {
+    fn name(&self) -> &str {
+        TESSERACT
+    }
+
+    fn recognize(&self, image: &RgbaImage, languages: &[String]) -> GrabResult<OcrResult> {
+        for lang in languages {
+            if lang.is_empty() || !lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(GrabError::InvalidRequest(format!(
+                    "Invalid OCR language: {}",
+                    lang
+                )));
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("grab-ocr-{}.png", uuid::Uuid::new_v4()));
+        image.save(&path)?;
+
+        let mut command = Command::new(&self.binary);
+        command.arg(&path).arg("stdout");
+        if !languages.is_empty() {
+            command.arg("-l").arg(languages.join("+"));
+        }
+        let stdout = self.run(command.arg("tsv"));
+        let _ = fs::remove_file(&path);
+
+        Ok(to_result(parse_tsv(&stdout?), self.name(), languages))
+    }
+}
+
+/// Recognize text in part of an image, keeping boxes in image coordinates
+pub fn recognize_region(
+    engine: &dyn OcrEngine,
+    image: &RgbaImage,
+    region: Option<&RegionBounds>,
+    languages: &[String],
+) -> GrabResult<OcrResult> {
+    let Some(region) = region else {
+        return engine.recognize(image, languages);
+    };
+
+    let x = region.x.clamp(0, image.width() as i32) as u32;
+    let y = region.y.clamp(0, image.height() as i32) as u32;
+    let width = region.width.min(image.width() - x);
+    let height = region.height.min(image.height() - y);
+    if width == 0 || height == 0 {
+        return Err(GrabError::InvalidRequest(
+            "OCR region lies outside the image".to_string(),
+        ));
+    }
+
+    let cropped = imageops::crop_imm(image, x, y, width, height).to_image();
+    let mut result = engine.recognize(&cropped, languages)?;
+    for line in &mut result.lines {
+        offset(&mut line.bounds, x, y);
+        for word in &mut line.words {
+            offset(&mut word.bounds, x, y);
+        }
+    }
+    Ok(result)
+}
+
+/// Parse tesseract's TSV output into words
+///
+/// Columns: level, page, block, paragraph, line, word, left, top, width,
+/// height, conf, text. Lines are numbered in reading order.
+pub fn parse_tsv(tsv: &str) -> Vec<OcrWord> {
+    let mut words = Vec::new();
+    let mut current_line = None;
+    let mut line = 0;
+
+    for row in tsv.lines().skip(1) {
+        let columns: Vec<&str> = row.splitn(12, '\t').collect();
+        if columns.len() < 12 || columns[0] != WORD_LEVEL {
+            continue;
+        }
+        let text = columns[11].trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let number = |i: usize| columns[i].trim().parse::<i64>().unwrap_or(0);
+        let key = (number(1), number(2), number(3), number(4));
+        if current_line.is_some_and(|current| current != key) {
+            line += 1;
+        }
+        current_line = Some(key);
+
+        words.push(OcrWord {
+            text: text.to_string(),
+            bounds: RegionBounds {
+                x: number(6) as i32,
+                y: number(7) as i32,
+                width: number(8).max(0) as u32,
+                height: number(9).max(0) as u32,
+            },
+            confidence: columns[10].trim().parse::<f32>().unwrap_or(0.0).max(0.0),
+            line,
+        });
+    }
+
+    words
+}
+
+/// Group words into lines and build the full text
+pub fn to_result(words: Vec<OcrWord>, engine: &str, languages: &[String]) -> OcrResult {
+    let mut lines: Vec<OcrLine> = Vec::new();
+    for word in words {
+        match lines.last_mut() {
+            Some(line) if line.words[0].line == word.line => line.words.push(word),
+            _ => lines.push(OcrLine {
+                text: String::new(),
+                bounds: word.bounds.clone(),
+                confidence: 0.0,
+                words: vec![word],
+            }),
+        }
+    }
+
+    for line in &mut lines {
+        let words = &line.words;
+        line.text = words
+            .iter()
+            .map(|w| w.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        line.confidence = words.iter().map(|w| w.confidence).sum::<f32>() / words.len() as f32;
+
+        let left = words.iter().map(|w| w.bounds.x).min().unwrap_or(0);
+        let top = words.iter().map(|w| w.bounds.y).min().unwrap_or(0);
+        let right = words
+            .iter()
+            .map(|w| w.bounds.x + w.bounds.width as i32)
+            .max()
+            .unwrap_or(0);
+        let bottom = words
+            .iter()
+            .map(|w| w.bounds.y + w.bounds.height as i32)
+            .max()
+            .unwrap_or(0);
+        line.bounds = RegionBounds {
+            x: left,
+            y: top,
+            width: (right - left).max(0) as u32,
+            height: (bottom - top).max(0) as u32,
+        };
+    }
+
+    OcrResult {
+        text: lines
+            .iter()
+            .map(|l| l.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        lines,
+        engine: engine.to_string(),
+        languages: languages.to_vec(),
+    }
+}
+
+fn offset(bounds: &mut RegionBounds, x: u32, y: u32) {
+    bounds.x += x as i32;
+    bounds.y += y as i32;
+}
+
+/// On-disk cache of recognition results
+#[derive(Clone)]
+pub struct OcrCache {
+    dir: PathBuf,
+}
+
+impl OcrCache {
+    pub fn new(app_handle: &AppHandle) -> GrabResult<Self> {
+        let app_data_dir = app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| GrabError::Io(std::io::Error::new(ErrorKind::NotFound, e)))?;
+        Ok(Self::in_dir(app_data_dir.join(CACHE_DIR)))
+    }
+
+    pub fn in_dir(dir: PathBuf) -> Self {
+        OcrCache { dir }
+    }
+
+    /// Cache key for an image file read with the given settings
+    pub fn key(
+        file_bytes: &[u8],
+        engine: &str,
+        languages: &[String],
+        region: Option<&RegionBounds>,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(file_bytes);
+        hasher.update(engine.as_bytes());
+        hasher.update(languages.join("+").as_bytes());
+        if let Some(r) = region {
+            hasher.update(format!("{},{},{},{}", r.x, r.y, r.width, r.height).as_bytes());
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<OcrResult> {
+        let content = fs::read_to_string(self.dir.join(format!("{}.json", key))).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn put(&self, key: &str, result: &OcrResult) -> GrabResult<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(
+            self.dir.join(format!("{}.json", key)),
+            serde_json::to_string(result)?,
+        )?;
+        self.prune();
+        Ok(())
+    }
+
+    /// Drop the least recently written entries beyond the limit
+    fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut files: Vec<_> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                Some((modified, entry.path()))
+            })
+            .collect();
+        if files.len() <= MAX_CACHE_ENTRIES {
+            return;
+        }
+
+        files.sort();
+        for (_, path) in &files[..files.len() - MAX_CACHE_ENTRIES] {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns canned words relative to whatever image it is given
+    struct FixedEngine;
+
+    impl OcrEngine for FixedEngine {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn recognize(&self, _image: &RgbaImage, languages: &[String]) -> GrabResult<OcrResult> {
+            let tsv = "\
+level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext
+4\t1\t1\t1\t1\t0\t2\t3\t90\t12\t-1\t
+5\t1\t1\t1\t1\t1\t2\t3\t40\t12\t90\tHello
+5\t1\t1\t1\t1\t2\t50\t4\t42\t11\t80\tworld
+5\t1\t1\t1\t2\t1\t2\t20\t30\t12\t70\tagain
+";
+            Ok(to_result(parse_tsv(tsv), self.name(), languages))
+        }
+    }
+
+    #[test]
+    fn test_region_results_use_image_coordinates() {
+        let image = RgbaImage::new(200, 100);
+        let region = RegionBounds {
+            x: 100,
+            y: 50,
+            width: 100,
+            height: 50,
+        };
+        let languages = vec!["eng".to_string()];
+
+        let result = recognize_region(&FixedEngine, &image, Some(&region), &languages).unwrap();
+        assert_eq!(result.text, "Hello world\nagain");
+        assert_eq!(result.lines[0].confidence, 85.0);
+        assert_eq!(
+            result.lines[0].bounds,
+            RegionBounds {
+                x: 102,
+                y: 53,
+                width: 90,
+                height: 12,
+            }
+        );
+        assert_eq!(result.lines[1].words[0].bounds.y, 70);
+
+        let dir = std::env::temp_dir().join(format!("grab-ocr-{}", uuid::Uuid::new_v4()));
+        let cache = OcrCache::in_dir(dir.clone());
+        let key = OcrCache::key(b"png", "fixed", &languages, Some(&region));
+        assert_ne!(key, OcrCache::key(b"png", "fixed", &languages, None));
+        cache.put(&key, &result).unwrap();
+        assert_eq!(cache.get(&key), Some(result));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
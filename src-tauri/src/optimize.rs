@@ -0,0 +1,417 @@
+//! Background PNG optimization
+//!
+//! Captures are written with the fastest PNG settings so the shortcut feels
+//! instant. A worker thread then re-encodes each file with adaptive filtering
+//! and maximum deflate and atomically swaps it in when the result is smaller.
+//!
+//! Captures with at most 256 distinct colours, such as flat UI, are written
+//! as an indexed palette instead. That is lossless palette reduction, not
+//! quantization: a capture with more colours (anti-aliased text, photos,
+//! gradients) keeps every pixel and only gets the better deflate.
+//!
+//! Tags are written into files by the same worker, so the two rewrites of a
+//! capture never race.
+
+use crate::error::{GrabError, GrabResult};
+use crate::history::HistoryStore;
+use crate::metadata;
+use crate::types::{CaptureMetadata, OptimizeSettings};
+use image::RgbaImage;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// PNG palettes are limited to 256 entries
+const MAX_PALETTE_COLORS: usize = 256;
+
+/// Size of a file before and after optimization
+#[derive(Debug, Clone, Copy)]
+pub struct OptimizeOutcome {
+    pub original_size: u64,
+    pub optimized_size: u64,
+}
+
+/// Metadata for a file saved without it, made from the file's bytes
+pub type MetadataFallback = Box<dyn FnOnce(&[u8]) -> GrabResult<CaptureMetadata> + Send>;
+
+enum OptimizeJob {
+    Optimize {
+        path: PathBuf,
+        settings: OptimizeSettings,
+    },
+    /// Replace the tags embedded in a file
+    Tags {
+        path: PathBuf,
+        tags: Vec<String>,
+        fallback: MetadataFallback,
+    },
+}
+
+/// Worker that optimizes and tags saved captures one at a time
+pub struct OptimizeWorker {
+    sender: Mutex<Sender<OptimizeJob>>,
+}
+
+impl OptimizeWorker {
+    /// Spawn the worker thread
+    pub fn new(app_handle: &AppHandle) -> GrabResult<Self> {
+        let (sender, receiver) = mpsc::channel::<OptimizeJob>();
+        let handle = app_handle.clone();
+
+        thread::Builder::new()
+            .name("grab-optimize".to_string())
+            .spawn(move || {
+                for job in receiver {
+                    let history = handle.state::<HistoryStore>();
+                    match job {
+                        OptimizeJob::Optimize { path, settings } => {
+                            match optimize_png(&path, &settings) {
+                                Ok(outcome) => {
+                                    let path_str = path.to_string_lossy().to_string();
+                                    if let Err(e) = history.set_file_sizes(
+                                        &path_str,
+                                        outcome.original_size,
+                                        outcome.optimized_size,
+                                    ) {
+                                        eprintln!("Failed to record optimized size: {}", e);
+                                    }
+                                }
+                                Err(e) => {
+                                    eprintln!("Optimize failed for {}: {}", path.display(), e);
+                                    continue;
+                                }
+                            }
+                        }
+                        OptimizeJob::Tags {
+                            path,
+                            tags,
+                            fallback,
+                        } => {
+                            // A file that can't be rewritten keeps its old
+                            // tags; the library is what counts
+                            let written = metadata::write_tags(&path, &tags, fallback)
+                                .and_then(|()| Ok(fs::metadata(&path)?.len()))
+                                .and_then(|size| {
+                                    history.set_file_size(&path.to_string_lossy(), size)
+                                });
+                            if let Err(e) = written {
+                                eprintln!("Failed to write tags to {}: {}", path.display(), e);
+                                continue;
+                            }
+                        }
+                    }
+                    if let Some(window) = handle.get_webview_window("main") {
+                        window.emit("history:refresh", ()).ok();
+                    }
+                }
+            })?;
+
+        Ok(OptimizeWorker {
+            sender: Mutex::new(sender),
+        })
+    }
+
+    /// Queue a saved PNG for optimization
+    pub fn queue(&self, path: PathBuf, settings: OptimizeSettings) {
+        self.send(OptimizeJob::Optimize { path, settings });
+    }
+
+    /// Queue writing a capture's tags into its file
+    pub fn queue_tags(&self, path: PathBuf, tags: Vec<String>, fallback: MetadataFallback) {
+        self.send(OptimizeJob::Tags {
+            path,
+            tags,
+            fallback,
+        });
+    }
+
+    fn send(&self, job: OptimizeJob) {
+        if self.sender.lock().unwrap().send(job).is_err() {
+            eprintln!("Optimize worker is not running");
+        }
+    }
+}
+
+/// Re-encode a PNG on disk, replacing it only if the result is smaller
+pub fn optimize_png(path: &Path, settings: &OptimizeSettings) -> GrabResult<OptimizeOutcome> {
+    let original = fs::read(path)?;
+    let original_size = original.len() as u64;
+    let modified = fs::metadata(path)?.modified().ok();
+
+    let ancillary = read_ancillary_chunks(&original)?;
+    let image = image::load_from_memory_with_format(&original, image::ImageFormat::Png)?.to_rgba8();
+
+    let encoded = encode_optimized(&image, &ancillary, settings)?;
+    let optimized_size = encoded.len() as u64;
+
+    if optimized_size >= original_size {
+        return Ok(OptimizeOutcome {
+            original_size,
+            optimized_size: original_size,
+        });
+    }
+
+    // Don't clobber the file if it was edited while we were encoding
+    let current = fs::metadata(path)?;
+    if current.len() != original_size || current.modified().ok() != modified {
+        return Err(GrabError::Cancelled);
+    }
+
+    replace_atomically(path, &encoded)?;
+
+    Ok(OptimizeOutcome {
+        original_size,
+        optimized_size,
+    })
+}
+
+/// Chunks carried over from the original file
+#[derive(Default)]
+struct AncillaryChunks {
+    text: Vec<(String, String)>,
+    ztxt: Vec<(String, String)>,
+    itxt: Vec<(String, String)>,
+    pixel_dims: Option<png::PixelDimensions>,
+}
+
+fn read_ancillary_chunks(bytes: &[u8]) -> GrabResult<AncillaryChunks> {
+    let reader = png::Decoder::new(Cursor::new(bytes))
+        .read_info()
+        .map_err(|e| GrabError::ExportFailed(e.to_string()))?;
+    let info = reader.info();
+
+    let mut chunks = AncillaryChunks {
+        pixel_dims: info.pixel_dims,
+        ..Default::default()
+    };
+
+    for chunk in &info.uncompressed_latin1_text {
+        chunks.text.push((chunk.keyword.clone(), chunk.text.clone()));
+    }
+    for chunk in &info.compressed_latin1_text {
+        if let Ok(text) = chunk.get_text() {
+            chunks.ztxt.push((chunk.keyword.clone(), text));
+        }
+    }
+    for chunk in &info.utf8_text {
+        if let Ok(text) = chunk.get_text() {
+            chunks.itxt.push((chunk.keyword.clone(), text));
+        }
+    }
+
+    Ok(chunks)
+}
+
+fn encode_optimized(
+    image: &RgbaImage,
+    ancillary: &AncillaryChunks,
+    settings: &OptimizeSettings,
+) -> GrabResult<Vec<u8>> {
+    let png_err = |e: png::EncodingError| GrabError::ExportFailed(e.to_string());
+
+    let palette = if settings.use_palette {
+        exact_palette(image)
+    } else {
+        None
+    };
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, image.width(), image.height());
+    encoder.set_compression(png::Compression::High);
+    encoder.set_pixel_dims(ancillary.pixel_dims);
+
+    let data = match palette {
+        Some(palette) => {
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(palette.depth);
+            encoder.set_palette(palette.rgb);
+            if !palette.alpha.is_empty() {
+                encoder.set_trns(palette.alpha);
+            }
+            // Filtering rarely helps indexed data
+            encoder.set_filter(png::Filter::NoFilter);
+            palette.indices
+        }
+        None if image.pixels().all(|p| p.0[3] == 255) => {
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_filter(png::Filter::Adaptive);
+            image.pixels().flat_map(|p| [p.0[0], p.0[1], p.0[2]]).collect()
+        }
+        None => {
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_filter(png::Filter::Adaptive);
+            image.as_raw().clone()
+        }
+    };
+
+    for (keyword, text) in &ancillary.text {
+        encoder.add_text_chunk(keyword.clone(), text.clone()).map_err(png_err)?;
+    }
+    for (keyword, text) in &ancillary.ztxt {
+        encoder.add_ztxt_chunk(keyword.clone(), text.clone()).map_err(png_err)?;
+    }
+    for (keyword, text) in &ancillary.itxt {
+        encoder.add_itxt_chunk(keyword.clone(), text.clone()).map_err(png_err)?;
+    }
+
+    let mut writer = encoder.write_header().map_err(png_err)?;
+    writer.write_image_data(&data).map_err(png_err)?;
+    writer.finish().map_err(png_err)?;
+
+    Ok(out)
+}
+
+/// Indexed representation of an image with at most 256 colours
+struct Palette {
+    rgb: Vec<u8>,
+    alpha: Vec<u8>,
+    depth: png::BitDepth,
+    indices: Vec<u8>,
+}
+
+/// Build an exact (lossless) palette, or `None` if there are too many colours
+///
+/// Pixels are never changed to make them fit.
+fn exact_palette(image: &RgbaImage) -> Option<Palette> {
+    let mut colors: Vec<[u8; 4]> = Vec::new();
+    let mut seen: HashSet<[u8; 4]> = HashSet::new();
+    let mut last: Option<[u8; 4]> = None;
+
+    for pixel in image.pixels() {
+        // Runs of identical pixels are the common case in UI screenshots
+        if last == Some(pixel.0) {
+            continue;
+        }
+        last = Some(pixel.0);
+        if seen.insert(pixel.0) {
+            if colors.len() == MAX_PALETTE_COLORS {
+                return None;
+            }
+            colors.push(pixel.0);
+        }
+    }
+
+    // Translucent entries first so the tRNS chunk can stop early
+    colors.sort_by_key(|c| c[3] == 255);
+    let lookup: HashMap<[u8; 4], u8> = colors
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (*c, i as u8))
+        .collect();
+
+    let (depth, bits) = match colors.len() {
+        0..=2 => (png::BitDepth::One, 1),
+        3..=4 => (png::BitDepth::Two, 2),
+        5..=16 => (png::BitDepth::Four, 4),
+        _ => (png::BitDepth::Eight, 8),
+    };
+
+    let width = image.width() as usize;
+    let row_bytes = (width * bits).div_ceil(8);
+    let mut indices = vec![0u8; row_bytes * image.height() as usize];
+
+    for (y, row) in image.rows().enumerate() {
+        let row_start = y * row_bytes;
+        for (x, pixel) in row.enumerate() {
+            let index = lookup[&pixel.0];
+            let bit = x * bits;
+            let shift = 8 - bits - (bit % 8);
+            indices[row_start + bit / 8] |= index << shift;
+        }
+    }
+
+    let rgb = colors.iter().flat_map(|c| [c[0], c[1], c[2]]).collect();
+    let alpha = colors
+        .iter()
+        .take_while(|c| c[3] != 255)
+        .map(|c| c[3])
+        .collect();
+
+    Some(Palette {
+        rgb,
+        alpha,
+        depth,
+        indices,
+    })
+}
+
+/// Write to a sibling temp file and rename it over the original
+pub fn replace_atomically(path: &Path, bytes: &[u8]) -> GrabResult<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| GrabError::InvalidRequest(format!("Invalid path: {}", path.display())))?
+        .to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.optimizing", file_name));
+
+    let write_result = (|| -> std::io::Result<()> {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()
+    })();
+
+    if let Err(e) = write_result.and_then(|_| fs::rename(&temp_path, path)) {
+        fs::remove_file(&temp_path).ok();
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_flat_image_uses_small_palette() {
+        let mut image = RgbaImage::from_pixel(64, 32, Rgba([255, 255, 255, 255]));
+        for x in 0..64 {
+            image.put_pixel(x, 10, Rgba([0, 122, 204, 255]));
+        }
+
+        let palette = exact_palette(&image).expect("two colours fit in a palette");
+        assert!(matches!(palette.depth, png::BitDepth::One));
+        assert_eq!(palette.indices.len(), 8 * 32);
+        assert!(palette.alpha.is_empty());
+    }
+
+    #[test]
+    fn test_many_colours_stay_lossless() {
+        // 257 distinct colours, one more than a palette holds
+        let image = RgbaImage::from_fn(257, 4, |x, y| {
+            Rgba([(x % 256) as u8, (x / 256) as u8 * 200, y as u8, 255])
+        });
+        assert!(exact_palette(&image).is_none());
+
+        let bytes =
+            encode_optimized(&image, &AncillaryChunks::default(), &OptimizeSettings::default())
+                .unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(decoded.color(), image::ColorType::Rgb8);
+        assert_eq!(decoded.to_rgba8(), image);
+    }
+
+    #[test]
+    fn test_optimized_png_round_trips() {
+        let image = RgbaImage::from_fn(40, 20, |x, y| {
+            Rgba([(x * 6) as u8, (y * 12) as u8, 90, if x < 4 { 128 } else { 255 }])
+        });
+
+        let ancillary = AncillaryChunks {
+            text: vec![("Software".to_string(), "grab".to_string())],
+            ..Default::default()
+        };
+        let bytes = encode_optimized(&image, &ancillary, &OptimizeSettings::default()).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgba8();
+
+        assert_eq!(decoded, image);
+        assert_eq!(read_ancillary_chunks(&bytes).unwrap().text, ancillary.text);
+    }
+}
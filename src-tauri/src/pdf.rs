@@ -0,0 +1,380 @@
+//! Multi-page PDF export
+//!
+//! A small PDF 1.4 writer: each capture becomes an image XObject (JPEG or
+//! Flate, with a soft mask for transparency) placed one per page or in a
+//! grid, with optional captions in the built-in Helvetica font.
+
+use crate::error::{GrabError, GrabResult};
+use crate::export;
+use crate::metadata;
+use crate::types::{Color, PdfCaption, PdfImageEncoding, PdfLayout, PdfOptions, PdfPageSize};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use std::io::Write;
+use std::path::Path;
+
+/// Page margin for A4/Letter, in points
+const MARGIN: f64 = 36.0;
+/// Gap between grid cells, in points
+const GUTTER: f64 = 12.0;
+const CAPTION_SIZE: f64 = 9.0;
+/// Space reserved under an image for its caption
+const CAPTION_BAND: f64 = 16.0;
+/// Images are never drawn larger than at 96 DPI
+const POINTS_PER_PIXEL: f64 = 72.0 / 96.0;
+
+/// An image to place in the PDF
+pub struct PdfImage {
+    pub image: DynamicImage,
+    pub caption: Option<String>,
+}
+
+/// Caption text for a capture, per the caption setting
+pub fn caption_for(caption: PdfCaption, path: &Path, timestamp: Option<&str>) -> Option<String> {
+    match caption {
+        PdfCaption::None => None,
+        PdfCaption::FileName => path.file_name().map(|n| n.to_string_lossy().to_string()),
+        PdfCaption::Timestamp => {
+            let timestamp = timestamp?;
+            Some(
+                chrono::DateTime::parse_from_rfc3339(timestamp)
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|_| timestamp.to_string()),
+            )
+        }
+    }
+}
+
+/// Render images into a PDF document
+pub fn write_pdf(images: &[PdfImage], options: &PdfOptions) -> GrabResult<Vec<u8>> {
+    if images.is_empty() {
+        return Err(GrabError::InvalidRequest("No images to export".to_string()));
+    }
+
+    let (columns, rows) = match (options.layout, options.page_size) {
+        // Fit-to-image pages hold exactly one image
+        (_, PdfPageSize::Fit) | (PdfLayout::Single, _) => (1, 1),
+        (PdfLayout::Grid { columns, rows }, _) => (columns.max(1), rows.max(1)),
+    };
+    let per_page = (columns * rows) as usize;
+
+    let mut pdf = PdfWriter::new();
+    let catalog_id = pdf.reserve();
+    let pages_id = pdf.reserve();
+    let font_id = pdf.reserve();
+    let info_id = pdf.reserve();
+
+    pdf.write_object(
+        font_id,
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+    );
+
+    let mut page_ids = Vec::new();
+    for chunk in images.chunks(per_page) {
+        let (page_width, page_height) = page_size(options.page_size, &chunk[0]);
+        let margin = if options.page_size == PdfPageSize::Fit {
+            0.0
+        } else {
+            MARGIN
+        };
+
+        let cell_width =
+            (page_width - 2.0 * margin - GUTTER * (columns - 1) as f64) / columns as f64;
+        let cell_height = (page_height - 2.0 * margin - GUTTER * (rows - 1) as f64) / rows as f64;
+
+        let mut content = String::new();
+        let mut xobjects = String::new();
+
+        for (i, item) in chunk.iter().enumerate() {
+            let column = (i as u32 % columns) as f64;
+            let row = (i as u32 / columns) as f64;
+            let cell_x = margin + column * (cell_width + GUTTER);
+            let cell_top = page_height - margin - row * (cell_height + GUTTER);
+
+            let band = if item.caption.is_some() { CAPTION_BAND } else { 0.0 };
+            let (width, height) = (item.image.width() as f64, item.image.height() as f64);
+            let scale = if options.page_size == PdfPageSize::Fit {
+                POINTS_PER_PIXEL
+            } else {
+                (cell_width / width)
+                    .min((cell_height - band) / height)
+                    .min(POINTS_PER_PIXEL)
+            };
+            let (draw_width, draw_height) = (width * scale, height * scale);
+
+            // Centered horizontally, top-aligned in the cell
+            let x = cell_x + (cell_width - draw_width) / 2.0;
+            let y = cell_top - draw_height;
+
+            let image_id = write_image(&mut pdf, &item.image, options)?;
+            let name = format!("Im{}", i + 1);
+            xobjects.push_str(&format!("/{} {} 0 R ", name, image_id));
+            content.push_str(&format!(
+                "q {:.2} 0 0 {:.2} {:.2} {:.2} cm /{} Do Q\n",
+                draw_width, draw_height, x, y, name
+            ));
+
+            if let Some(ref caption) = item.caption {
+                let max_chars = (cell_width / (CAPTION_SIZE * 0.55)).max(4.0) as usize;
+                content.push_str(&format!(
+                    "BT /F1 {} Tf {:.2} {:.2} Td ({}) Tj ET\n",
+                    CAPTION_SIZE,
+                    x,
+                    y - CAPTION_SIZE - 3.0,
+                    pdf_text(caption, max_chars)
+                ));
+            }
+        }
+
+        let content_id = pdf.reserve();
+        pdf.write_stream(content_id, "", content.as_bytes(), true);
+
+        let page_id = pdf.reserve();
+        pdf.write_object(
+            page_id,
+            &format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                 /Resources << /Font << /F1 {} 0 R >> /XObject << {}>> >> /Contents {} 0 R >>",
+                pages_id, page_width, page_height, font_id, xobjects, content_id
+            ),
+        );
+        page_ids.push(page_id);
+    }
+
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    pdf.write_object(
+        pages_id,
+        &format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            page_ids.len()
+        ),
+    );
+    pdf.write_object(catalog_id, &format!("<< /Type /Catalog /Pages {} 0 R >>", pages_id));
+    pdf.write_object(
+        info_id,
+        &format!(
+            "<< /Producer ({}) /CreationDate (D:{}) >>",
+            pdf_text(&metadata::software(), usize::MAX),
+            chrono::Utc::now().format("%Y%m%d%H%M%SZ")
+        ),
+    );
+
+    Ok(pdf.finish(catalog_id, info_id))
+}
+
+/// Page size in points
+fn page_size(size: PdfPageSize, first: &PdfImage) -> (f64, f64) {
+    match size {
+        PdfPageSize::A4 => (595.28, 841.89),
+        PdfPageSize::Letter => (612.0, 792.0),
+        PdfPageSize::Fit => {
+            let band = if first.caption.is_some() {
+                CAPTION_BAND
+            } else {
+                0.0
+            };
+            (
+                first.image.width() as f64 * POINTS_PER_PIXEL,
+                first.image.height() as f64 * POINTS_PER_PIXEL + band,
+            )
+        }
+    }
+}
+
+/// Write an image XObject (plus soft mask if needed) and return its id
+fn write_image(pdf: &mut PdfWriter, image: &DynamicImage, options: &PdfOptions) -> GrabResult<usize> {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let has_alpha = rgba.pixels().any(|p| p.0[3] != 255);
+
+    let image_id = pdf.reserve();
+    let mut dict = format!(
+        "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8",
+        width, height
+    );
+
+    match options.encoding {
+        PdfImageEncoding::Jpeg => {
+            // JPEG has no alpha, so flatten onto the page colour
+            let rgb = export::flatten(&rgba, Color::WHITE);
+            let quality = options
+                .quality
+                .unwrap_or(export::DEFAULT_JPEG_QUALITY)
+                .clamp(1, 100);
+            let mut jpeg = Vec::new();
+            JpegEncoder::new_with_quality(&mut jpeg, quality)
+                .encode_image(&rgb)
+                .map_err(|e| GrabError::ExportFailed(e.to_string()))?;
+
+            dict.push_str(" /Filter /DCTDecode");
+            pdf.write_stream(image_id, &dict, &jpeg, false);
+        }
+        PdfImageEncoding::Flate => {
+            if has_alpha {
+                let alpha: Vec<u8> = rgba.pixels().map(|p| p.0[3]).collect();
+                let mask_id = pdf.reserve();
+                pdf.write_stream(
+                    mask_id,
+                    &format!(
+                        "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray /BitsPerComponent 8",
+                        width, height
+                    ),
+                    &alpha,
+                    true,
+                );
+                dict.push_str(&format!(" /SMask {} 0 R", mask_id));
+            }
+
+            let rgb: Vec<u8> = rgba.pixels().flat_map(|p| [p.0[0], p.0[1], p.0[2]]).collect();
+            pdf.write_stream(image_id, &dict, &rgb, true);
+        }
+    }
+
+    Ok(image_id)
+}
+
+/// Escape text for a PDF string in WinAnsi encoding, truncating if needed
+fn pdf_text(text: &str, max_chars: usize) -> String {
+    let mut chars: Vec<char> = text.chars().collect();
+    if chars.len() > max_chars {
+        chars.truncate(max_chars.saturating_sub(3));
+        chars.extend("...".chars());
+    }
+
+    let mut out = String::new();
+    for c in chars {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            // Latin-1 supplement matches WinAnsi byte-for-byte
+            '\u{a0}'..='\u{ff}' => out.push_str(&format!("\\{:03o}", c as u32)),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+/// Tracks object offsets while a PDF is written
+struct PdfWriter {
+    out: Vec<u8>,
+    /// Byte offset of each object, indexed by id - 1
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn new() -> Self {
+        PdfWriter {
+            // Binary comment marks the file as binary for transfer tools
+            out: b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec(),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Allocate an object id
+    fn reserve(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len()
+    }
+
+    fn write_object(&mut self, id: usize, body: &str) {
+        self.offsets[id - 1] = self.out.len();
+        self.out
+            .extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", id, body).as_bytes());
+    }
+
+    /// Write a stream object; `dict` holds extra dictionary entries
+    fn write_stream(&mut self, id: usize, dict: &str, data: &[u8], compress: bool) {
+        let (data, filter) = if compress {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            // Writing to a Vec can't fail
+            encoder.write_all(data).ok();
+            (encoder.finish().unwrap_or_default(), " /Filter /FlateDecode")
+        } else {
+            (data.to_vec(), "")
+        };
+
+        self.offsets[id - 1] = self.out.len();
+        self.out.extend_from_slice(
+            format!(
+                "{} 0 obj\n<< {}{} /Length {} >>\nstream\n",
+                id,
+                dict,
+                filter,
+                data.len()
+            )
+            .as_bytes(),
+        );
+        self.out.extend_from_slice(&data);
+        self.out.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    /// Write the cross-reference table and trailer
+    fn finish(mut self, root: usize, info: usize) -> Vec<u8> {
+        let xref_offset = self.out.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            xref.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        xref.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            root,
+            info,
+            xref_offset
+        ));
+        self.out.extend_from_slice(xref.as_bytes());
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn test_grid_pdf_structure() {
+        let image = |alpha| PdfImage {
+            image: DynamicImage::ImageRgba8(RgbaImage::from_pixel(120, 80, Rgba([200, 10, 10, alpha]))),
+            caption: Some("shot (1).png".to_string()),
+        };
+        let images = vec![image(255), image(128), image(255)];
+        let options = PdfOptions {
+            layout: PdfLayout::Grid {
+                columns: 2,
+                rows: 1,
+            },
+            ..Default::default()
+        };
+
+        let bytes = write_pdf(&images, &options).unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+
+        assert!(bytes.starts_with(b"%PDF-1.4"));
+        assert_eq!(text.matches("/Type /Page ").count(), 2);
+        assert_eq!(text.matches("/SMask").count(), 1);
+        assert_eq!(pdf_text("shot (1) é✓.png", 80), "shot \\(1\\) \\351?.png");
+
+        // Every xref entry must point at the start of its object
+        let start = text.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let xref: usize = text[start..].lines().next().unwrap().parse().unwrap();
+        let table = std::str::from_utf8(&bytes[xref..]).unwrap();
+        let mut lines = table.lines();
+        assert_eq!(lines.next(), Some("xref"));
+        let count: usize = lines.next().unwrap()[2..].parse().unwrap();
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+        let entries: Vec<&str> = lines.take_while(|l| l.ends_with(" n ")).collect();
+        assert!(count > 1);
+        assert_eq!(entries.len(), count - 1);
+        for (id, line) in entries.iter().enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            assert!(bytes[offset..].starts_with(format!("{} 0 obj", id + 1).as_bytes()));
+        }
+    }
+}
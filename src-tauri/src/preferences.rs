@@ -5,7 +5,7 @@
 use crate::error::{GrabError, GrabResult};
 use crate::types::CapturePreferences;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use tauri::{AppHandle, Manager};
 
@@ -29,26 +29,7 @@ impl PreferencesStore {
         fs::create_dir_all(&app_data_dir)?;
 
         let file_path = app_data_dir.join(PREFERENCES_FILE);
-
-        // Load existing preferences or create defaults
-        let preferences = if file_path.exists() {
-            match fs::read_to_string(&file_path) {
-                Ok(content) => serde_json::from_str(&content).unwrap_or_else(|_| {
-                    let mut prefs = CapturePreferences::default();
-                    prefs.output_folder = get_default_output_folder();
-                    prefs
-                }),
-                Err(_) => {
-                    let mut prefs = CapturePreferences::default();
-                    prefs.output_folder = get_default_output_folder();
-                    prefs
-                }
-            }
-        } else {
-            let mut prefs = CapturePreferences::default();
-            prefs.output_folder = get_default_output_folder();
-            prefs
-        };
+        let preferences = load_preferences(&app_data_dir);
 
         let store = PreferencesStore {
             preferences: Mutex::new(preferences),
@@ -93,6 +74,21 @@ impl PreferencesStore {
     }
 }
 
+/// Load preferences from an app data directory, falling back to defaults
+///
+/// Doesn't need a running app, so the CLI can use it too.
+pub fn load_preferences(app_data_dir: &Path) -> CapturePreferences {
+    let file_path = app_data_dir.join(PREFERENCES_FILE);
+
+    fs::read_to_string(&file_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_else(|| CapturePreferences {
+            output_folder: get_default_output_folder(),
+            ..Default::default()
+        })
+}
+
 /// Get the default output folder for captures
 pub fn get_default_output_folder() -> String {
     dirs::picture_dir()
@@ -0,0 +1,482 @@
+//! Non-destructive `.grab` project files
+//!
+//! A project is a zip archive holding the untouched capture, its annotation
+//! layers and crop, so edits can be changed later without re-capturing:
+//!
+//! ```text
+//! manifest.json      format, version, metadata, crop, layer list
+//! original.png       capture pixels as taken
+//! preview.png        small flattened render for file browsers
+//! layers/NN.json     {"annotations": [...]} per layer
+//! ```
+//!
+//! The manifest version is `major.minor`. Readers reject a different major
+//! version and accept any minor one. Unknown fields written by newer
+//! versions are skipped; unknown annotation types are kept with their layer
+//! and written back on save, so editing the project doesn't lose them.
+
+use crate::annotate;
+use crate::error::{GrabError, GrabResult};
+use crate::metadata;
+use crate::redact;
+use crate::types::{AnnotationLayer, CaptureMetadata, GrabProject, Redaction, RegionBounds};
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// File extension for project files
+pub const PROJECT_EXTENSION: &str = "grab";
+
+const FORMAT: &str = "grab-project";
+const MAJOR_VERSION: u32 = 1;
+const MINOR_VERSION: u32 = 0;
+
+const MANIFEST: &str = "manifest.json";
+const ORIGINAL: &str = "original.png";
+const PREVIEW: &str = "preview.png";
+
+/// Longest edge of the embedded preview
+const PREVIEW_SIZE: u32 = 512;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format: String,
+    version: String,
+    #[serde(default)]
+    software: String,
+    #[serde(default)]
+    created_at: String,
+    #[serde(default)]
+    modified_at: String,
+    metadata: CaptureMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    crop: Option<RegionBounds>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redactions: Vec<Redaction>,
+    #[serde(default)]
+    layers: Vec<LayerEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LayerEntry {
+    id: String,
+    name: String,
+    #[serde(default = "default_true")]
+    visible: bool,
+    file: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LayerFile {
+    #[serde(default)]
+    annotations: Vec<serde_json::Value>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// A project read from disk
+pub struct ProjectContents {
+    pub project: GrabProject,
+    /// The original capture, PNG-encoded
+    pub original_png: Vec<u8>,
+}
+
+impl ProjectContents {
+    pub fn original(&self) -> GrabResult<RgbaImage> {
+        Ok(image::load_from_memory(&self.original_png)?.to_rgba8())
+    }
+}
+
+/// Whether a path names a project file
+pub fn is_project(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case(PROJECT_EXTENSION))
+        .unwrap_or(false)
+}
+
+/// Where the project for a capture lives: `<stem>.grab` beside it
+pub fn project_path_for(capture: &Path) -> PathBuf {
+    capture.with_extension(PROJECT_EXTENSION)
+}
+
+/// The project beside a capture, if one has been saved
+pub fn existing_project_for(capture: &Path) -> Option<PathBuf> {
+    let path = project_path_for(capture);
+    path.is_file().then_some(path)
+}
+
+/// Read a project file
+pub fn read_project(path: &Path) -> GrabResult<ProjectContents> {
+    let file = fs::File::open(path)?;
+    let mut archive = ZipArchive::new(file).map_err(invalid)?;
+
+    let manifest: Manifest = serde_json::from_slice(&read_entry(&mut archive, MANIFEST)?)?;
+    check_version(&manifest)?;
+
+    let mut layers = Vec::with_capacity(manifest.layers.len());
+    for entry in manifest.layers {
+        let layer: LayerFile = serde_json::from_slice(&read_entry(&mut archive, &entry.file)?)?;
+        let mut annotations = Vec::new();
+        let mut unknown_annotations = Vec::new();
+        for value in layer.annotations {
+            match serde_json::from_value(value.clone()) {
+                Ok(annotation) => annotations.push(annotation),
+                Err(_) => unknown_annotations.push(value),
+            }
+        }
+        layers.push(AnnotationLayer {
+            id: entry.id,
+            name: entry.name,
+            visible: entry.visible,
+            annotations,
+            unknown_annotations,
+        });
+    }
+
+    Ok(ProjectContents {
+        project: GrabProject {
+            metadata: manifest.metadata,
+            crop: manifest.crop,
+            redactions: manifest.redactions,
+            layers,
+        },
+        original_png: read_entry(&mut archive, ORIGINAL)?,
+    })
+}
+
+/// Write a project file, replacing any existing one
+///
+/// The creation time of an existing project is kept. The file is written
+/// beside the target and renamed into place so a failed save never leaves
+/// a truncated project.
+pub fn write_project(path: &Path, project: &GrabProject, original: &RgbaImage) -> GrabResult<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let created_at = read_manifest(path)
+        .map(|m| m.created_at)
+        .filter(|created| !created.is_empty())
+        .unwrap_or_else(|| now.clone());
+
+    let manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: format!("{}.{}", MAJOR_VERSION, MINOR_VERSION),
+        software: metadata::software(),
+        created_at,
+        modified_at: now,
+        metadata: project.metadata.clone(),
+        crop: project.crop.clone(),
+        redactions: project.redactions.clone(),
+        layers: project
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| LayerEntry {
+                id: layer.id.clone(),
+                name: layer.name.clone(),
+                visible: layer.visible,
+                file: format!("layers/{:02}.json", index),
+            })
+            .collect(),
+    };
+
+    let flattened = flatten(project, original)?;
+    let preview = if flattened.width().max(flattened.height()) > PREVIEW_SIZE {
+        image::DynamicImage::ImageRgba8(flattened)
+            .thumbnail(PREVIEW_SIZE, PREVIEW_SIZE)
+            .to_rgba8()
+    } else {
+        flattened
+    };
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let json = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // PNG data is already compressed
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    write_entry(
+        &mut writer,
+        MANIFEST,
+        json,
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    write_entry(&mut writer, ORIGINAL, stored, &encode_png(original)?)?;
+    write_entry(&mut writer, PREVIEW, stored, &encode_png(&preview)?)?;
+    for (entry, layer) in manifest.layers.iter().zip(&project.layers) {
+        let mut annotations = serde_json::to_value(&layer.annotations)?;
+        if let Some(annotations) = annotations.as_array_mut() {
+            annotations.extend(layer.unknown_annotations.iter().cloned());
+        }
+        let file = serde_json::json!({ "annotations": annotations });
+        write_entry(&mut writer, &entry.file, json, &serde_json::to_vec(&file)?)?;
+    }
+    let bytes = writer
+        .finish()
+        .map_err(|e| GrabError::ExportFailed(e.to_string()))?
+        .into_inner();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = path.with_extension(format!("{}.tmp", PROJECT_EXTENSION));
+    fs::write(&temp, bytes)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })?;
+
+    Ok(())
+}
+
+/// Redact the original, draw the visible layers, then apply the crop
+pub fn flatten(project: &GrabProject, original: &RgbaImage) -> GrabResult<RgbaImage> {
+    let annotations: Vec<_> = project
+        .layers
+        .iter()
+        .filter(|layer| layer.visible)
+        .flat_map(|layer| layer.annotations.iter().cloned())
+        .collect();
+
+    let rendered = if project.redactions.is_empty() {
+        annotate::render(original, &annotations)?
+    } else {
+        annotate::render(&redact::apply(original, &project.redactions)?, &annotations)?
+    };
+
+    let Some(ref crop) = project.crop else {
+        return Ok(rendered);
+    };
+
+    // Clamp the crop to the image so a stale crop can't fail an export
+    let x = crop.x.clamp(0, rendered.width() as i32) as u32;
+    let y = crop.y.clamp(0, rendered.height() as i32) as u32;
+    let width = crop.width.min(rendered.width() - x);
+    let height = crop.height.min(rendered.height() - y);
+    if width == 0 || height == 0 {
+        return Err(GrabError::InvalidRequest(
+            "Crop lies outside the image".to_string(),
+        ));
+    }
+
+    Ok(imageops::crop_imm(&rendered, x, y, width, height).to_image())
+}
+
+fn check_version(manifest: &Manifest) -> GrabResult<()> {
+    if manifest.format != FORMAT {
+        return Err(GrabError::InvalidRequest(format!(
+            "Not a Grab project: {}",
+            manifest.format
+        )));
+    }
+
+    let major = manifest
+        .version
+        .split('.')
+        .next()
+        .and_then(|major| major.trim().parse::<u32>().ok());
+    match major {
+        Some(MAJOR_VERSION) => Ok(()),
+        _ => Err(GrabError::InvalidRequest(format!(
+            "Unsupported project version: {}",
+            manifest.version
+        ))),
+    }
+}
+
+fn read_manifest(path: &Path) -> Option<Manifest> {
+    let file = fs::File::open(path).ok()?;
+    let mut archive = ZipArchive::new(file).ok()?;
+    serde_json::from_slice(&read_entry(&mut archive, MANIFEST).ok()?).ok()
+}
+
+fn read_entry<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> GrabResult<Vec<u8>> {
+    let mut entry = archive.by_name(name).map_err(invalid)?;
+    let mut bytes = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_entry<W: Write + std::io::Seek>(
+    writer: &mut ZipWriter<W>,
+    name: &str,
+    options: SimpleFileOptions,
+    bytes: &[u8],
+) -> GrabResult<()> {
+    writer
+        .start_file(name, options)
+        .map_err(|e| GrabError::ExportFailed(e.to_string()))?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+fn encode_png(image: &RgbaImage) -> GrabResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)?;
+    Ok(bytes)
+}
+
+fn invalid(error: zip::result::ZipError) -> GrabError {
+    GrabError::InvalidRequest(format!("Invalid project file: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Annotation, CaptureMode, Color};
+    use image::Rgba;
+
+    fn sample_project() -> GrabProject {
+        GrabProject {
+            metadata: CaptureMetadata {
+                mode: CaptureMode::Region,
+                display_id: None,
+                window_id: None,
+                bounds: RegionBounds {
+                    x: 0,
+                    y: 0,
+                    width: 40,
+                    height: 30,
+                },
+                timestamp: "2024-05-01T10:20:30Z".to_string(),
+                scale_factor: 2.0,
+                file_name: None,
+                window_title: None,
+                app_name: None,
+                redactions: Vec::new(),
+                trim: None,
+                tags: Vec::new(),
+            },
+            crop: Some(RegionBounds {
+                x: 10,
+                y: 5,
+                width: 20,
+                height: 20,
+            }),
+            redactions: Vec::new(),
+            layers: vec![AnnotationLayer {
+                id: "arrows".to_string(),
+                name: "Arrows".to_string(),
+                visible: true,
+                annotations: vec![Annotation::Rectangle {
+                    x: 10.0,
+                    y: 5.0,
+                    width: 20.0,
+                    height: 20.0,
+                    color: Color([255, 0, 0, 255]),
+                    stroke_width: 2.0,
+                    fill: Some(Color([255, 0, 0, 255])),
+                    corner_radius: 0.0,
+                }],
+                unknown_annotations: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_project_round_trip() {
+        let dir = std::env::temp_dir().join(format!("grab-project-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("shot.grab");
+        let original = RgbaImage::from_pixel(40, 30, Rgba([0, 0, 255, 255]));
+        let project = sample_project();
+
+        write_project(&path, &project, &original).unwrap();
+        let contents = read_project(&path).unwrap();
+
+        assert_eq!(contents.original().unwrap(), original);
+        assert_eq!(contents.project.layers, project.layers);
+        assert_eq!(contents.project.crop.as_ref().map(|c| c.width), Some(20));
+
+        let flattened = flatten(&contents.project, &original).unwrap();
+        assert_eq!(flattened.dimensions(), (20, 20));
+        assert_eq!(flattened.get_pixel(10, 10).0, [255, 0, 0, 255]);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_reader_tolerates_newer_minor_version() {
+        let dir = std::env::temp_dir().join(format!("grab-project-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let original = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]));
+
+        let write = |version: &str| {
+            let path = dir.join(format!("v{}.grab", version));
+            let mut manifest = serde_json::to_value(Manifest {
+                format: FORMAT.to_string(),
+                version: version.to_string(),
+                software: String::new(),
+                created_at: String::new(),
+                modified_at: String::new(),
+                metadata: sample_project().metadata,
+                crop: None,
+                redactions: Vec::new(),
+                layers: vec![LayerEntry {
+                    id: "a".to_string(),
+                    name: "A".to_string(),
+                    visible: true,
+                    file: "layers/00.json".to_string(),
+                }],
+            })
+            .unwrap();
+            manifest["futureField"] = serde_json::json!({ "nested": true });
+            let layer = serde_json::json!({ "annotations": [
+                { "type": "hologram", "depth": 3 },
+                { "type": "step", "x": 1.0, "y": 1.0, "number": 1, "color": "#ff0000", "radius": 8.0 },
+            ]});
+
+            let mut writer = ZipWriter::new(fs::File::create(&path).unwrap());
+            let options = SimpleFileOptions::default();
+            write_entry(
+                &mut writer,
+                MANIFEST,
+                options,
+                manifest.to_string().as_bytes(),
+            )
+            .unwrap();
+            write_entry(
+                &mut writer,
+                ORIGINAL,
+                options,
+                &encode_png(&original).unwrap(),
+            )
+            .unwrap();
+            write_entry(
+                &mut writer,
+                "layers/00.json",
+                options,
+                layer.to_string().as_bytes(),
+            )
+            .unwrap();
+            writer.finish().unwrap();
+            read_project(&path)
+        };
+
+        let contents = write("1.7").unwrap();
+        assert_eq!(contents.project.layers[0].annotations.len(), 1);
+        assert!(matches!(
+            contents.project.layers[0].annotations[0],
+            Annotation::Step { number: 1, .. }
+        ));
+        assert!(write("2.0").is_err());
+
+        // Annotations this version can't draw survive a save
+        let resaved = dir.join("resaved.grab");
+        write_project(&resaved, &contents.project, &original).unwrap();
+        let layers = read_project(&resaved).unwrap().project.layers;
+        assert_eq!(layers, contents.project.layers);
+        assert_eq!(
+            layers[0].unknown_annotations,
+            [serde_json::json!({ "type": "hologram", "depth": 3 })]
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,274 @@
+//! Destructive redaction
+//!
+//! Unlike the editor's canvas effects, redactions replace the pixels of the
+//! image buffer itself, before anything is encoded. Every redaction is then
+//! checked against the original: if a region still carries enough of the
+//! original detail to be reconstructed (a light blur, say), the export is
+//! refused rather than leaking the content.
+
+use crate::annotate;
+use crate::error::{GrabError, GrabResult};
+use crate::types::{CaptureMetadata, Redaction, RedactionMethod, RegionBounds};
+use image::{imageops, Rgba, RgbaImage};
+
+/// Smallest mosaic block; smaller blocks can be brute-forced back to text
+pub const MIN_BLOCK_SIZE: u32 = 8;
+
+/// Highest correlation allowed between the original and redacted detail
+const MAX_RETAINED_DETAIL: f64 = 0.2;
+
+/// Regions with less detail than this had nothing to hide
+const MIN_SOURCE_DETAIL: f64 = 4.0;
+
+/// Apply redactions to a copy of the image and verify them
+pub fn apply(image: &RgbaImage, redactions: &[Redaction]) -> GrabResult<RgbaImage> {
+    let mut redacted = image.clone();
+
+    for redaction in redactions {
+        let Some((x, y, width, height)) = clip(&redaction.bounds, image) else {
+            continue;
+        };
+        let mut region = imageops::crop_imm(&redacted, x, y, width, height).to_image();
+
+        region = match redaction.method {
+            RedactionMethod::Blur { radius } => imageops::blur(&region, radius.max(0.0)),
+            RedactionMethod::Pixelate { block_size } => {
+                annotate::pixelate(&mut region, block_size.max(MIN_BLOCK_SIZE));
+                region
+            }
+            // Always opaque, so nothing shows through a translucent colour
+            RedactionMethod::Fill { color } => {
+                let [r, g, b, _] = color.0;
+                RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]))
+            }
+        };
+
+        imageops::replace(&mut redacted, &region, x as i64, y as i64);
+    }
+
+    verify(image, &redacted, redactions)?;
+    Ok(redacted)
+}
+
+/// Refuse redactions whose region still correlates with the original detail
+pub fn verify(
+    original: &RgbaImage,
+    redacted: &RgbaImage,
+    redactions: &[Redaction],
+) -> GrabResult<()> {
+    for redaction in redactions {
+        let Some(rect) = clip(&redaction.bounds, original) else {
+            continue;
+        };
+
+        let risk = retained_detail(original, redacted, rect);
+        if risk > MAX_RETAINED_DETAIL {
+            let bounds = &redaction.bounds;
+            return Err(GrabError::ExportFailed(format!(
+                "Redaction at {},{} ({}x{}) could be reversed; use a stronger blur, larger blocks or a fill",
+                bounds.x, bounds.y, bounds.width, bounds.height
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy of the metadata with the redacted regions recorded
+///
+/// Regions are in the coordinates of the image they were applied to.
+pub fn record(metadata: &CaptureMetadata, redactions: &[Redaction]) -> CaptureMetadata {
+    let mut metadata = metadata.clone();
+    metadata.redactions.extend(redactions.iter().cloned());
+    metadata
+}
+
+/// Metadata for an export of a redacted image
+///
+/// The source capture's, or what `fallback` makes for an image without any
+/// (the editor's data URLs, say), with the redactions recorded.
+pub fn export_metadata(
+    source: Option<CaptureMetadata>,
+    fallback: impl FnOnce() -> CaptureMetadata,
+    redactions: &[Redaction],
+) -> CaptureMetadata {
+    record(&source.unwrap_or_else(fallback), redactions)
+}
+
+/// Intersect a region with the image, returning `(x, y, width, height)`
+fn clip(bounds: &RegionBounds, image: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let left = bounds.x.max(0) as i64;
+    let top = bounds.y.max(0) as i64;
+    let right = (bounds.x as i64 + bounds.width as i64).min(image.width() as i64);
+    let bottom = (bounds.y as i64 + bounds.height as i64).min(image.height() as i64);
+
+    if right <= left || bottom <= top {
+        return None;
+    }
+    Some((
+        left as u32,
+        top as u32,
+        (right - left) as u32,
+        (bottom - top) as u32,
+    ))
+}
+
+/// How much of the original's fine detail survives in a region, from 0 to 1
+///
+/// Correlates the luminance gradients of both images. Solid fills, strong
+/// blurs and coarse mosaics leave little of the original edge structure;
+/// light blurs keep most of it.
+fn retained_detail(original: &RgbaImage, redacted: &RgbaImage, rect: (u32, u32, u32, u32)) -> f64 {
+    let a = gradients(original, rect);
+    let b = gradients(redacted, rect);
+    if a.len() < 2 {
+        return 0.0;
+    }
+
+    let n = a.len() as f64;
+    let mean_a = a.iter().sum::<f64>() / n;
+    let mean_b = b.iter().sum::<f64>() / n;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (a, b) in a.iter().zip(&b) {
+        cov += (a - mean_a) * (b - mean_b);
+        var_a += (a - mean_a).powi(2);
+        var_b += (b - mean_b).powi(2);
+    }
+
+    // A flat source has nothing to leak; a flat result leaks nothing
+    if var_a / n < MIN_SOURCE_DETAIL || var_b == 0.0 {
+        return 0.0;
+    }
+    (cov / (var_a * var_b).sqrt()).max(0.0)
+}
+
+/// Horizontal and vertical luminance differences inside a region
+fn gradients(image: &RgbaImage, (x, y, width, height): (u32, u32, u32, u32)) -> Vec<f64> {
+    let luma = |px: u32, py: u32| {
+        let [r, g, b, _] = image.get_pixel(px, py).0;
+        0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64
+    };
+
+    let mut values = Vec::with_capacity(2 * width as usize * height as usize);
+    for py in y..y + height {
+        for px in x..x + width {
+            let here = luma(px, py);
+            if px + 1 < x + width {
+                values.push(luma(px + 1, py) - here);
+            }
+            if py + 1 < y + height {
+                values.push(luma(px, py + 1) - here);
+            }
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Annotation, Color};
+
+    fn text_image() -> RgbaImage {
+        let background = RgbaImage::from_pixel(240, 40, Rgba([255, 255, 255, 255]));
+        annotate::render(
+            &background,
+            &[Annotation::Text {
+                x: 4.0,
+                y: 4.0,
+                text: "sk-live-4f9a2c7e1b".to_string(),
+                color: Color([0, 0, 0, 255]),
+                font_size: 24.0,
+                bold: false,
+                background: None,
+            }],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_redaction_replaces_pixels_and_rejects_weak_blur() {
+        let image = text_image();
+        let redaction = |method| Redaction {
+            bounds: RegionBounds {
+                x: 0,
+                y: 0,
+                width: 240,
+                height: 40,
+            },
+            method,
+        };
+
+        let weak = apply(&image, &[redaction(RedactionMethod::Blur { radius: 1.0 })]);
+        assert!(weak.is_err());
+        assert!(apply(&image, &[redaction(RedactionMethod::Blur { radius: 8.0 })]).is_ok());
+
+        // Tiny blocks are raised to the minimum
+        let mosaic = apply(
+            &image,
+            &[redaction(RedactionMethod::Pixelate { block_size: 2 })],
+        )
+        .unwrap();
+        assert_eq!(
+            mosaic.get_pixel(0, 0),
+            mosaic.get_pixel(MIN_BLOCK_SIZE - 1, MIN_BLOCK_SIZE - 1)
+        );
+
+        let fill = RedactionMethod::Fill {
+            color: Color([255, 0, 0, 0]),
+        };
+        let filled = apply(&image, &[redaction(fill)]).unwrap();
+        assert!(filled.pixels().all(|p| p.0 == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_export_without_source_metadata_records_redactions() {
+        let redactions = [Redaction {
+            bounds: RegionBounds {
+                x: 0,
+                y: 0,
+                width: 240,
+                height: 40,
+            },
+            method: RedactionMethod::Pixelate { block_size: 12 },
+        }];
+        let redacted = apply(&text_image(), &redactions).unwrap();
+        let fallback = || CaptureMetadata {
+            mode: crate::types::CaptureMode::Region,
+            display_id: None,
+            window_id: None,
+            bounds: redactions[0].bounds.clone(),
+            timestamp: "2024-03-01T09:00:00Z".to_string(),
+            scale_factor: 1.0,
+            file_name: None,
+            window_title: None,
+            app_name: None,
+            redactions: Vec::new(),
+            trim: None,
+            tags: Vec::new(),
+        };
+        let metadata = export_metadata(None, fallback, &redactions);
+
+        let path = std::env::temp_dir().join(format!("grab-redact-{}.png", uuid::Uuid::new_v4()));
+        crate::export::export_to_file(
+            &image::DynamicImage::ImageRgba8(redacted),
+            &Default::default(),
+            Some(&metadata),
+            1.0,
+            &path,
+        )
+        .unwrap();
+        let read = crate::metadata::read_from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(read.redactions, redactions);
+
+        // Source metadata wins over the fallback
+        let source = CaptureMetadata {
+            window_title: Some("Terminal".to_string()),
+            ..fallback()
+        };
+        let metadata = export_metadata(Some(source), || unreachable!(), &redactions);
+        assert_eq!(metadata.window_title.as_deref(), Some("Terminal"));
+        assert_eq!(metadata.redactions.len(), 1);
+    }
+}
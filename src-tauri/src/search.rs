@@ -0,0 +1,255 @@
+//! Full-text search of captures
+//!
+//! The library keeps an SQLite FTS5 index over file names, window titles,
+//! app names, tags and the text tesseract reads from each capture. Titles,
+//! names and tags are indexed as they're recorded; reading the text takes a
+//! while, so a worker thread does it after the capture is saved and catches
+//! up on older captures in the background. Nothing leaves the machine.
+
+use crate::error::GrabResult;
+use crate::history::HistoryStore;
+use crate::ocr::{self, OcrCache, OcrEngine};
+use crate::types::{OcrSettings, SnippetPart};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+use tauri::{AppHandle, Manager};
+
+/// Marks around the matches in snippets from the index
+pub const HIGHLIGHT_START: char = '\u{e000}';
+pub const HIGHLIGHT_END: char = '\u{e001}';
+
+enum IndexJob {
+    /// Read the text of a new capture
+    Capture {
+        path: PathBuf,
+        settings: OcrSettings,
+    },
+    /// Read the text of every capture that hasn't been read yet
+    Backfill(OcrSettings),
+}
+
+/// Worker that reads the text of captures into the search index
+pub struct SearchIndexer {
+    sender: Mutex<Sender<IndexJob>>,
+}
+
+impl SearchIndexer {
+    /// Spawn the worker thread
+    pub fn new(app_handle: &AppHandle) -> GrabResult<Self> {
+        let (sender, receiver) = mpsc::channel::<IndexJob>();
+        let handle = app_handle.clone();
+
+        thread::Builder::new()
+            .name("grab-index".to_string())
+            .spawn(move || {
+                for job in receiver {
+                    let history = handle.state::<HistoryStore>();
+                    let cache = handle.state::<OcrCache>();
+                    match job {
+                        IndexJob::Capture { path, settings } => {
+                            // Left unread on failure, for the next backfill
+                            if let Err(e) = index_capture(&history, &cache, &path, &settings) {
+                                eprintln!("Indexing failed for {}: {}", path.display(), e);
+                            }
+                        }
+                        IndexJob::Backfill(settings) => backfill(&history, &cache, &settings),
+                    }
+                }
+            })?;
+
+        Ok(SearchIndexer {
+            sender: Mutex::new(sender),
+        })
+    }
+
+    /// Queue a saved capture for indexing
+    pub fn queue(&self, path: PathBuf, settings: OcrSettings) {
+        self.send(IndexJob::Capture { path, settings });
+    }
+
+    /// Queue the captures whose text hasn't been read yet
+    pub fn queue_unindexed(&self, settings: OcrSettings) {
+        self.send(IndexJob::Backfill(settings));
+    }
+
+    fn send(&self, job: IndexJob) {
+        if self.sender.lock().unwrap().send(job).is_err() {
+            eprintln!("Search indexer is not running");
+        }
+    }
+}
+
+/// Read a capture's text into the library, through the OCR cache
+fn index_capture(
+    history: &HistoryStore,
+    cache: &OcrCache,
+    path: &Path,
+    settings: &OcrSettings,
+) -> GrabResult<()> {
+    let engine = ocr::Tesseract::new(settings);
+    let bytes = fs::read(path)?;
+    let key = OcrCache::key(&bytes, engine.name(), &settings.languages, None);
+    let text = match cache.get(&key) {
+        Some(result) => result.text,
+        None => {
+            let image = image::load_from_memory(&bytes)?.to_rgba8();
+            let result = engine.recognize(&image, &settings.languages)?;
+            if let Err(e) = cache.put(&key, &result) {
+                eprintln!("Failed to cache OCR result: {}", e);
+            }
+            result.text
+        }
+    };
+    history.set_ocr_text(&path.to_string_lossy(), &text)?;
+    Ok(())
+}
+
+fn backfill(history: &HistoryStore, cache: &OcrCache, settings: &OcrSettings) {
+    // Without tesseract every capture would fail the same way
+    if let Err(e) = ocr::Tesseract::new(settings).languages() {
+        eprintln!("Skipping text indexing: {}", e);
+        return;
+    }
+
+    let paths = match history.unindexed() {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("Failed to list captures to index: {}", e);
+            return;
+        }
+    };
+    for path in paths {
+        if let Err(e) = index_capture(history, cache, Path::new(&path), settings) {
+            eprintln!("Indexing failed for {}: {}", path, e);
+            // An image tesseract can't read won't do better next time
+            if let Err(e) = history.set_ocr_text(&path, "") {
+                eprintln!("Failed to record indexing of {}: {}", path, e);
+            }
+        }
+    }
+}
+
+/// FTS5 expression for a search query, or `None` if it has no terms
+///
+/// Every term must match. Terms are quoted, so FTS5 syntax in the query is
+/// taken literally: `"stack trace"` is a phrase, and a trailing `*` on a
+/// word or phrase matches by prefix.
+pub fn match_expression(query: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut rest = query.trim_start();
+    while !rest.is_empty() {
+        let (term, mut remaining) = match rest.strip_prefix('"') {
+            Some(phrase) => match phrase.find('"') {
+                Some(end) => (&phrase[..end], &phrase[end + 1..]),
+                None => (phrase, ""),
+            },
+            None => {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        let (term, mut prefix) = match term.strip_suffix('*') {
+            Some(word) => (word, true),
+            None => (term, false),
+        };
+        if let Some(after) = remaining.strip_prefix('*') {
+            prefix = true;
+            remaining = after;
+        }
+        terms.extend(quote(term, prefix));
+        rest = remaining.trim_start();
+    }
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// A term as an FTS5 string, or `None` if it has nothing to match
+fn quote(term: &str, prefix: bool) -> Option<String> {
+    // The tokenizer drops punctuation, so a term of it alone matches nothing
+    if !term.chars().any(char::is_alphanumeric) {
+        return None;
+    }
+    let quoted = format!("\"{}\"", term.replace('"', "\"\""));
+    Some(if prefix { quoted + "*" } else { quoted })
+}
+
+/// Split a snippet from the index into plain and highlighted runs
+pub fn snippet_parts(snippet: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut push = |text: &mut String, highlight: bool| {
+        if !text.is_empty() {
+            parts.push(SnippetPart {
+                text: std::mem::take(text),
+                highlight,
+            });
+        }
+    };
+    for c in snippet.chars() {
+        match c {
+            HIGHLIGHT_START => push(&mut text, false),
+            HIGHLIGHT_END => push(&mut text, true),
+            c => text.push(c),
+        }
+    }
+    push(&mut text, false);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_expression() {
+        assert_eq!(
+            match_expression("stack trace").as_deref(),
+            Some("\"stack\" \"trace\"")
+        );
+        assert_eq!(
+            match_expression("  \"null pointer\" excep*").as_deref(),
+            Some("\"null pointer\" \"excep\"*")
+        );
+        assert_eq!(
+            match_expression("\"Null Poi\"* tuesday").as_deref(),
+            Some("\"Null Poi\"* \"tuesday\"")
+        );
+        // FTS5 syntax is matched as text
+        assert_eq!(
+            match_expression("NOT a\"b OR").as_deref(),
+            Some("\"NOT\" \"a\"\"b\" \"OR\"")
+        );
+        assert_eq!(
+            match_expression("\"unclosed phrase").as_deref(),
+            Some("\"unclosed phrase\"")
+        );
+        assert_eq!(match_expression(" - * \"\" "), None);
+        assert_eq!(match_expression(""), None);
+    }
+
+    #[test]
+    fn test_snippet_parts() {
+        let snippet = format!(
+            "…at {}Parser{}.parse ({}parser{}.rs:42)",
+            HIGHLIGHT_START, HIGHLIGHT_END, HIGHLIGHT_START, HIGHLIGHT_END
+        );
+        let parts = snippet_parts(&snippet);
+        let runs: Vec<(&str, bool)> = parts
+            .iter()
+            .map(|p| (p.text.as_str(), p.highlight))
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                ("…at ", false),
+                ("Parser", true),
+                (".parse (", false),
+                ("parser", true),
+                (".rs:42)", false)
+            ]
+        );
+        assert!(snippet_parts("").is_empty());
+    }
+}
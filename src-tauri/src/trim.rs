@@ -0,0 +1,289 @@
+//! Automatic trimming of uniform margins
+//!
+//! Window captures often come with a border and region selections with
+//! extra whitespace. Rows and columns of one colour are peeled off each side
+//! in turn. A thin border may be followed by a margin of another colour,
+//! but no further layers, so uniform content isn't mistaken for margin.
+
+use crate::types::{CaptureMetadata, RegionBounds, TrimOptions};
+use image::{imageops, Rgba, RgbaImage};
+
+/// Thickest border, in logical pixels, that may have a margin inside it
+const BORDER_WIDTH: f64 = 4.0;
+
+/// Crop uniform margins off an image
+///
+/// Returns the cropped image and the area kept, in the original's pixels,
+/// or `None` when there is nothing to trim. An image of one colour is left
+/// alone.
+pub fn trim(
+    image: &RgbaImage,
+    options: &TrimOptions,
+    scale_factor: f64,
+) -> Option<(RgbaImage, RegionBounds)> {
+    let bounds = find_content(image, options, scale_factor)?;
+    let cropped = imageops::crop_imm(
+        image,
+        bounds.x as u32,
+        bounds.y as u32,
+        bounds.width,
+        bounds.height,
+    )
+    .to_image();
+    Some((cropped, bounds))
+}
+
+/// The area inside uniform margins, or `None` if it's the whole image
+pub fn find_content(
+    image: &RgbaImage,
+    options: &TrimOptions,
+    scale_factor: f64,
+) -> Option<RegionBounds> {
+    let (width, height) = image.dimensions();
+    // A blank image has no content to trim down to
+    if width == 0 || height == 0 || uniform(image.pixels(), options.tolerance).is_some() {
+        return None;
+    }
+
+    let scale = if scale_factor.is_finite() && scale_factor > 0.0 {
+        scale_factor
+    } else {
+        1.0
+    };
+
+    // Furthest each side may move in
+    let limit = options.max_percent.clamp(0.0, 100.0) as f64 / 100.0;
+    let max_x = (width as f64 * limit) as u32;
+    let max_y = (height as f64 * limit) as u32;
+
+    let tolerance = options.tolerance;
+    let border = (BORDER_WIDTH * scale).round() as u32;
+    let row = |y: u32, left: u32, right: u32| {
+        uniform((left..right).map(|x| image.get_pixel(x, y)), tolerance)
+    };
+    let column = |x: u32, top: u32, bottom: u32| {
+        uniform((top..bottom).map(|y| image.get_pixel(x, y)), tolerance)
+    };
+
+    // Exclusive right and bottom edges
+    let (mut left, mut top, mut right, mut bottom) = (0, 0, width, height);
+    let mut sides = [Side::default(); 4];
+    loop {
+        let before = (left, top, right, bottom);
+        while top < max_y
+            && bottom - top > 1
+            && sides[0].peel(row(top, left, right), tolerance, border)
+        {
+            top += 1;
+        }
+        while height - bottom < max_y
+            && bottom - top > 1
+            && sides[1].peel(row(bottom - 1, left, right), tolerance, border)
+        {
+            bottom -= 1;
+        }
+        while left < max_x
+            && right - left > 1
+            && sides[2].peel(column(left, top, bottom), tolerance, border)
+        {
+            left += 1;
+        }
+        while width - right < max_x
+            && right - left > 1
+            && sides[3].peel(column(right - 1, top, bottom), tolerance, border)
+        {
+            right -= 1;
+        }
+        // Trimming a border can let the other sides get further
+        if (left, top, right, bottom) == before {
+            break;
+        }
+    }
+
+    let padding = (options.padding as f64 * scale).round() as u32;
+    let left = left.saturating_sub(padding);
+    let top = top.saturating_sub(padding);
+    let right = (right + padding).min(width);
+    let bottom = (bottom + padding).min(height);
+
+    if (left, top, right, bottom) == (0, 0, width, height) {
+        return None;
+    }
+    Some(RegionBounds {
+        x: left as i32,
+        y: top as i32,
+        width: right - left,
+        height: bottom - top,
+    })
+}
+
+/// Metadata for a trimmed capture, recording the area kept
+///
+/// Trimming an already trimmed capture keeps the offset relative to the
+/// original.
+pub fn record(metadata: &CaptureMetadata, bounds: &RegionBounds) -> CaptureMetadata {
+    let (x, y) = match metadata.trim {
+        Some(ref previous) => (previous.x + bounds.x, previous.y + bounds.y),
+        None => (bounds.x, bounds.y),
+    };
+    CaptureMetadata {
+        trim: Some(RegionBounds {
+            x,
+            y,
+            width: bounds.width,
+            height: bounds.height,
+        }),
+        ..metadata.clone()
+    }
+}
+
+/// Progress trimming one side
+#[derive(Clone, Copy, Default)]
+struct Side {
+    /// Colour of the layer being trimmed
+    color: Option<Rgba<u8>>,
+    layers: u32,
+    /// Lines trimmed in this layer
+    depth: u32,
+}
+
+impl Side {
+    /// Whether the next line, given its colour if uniform, can go
+    fn peel(&mut self, line: Option<Rgba<u8>>, tolerance: u8, border: u32) -> bool {
+        let Some(line) = line else {
+            return false;
+        };
+        match self.color {
+            Some(ref color) if similar(&line, color, tolerance) => self.depth += 1,
+            // A margin inside a thin border
+            Some(_) if self.layers == 1 && self.depth <= border => {
+                *self = Side {
+                    color: Some(line),
+                    layers: 2,
+                    depth: 1,
+                };
+            }
+            Some(_) => return false,
+            None => {
+                *self = Side {
+                    color: Some(line),
+                    layers: 1,
+                    depth: 1,
+                };
+            }
+        }
+        true
+    }
+}
+
+/// The colour of the pixels if all are within `tolerance` of the first
+fn uniform<'a>(mut pixels: impl Iterator<Item = &'a Rgba<u8>>, tolerance: u8) -> Option<Rgba<u8>> {
+    let first = *pixels.next()?;
+    pixels
+        .all(|p| similar(p, &first, tolerance))
+        .then_some(first)
+}
+
+fn similar(a: &Rgba<u8>, b: &Rgba<u8>, tolerance: u8) -> bool {
+    // Transparent pixels match whatever colour they carry
+    if a.0[3] == 0 && b.0[3] == 0 {
+        return true;
+    }
+    a.0.iter()
+        .zip(b.0.iter())
+        .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+        for y in y..y + height {
+            for x in x..x + width {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    #[test]
+    fn test_trim_border_and_margin() {
+        // A 1px grey border around white space around the content
+        let mut image = RgbaImage::from_pixel(100, 80, Rgba([128, 128, 128, 255]));
+        fill(&mut image, 1, 1, 98, 78, Rgba([255, 255, 255, 255]));
+        fill(&mut image, 30, 20, 40, 30, Rgba([0, 90, 200, 255]));
+        // Slight noise in the margin stays within the tolerance
+        image.put_pixel(10, 10, Rgba([250, 252, 255, 255]));
+
+        let options = TrimOptions {
+            max_percent: 50.0,
+            ..Default::default()
+        };
+        let (cropped, bounds) = trim(&image, &options, 1.0).unwrap();
+        assert_eq!(
+            bounds,
+            RegionBounds {
+                x: 30,
+                y: 20,
+                width: 40,
+                height: 30
+            }
+        );
+        assert_eq!(cropped.dimensions(), (40, 30));
+
+        // Padding is in logical pixels
+        let padded = find_content(
+            &image,
+            &TrimOptions {
+                padding: 5,
+                ..options.clone()
+            },
+            2.0,
+        )
+        .unwrap();
+        assert_eq!((padded.x, padded.y, padded.width), (20, 10, 60));
+
+        // The bound stops it short of the content
+        let bounded = find_content(&image, &TrimOptions::default(), 1.0).unwrap();
+        assert_eq!((bounded.x, bounded.y), (25, 20));
+        assert_eq!((bounded.width, bounded.height), (50, 40));
+
+        assert!(find_content(
+            &RgbaImage::from_pixel(20, 20, Rgba([9, 9, 9, 255])),
+            &options,
+            1.0
+        )
+        .is_none());
+        assert!(find_content(&cropped, &options, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_record_accumulates_offsets() {
+        let metadata: CaptureMetadata = serde_json::from_value(serde_json::json!({
+            "mode": "region",
+            "bounds": { "x": 0, "y": 0, "width": 100, "height": 80 },
+            "timestamp": "2024-01-01T00:00:00Z",
+            "scaleFactor": 1.0,
+        }))
+        .unwrap();
+        let bounds = RegionBounds {
+            x: 10,
+            y: 5,
+            width: 50,
+            height: 40,
+        };
+
+        let once = record(&metadata, &bounds);
+        assert_eq!(once.trim, Some(bounds.clone()));
+        let twice = record(&once, &bounds);
+        assert_eq!(
+            twice.trim,
+            Some(RegionBounds {
+                x: 20,
+                y: 10,
+                width: 50,
+                height: 40
+            })
+        );
+    }
+}
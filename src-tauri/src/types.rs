@@ -2,7 +2,8 @@
 //!
 //! These types mirror the TypeScript types from the original Electron app.
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
 
 /// Supported capture modes
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -12,6 +13,8 @@ pub enum CaptureMode {
     Display,
     Window,
     Region,
+    /// Several captures laid out together
+    Composite,
 }
 
 impl Default for CaptureMode {
@@ -21,7 +24,7 @@ impl Default for CaptureMode {
 }
 
 /// Region bounds for capture
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RegionBounds {
     pub x: i32,
     pub y: i32,
@@ -64,6 +67,627 @@ pub struct CaptureMetadata {
     pub scale_factor: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    /// Regions destructively redacted before the file was written
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redactions: Vec<Redaction>,
+    /// Area kept when uniform margins were trimmed, in the untrimmed
+    /// capture's pixels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim: Option<RegionBounds>,
+    /// User tags, also written as the XMP `dc:subject`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// How a redacted region is obscured
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case", rename_all_fields = "camelCase")]
+pub enum RedactionMethod {
+    /// Gaussian blur with the given sigma
+    Blur { radius: f32 },
+    /// Mosaic of averaged blocks
+    Pixelate { block_size: u32 },
+    /// Solid colour
+    Fill {
+        #[serde(default = "default_redaction_color")]
+        color: Color,
+    },
+}
+
+fn default_redaction_color() -> Color {
+    Color([0, 0, 0, 255])
+}
+
+/// A region whose pixels are replaced before encoding
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Redaction {
+    pub bounds: RegionBounds,
+    pub method: RedactionMethod,
+}
+
+/// A recognized word and where it is in the image
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrWord {
+    pub text: String,
+    pub bounds: RegionBounds,
+    /// 0-100, as reported by the engine
+    pub confidence: f32,
+    /// Index of the text line the word belongs to
+    pub line: u32,
+}
+
+/// A line of recognized text
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrLine {
+    pub text: String,
+    pub bounds: RegionBounds,
+    /// Mean confidence of the line's words
+    pub confidence: f32,
+    pub words: Vec<OcrWord>,
+}
+
+/// Text recognized in an image, in reading order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrResult {
+    /// Lines joined with newlines
+    pub text: String,
+    pub lines: Vec<OcrLine>,
+    pub engine: String,
+    pub languages: Vec<String>,
+}
+
+/// Settings for text recognition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrSettings {
+    /// Tesseract language codes, e.g. `eng` or `deu`
+    #[serde(default = "default_ocr_languages")]
+    pub languages: Vec<String>,
+    /// Path to the tesseract executable; found on `PATH` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tesseract_path: Option<String>,
+    /// Read the text of captures in the background so search can find it
+    #[serde(default = "default_true")]
+    pub index_captures: bool,
+}
+
+impl Default for OcrSettings {
+    fn default() -> Self {
+        OcrSettings {
+            languages: default_ocr_languages(),
+            tesseract_path: None,
+            index_captures: true,
+        }
+    }
+}
+
+fn default_ocr_languages() -> Vec<String> {
+    vec!["eng".to_string()]
+}
+
+/// Recognize text in a history item or image file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrRequest {
+    #[serde(default)]
+    pub item_id: Option<String>,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    /// Only read this part of the image; boxes stay in image coordinates
+    #[serde(default)]
+    pub region: Option<RegionBounds>,
+    /// Overrides the configured languages
+    #[serde(default)]
+    pub languages: Option<Vec<String>>,
+}
+
+/// A QR code or barcode found in a capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedCode {
+    /// Kebab-case format name, e.g. `qr-code`, `data-matrix` or `ean-13`
+    pub format: String,
+    pub text: String,
+    /// Points located by the decoder, in image coordinates: finder patterns
+    /// for 2D codes, the ends of the scan line for 1D barcodes
+    pub points: Vec<Point>,
+    pub bounds: RegionBounds,
+}
+
+/// Decode the codes in a history item or image file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeScanRequest {
+    #[serde(default)]
+    pub item_id: Option<String>,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    /// Only scan this part of the image; positions stay in image coordinates
+    #[serde(default)]
+    pub region: Option<RegionBounds>,
+}
+
+/// Codes found in a new capture, offered for copying
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedCodes {
+    pub file_path: Option<String>,
+    pub codes: Vec<DecodedCode>,
+}
+
+/// Text format for a colour
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorFormat {
+    Hex,
+    Rgb,
+    Hsl,
+    Oklch,
+}
+
+/// A colour written out in each format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorValue {
+    /// e.g. `#3b82f6`
+    pub hex: String,
+    /// e.g. `rgb(59, 130, 246)`
+    pub rgb: String,
+    /// e.g. `hsl(217, 91%, 60%)`
+    pub hsl: String,
+    /// e.g. `oklch(62.3% 0.188 259.8)`
+    pub oklch: String,
+}
+
+impl ColorValue {
+    /// The colour written in one format
+    pub fn get(&self, format: ColorFormat) -> &str {
+        match format {
+            ColorFormat::Hex => &self.hex,
+            ColorFormat::Rgb => &self.rgb,
+            ColorFormat::Hsl => &self.hsl,
+            ColorFormat::Oklch => &self.oklch,
+        }
+    }
+}
+
+/// Read the colour at a point on screen or in a capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorPickRequest {
+    /// Global screen coordinates, or image pixels when picking from a
+    /// history item or file
+    pub x: i32,
+    pub y: i32,
+    #[serde(default)]
+    pub item_id: Option<String>,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    /// Pixels on each side of the point to include in the magnifier grid
+    #[serde(default)]
+    pub radius: u32,
+    /// Copy the colour to the clipboard in this format
+    #[serde(default)]
+    pub copy: Option<ColorFormat>,
+}
+
+/// The colour at a point and the pixels around it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorSample {
+    /// Pixel that was read, in image coordinates
+    pub x: u32,
+    pub y: u32,
+    pub color: ColorValue,
+    /// Side length of the grid, `2 * radius + 1`
+    pub grid_size: u32,
+    /// Row-major pixels around the point; `None` outside the image
+    pub grid: Vec<Option<Color>>,
+}
+
+/// Find the dominant colours of a capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteRequest {
+    #[serde(default)]
+    pub item_id: Option<String>,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    #[serde(default = "default_palette_size")]
+    pub count: usize,
+    /// Copy the palette to the clipboard in this format, one colour per line
+    #[serde(default)]
+    pub copy: Option<ColorFormat>,
+}
+
+fn default_palette_size() -> usize {
+    6
+}
+
+/// One colour of a palette
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteColor {
+    pub color: ColorValue,
+    /// Share of the image's pixels, from 0 to 100
+    pub percentage: f32,
+}
+
+/// Find the colour boundaries around a point in a capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeProbeRequest {
+    #[serde(default)]
+    pub item_id: Option<String>,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    /// Point in image pixels
+    pub x: u32,
+    pub y: u32,
+    /// Largest per-channel difference still counted as the same colour
+    #[serde(default = "default_edge_tolerance")]
+    pub tolerance: u8,
+    /// Overrides the scale factor recorded in the capture
+    #[serde(default)]
+    pub scale_factor: Option<f64>,
+}
+
+fn default_edge_tolerance() -> u8 {
+    16
+}
+
+/// Measure between two points in a capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DistanceRequest {
+    #[serde(default)]
+    pub item_id: Option<String>,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    /// Points in image pixels
+    pub from: Point,
+    pub to: Point,
+    /// Overrides the scale factor recorded in the capture
+    #[serde(default)]
+    pub scale_factor: Option<f64>,
+}
+
+/// The area of uniform colour around a point, bounded by the first colour
+/// change in each direction
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ElementMeasurement {
+    /// The probed point
+    pub x: u32,
+    pub y: u32,
+    /// In physical (image) pixels
+    pub bounds: RegionBounds,
+    pub scale_factor: f64,
+    pub logical_width: f64,
+    pub logical_height: f64,
+}
+
+/// Distance between two points, in physical and logical pixels
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DistanceMeasurement {
+    pub from: Point,
+    pub to: Point,
+    pub dx: f64,
+    pub dy: f64,
+    pub distance: f64,
+    pub scale_factor: f64,
+    pub logical_dx: f64,
+    pub logical_dy: f64,
+    pub logical_distance: f64,
+}
+
+/// A measurement that can be turned into annotations
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Measurement {
+    Element(ElementMeasurement),
+    Distance(DistanceMeasurement),
+}
+
+/// What to do with a capture using an AI model
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmartAction {
+    Ocr,
+    CleanFormat,
+    Translate,
+    Summarize,
+    MarkdownNotes,
+    BugReport,
+}
+
+/// Azure OpenAI connection; unset fields fall back to `AZURE_OPENAI_*`
+/// environment variables
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AzureOpenAiSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+}
+
+/// Server that answers smart actions
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum AiProvider {
+    #[default]
+    Azure,
+    /// Any server speaking the OpenAI chat-completions API, e.g. Ollama or
+    /// llama.cpp running locally
+    #[serde(rename = "openai-compatible")]
+    OpenAiCompatible,
+}
+
+/// A generic OpenAI-compatible server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenAiCompatibleSettings {
+    /// Base URL including the API prefix, e.g. `http://localhost:11434/v1`
+    #[serde(default = "default_openai_base_url")]
+    pub base_url: String,
+    #[serde(default)]
+    pub model: String,
+    /// Sent as a bearer token when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Whether the model accepts images; asked from the server when unset.
+    /// Text-only models get the locally recognized text instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vision: Option<bool>,
+}
+
+impl Default for OpenAiCompatibleSettings {
+    fn default() -> Self {
+        OpenAiCompatibleSettings {
+            base_url: default_openai_base_url(),
+            model: String::new(),
+            api_key: None,
+            vision: None,
+        }
+    }
+}
+
+fn default_openai_base_url() -> String {
+    "http://localhost:11434/v1".to_string()
+}
+
+/// Settings for AI smart actions, which are off until enabled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Provider used by actions without their own choice
+    #[serde(default)]
+    pub provider: AiProvider,
+    /// Per-action provider overrides
+    #[serde(default)]
+    pub action_providers: HashMap<SmartAction, AiProvider>,
+    #[serde(default)]
+    pub azure: AzureOpenAiSettings,
+    #[serde(default)]
+    pub openai_compatible: OpenAiCompatibleSettings,
+    /// How long to wait for the server before giving up
+    #[serde(default = "default_ai_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Language for the translate action
+    #[serde(default = "default_target_language")]
+    pub target_language: String,
+}
+
+impl AiSettings {
+    /// Provider configured for an action
+    pub fn provider_for(&self, action: SmartAction) -> AiProvider {
+        self.action_providers
+            .get(&action)
+            .copied()
+            .unwrap_or(self.provider)
+    }
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        AiSettings {
+            enabled: false,
+            provider: AiProvider::default(),
+            action_providers: HashMap::new(),
+            azure: AzureOpenAiSettings::default(),
+            openai_compatible: OpenAiCompatibleSettings::default(),
+            timeout_secs: default_ai_timeout_secs(),
+            target_language: default_target_language(),
+        }
+    }
+}
+
+fn default_ai_timeout_secs() -> u64 {
+    120
+}
+
+fn default_target_language() -> String {
+    "English".to_string()
+}
+
+/// Run a smart action on a history item or image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartActionRequest {
+    /// Echoed in the streamed events
+    pub request_id: String,
+    pub action: SmartAction,
+    #[serde(default)]
+    pub item_id: Option<String>,
+    /// Data URL or file path, used when no item is given
+    #[serde(default)]
+    pub image_data: Option<String>,
+    /// Overrides the configured target language
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Overrides the provider configured for the action
+    #[serde(default)]
+    pub provider: Option<AiProvider>,
+}
+
+/// Streamed output of a smart action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartActionEvent {
+    pub request_id: String,
+    /// Text received since the last event
+    pub delta: String,
+    pub done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Extra validation a secret rule's match must pass
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretCheck {
+    /// Card number checksum
+    Luhn,
+    /// Four octets of 0-255
+    Ipv4,
+}
+
+/// A pattern that flags likely secrets or personal data
+///
+/// If the pattern has a group named `secret`, only that part is flagged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretRule {
+    pub name: String,
+    pub pattern: String,
+    /// Minimum Shannon entropy, in bits per character
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_entropy: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<SecretCheck>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+/// Settings for secret and PII detection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretScanSettings {
+    /// Checked in order; earlier rules win when matches overlap
+    #[serde(default = "default_secret_rules")]
+    pub rules: Vec<SecretRule>,
+    /// Pixels added around each flagged region
+    #[serde(default = "default_secret_padding")]
+    pub padding: u32,
+    /// How "safe share" exports obscure flagged regions
+    #[serde(default = "default_safe_share_method")]
+    pub method: RedactionMethod,
+}
+
+impl Default for SecretScanSettings {
+    fn default() -> Self {
+        SecretScanSettings {
+            rules: default_secret_rules(),
+            padding: default_secret_padding(),
+            method: default_safe_share_method(),
+        }
+    }
+}
+
+fn default_secret_padding() -> u32 {
+    4
+}
+
+fn default_safe_share_method() -> RedactionMethod {
+    RedactionMethod::Fill {
+        color: default_redaction_color(),
+    }
+}
+
+fn default_secret_rules() -> Vec<SecretRule> {
+    let rule = |name: &str, pattern: &str, min_entropy: Option<f64>, check: Option<SecretCheck>| {
+        SecretRule {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            min_entropy,
+            check,
+            enabled: true,
+        }
+    };
+
+    vec![
+        rule("aws-access-key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b", None, None),
+        rule(
+            "jwt",
+            r"\beyJ[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}",
+            None,
+            None,
+        ),
+        rule(
+            "api-key",
+            r"\b(?:(?:sk|pk|rk)_(?:live|test)_[A-Za-z0-9]{16,}|sk-[A-Za-z0-9_-]{20,}|gh[pousr]_[A-Za-z0-9]{36}|xox[abprs]-[A-Za-z0-9-]{10,}|AIza[0-9A-Za-z_-]{35})",
+            None,
+            None,
+        ),
+        rule(
+            "credential",
+            r"(?i)\b(?:password|passwd|pwd|secret|token|api[_-]?key)\s*[:=]\s*(?P<secret>\S{6,})",
+            None,
+            None,
+        ),
+        rule(
+            "high-entropy-token",
+            r"[A-Za-z0-9+/=_-]{32,}",
+            Some(4.2),
+            None,
+        ),
+        rule(
+            "email",
+            r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b",
+            None,
+            None,
+        ),
+        rule(
+            "ip-address",
+            r"\b(?:\d{1,3}\.){3}\d{1,3}\b",
+            None,
+            Some(SecretCheck::Ipv4),
+        ),
+        rule(
+            "credit-card",
+            r"\b(?:\d[ -]?){12,18}\d\b",
+            None,
+            Some(SecretCheck::Luhn),
+        ),
+    ]
+}
+
+/// Text that looks like a secret, and where to redact it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretFinding {
+    pub rule: String,
+    /// Masked version of the matched text
+    pub preview: String,
+    pub bounds: RegionBounds,
+    /// Lowest OCR confidence among the matched words
+    pub confidence: f32,
 }
 
 /// Result of a capture operation
@@ -97,6 +721,9 @@ pub struct ShortcutConfig {
     pub full_screen: String,
     pub region: String,
     pub window: String,
+    /// Capture the screen and decode the QR codes on it
+    #[serde(default = "default_scan_code_shortcut")]
+    pub scan_code: String,
 }
 
 impl Default for ShortcutConfig {
@@ -105,10 +732,15 @@ impl Default for ShortcutConfig {
             full_screen: "CommandOrControl+Shift+1".to_string(),
             region: "CommandOrControl+Shift+2".to_string(),
             window: "CommandOrControl+Shift+3".to_string(),
+            scan_code: default_scan_code_shortcut(),
         }
     }
 }
 
+fn default_scan_code_shortcut() -> String {
+    "CommandOrControl+Shift+Q".to_string()
+}
+
 /// User preferences for capture behavior
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -125,6 +757,45 @@ pub struct CapturePreferences {
     pub hide_editor_during_capture: bool,
     #[serde(default = "default_true")]
     pub show_notifications: bool,
+    /// Look for QR codes and barcodes in new captures and offer their text
+    ///
+    /// Off by default: the scan runs on every capture.
+    #[serde(default)]
+    pub scan_codes_after_capture: bool,
+    #[serde(default)]
+    pub optimize: OptimizeSettings,
+    /// Don't embed capture metadata in saved or exported images
+    #[serde(default)]
+    pub strip_metadata: bool,
+    #[serde(default = "default_export_presets")]
+    pub export_presets: Vec<ExportPreset>,
+    /// Preset to export every new capture with, into `exports/`
+    #[serde(default)]
+    pub auto_export_preset: Option<String>,
+    #[serde(default = "default_beautify_styles")]
+    pub beautify_styles: Vec<BeautifyStyle>,
+    /// Style to frame new captures with; the framed copy goes to the
+    /// clipboard and `exports/`, the original is kept as is
+    #[serde(default)]
+    pub auto_beautify_style: Option<String>,
+    /// Crop uniform margins off new captures before they are saved
+    #[serde(default)]
+    pub auto_trim: Option<TrimOptions>,
+    /// Don't save a capture with exactly the same pixels as the last one
+    #[serde(default)]
+    pub skip_identical_captures: bool,
+    /// Most captures to keep in the library, or no limit; only the library
+    /// entries of older captures are dropped, never the files
+    #[serde(default)]
+    pub history_limit: Option<u32>,
+    #[serde(default)]
+    pub watermark: WatermarkSettings,
+    #[serde(default)]
+    pub secret_scan: SecretScanSettings,
+    #[serde(default)]
+    pub ocr: OcrSettings,
+    #[serde(default)]
+    pub ai: AiSettings,
 }
 
 impl Default for CapturePreferences {
@@ -139,19 +810,1259 @@ impl Default for CapturePreferences {
             open_editor_after_capture: false,
             hide_editor_during_capture: false,
             show_notifications: true,
+            scan_codes_after_capture: false,
+            optimize: OptimizeSettings::default(),
+            strip_metadata: false,
+            export_presets: default_export_presets(),
+            auto_export_preset: None,
+            beautify_styles: default_beautify_styles(),
+            auto_beautify_style: None,
+            auto_trim: None,
+            skip_identical_captures: false,
+            history_limit: None,
+            watermark: WatermarkSettings::default(),
+            secret_scan: SecretScanSettings::default(),
+            ocr: OcrSettings::default(),
+            ai: AiSettings::default(),
         }
     }
 }
 
-/// History item for tracking recent captures
+/// Settings for the background PNG optimization pass
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct HistoryItem {
-    pub id: String,
-    pub file_path: String,
-    pub timestamp: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub thumbnail: Option<String>,
+pub struct OptimizeSettings {
+    pub enabled: bool,
+    /// Re-encode as an indexed palette when the capture has at most 256
+    /// colours; lossless, so busier captures keep full colour
+    pub use_palette: bool,
+}
+
+impl Default for OptimizeSettings {
+    fn default() -> Self {
+        OptimizeSettings {
+            enabled: true,
+            use_palette: true,
+        }
+    }
+}
+
+/// RGBA colour, serialized as a `#rrggbb` or `#rrggbbaa` hex string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub [u8; 4]);
+
+impl Color {
+    pub const WHITE: Color = Color([255, 255, 255, 255]);
+    pub const TRANSPARENT: Color = Color([0, 0, 0, 0]);
+
+    /// Parse `#rgb`, `#rrggbb` or `#rrggbbaa` (the `#` is optional)
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim().trim_start_matches('#');
+        if !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        match hex.len() {
+            3 => {
+                let mut rgba = [255; 4];
+                for (i, c) in hex.chars().enumerate() {
+                    let v = c.to_digit(16)? as u8;
+                    rgba[i] = v * 17;
+                }
+                Some(Color(rgba))
+            }
+            6 => Some(Color([channel(0)?, channel(2)?, channel(4)?, 255])),
+            8 => Some(Color([channel(0)?, channel(2)?, channel(4)?, channel(6)?])),
+            _ => None,
+        }
+    }
+
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.0;
+        if a == 255 {
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Color::from_hex(&hex)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid colour: {}", hex)))
+    }
+}
+
+/// Image format for exports
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ExportFormat {
+    /// Parse a format name or file extension
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(ExportFormat::Png),
+            "jpeg" | "jpg" => Some(ExportFormat::Jpeg),
+            "webp" => Some(ExportFormat::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::Webp => "webp",
+        }
+    }
+
+    pub fn image_format(&self) -> image::ImageFormat {
+        match self {
+            ExportFormat::Png => image::ImageFormat::Png,
+            ExportFormat::Jpeg => image::ImageFormat::Jpeg,
+            ExportFormat::Webp => image::ImageFormat::WebP,
+        }
+    }
+}
+
+/// Resampling filter used when resizing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResampleFilter {
+    Nearest,
+    Bilinear,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+/// How an exact-size resize handles a different aspect ratio
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum FitMode {
+    /// Scale to fit inside the size (output may be smaller on one axis)
+    #[default]
+    Fit,
+    /// Scale to cover the size and crop the overflow
+    Fill,
+    /// Scale to fit and pad to the exact size
+    Pad,
+}
+
+/// Resize applied to an export
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case", rename_all_fields = "camelCase")]
+pub enum ResizeOptions {
+    /// Shrink to fit within the bounds; never enlarges
+    MaxSize {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_width: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_height: Option<u32>,
+    },
+    /// Scale by a percentage
+    Scale { percent: f32 },
+    /// Resize to an exact size
+    Exact {
+        width: u32,
+        height: u32,
+        #[serde(default)]
+        fit: FitMode,
+    },
+    /// Downscale HiDPI captures to logical (1x) pixels
+    Logical,
+}
+
+/// Options for exporting an image
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportOptions {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// JPEG quality (1-100)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resize: Option<ResizeOptions>,
+    #[serde(default)]
+    pub filter: ResampleFilter,
+    /// Resolution written to pHYs (PNG) or EXIF/JFIF (JPEG, WebP)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dpi: Option<u32>,
+    /// Fill for padding, and for transparency in formats without alpha
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<Color>,
+    /// Stamped onto the image before it is framed and resized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<Watermark>,
+    /// Frame the image before it is resized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub beautify: Option<BeautifyStyle>,
+    /// Crop uniform margins first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim: Option<TrimOptions>,
+}
+
+/// How uniform margins are trimmed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrimOptions {
+    /// Largest difference in any channel still counted as the same colour
+    #[serde(default = "default_trim_tolerance")]
+    pub tolerance: u8,
+    /// Most that may come off each side, in percent of the width or height
+    #[serde(default = "default_trim_max_percent")]
+    pub max_percent: f32,
+    /// Margin to keep around the content, in logical pixels
+    #[serde(default)]
+    pub padding: u32,
+}
+
+impl Default for TrimOptions {
+    fn default() -> Self {
+        TrimOptions {
+            tolerance: default_trim_tolerance(),
+            max_percent: default_trim_max_percent(),
+            padding: 0,
+        }
+    }
+}
+
+fn default_trim_tolerance() -> u8 {
+    8
+}
+
+fn default_trim_max_percent() -> f32 {
+    25.0
+}
+
+/// Named set of export options
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPreset {
+    pub name: String,
+    #[serde(flatten)]
+    pub options: ExportOptions,
+}
+
+fn default_export_presets() -> Vec<ExportPreset> {
+    vec![
+        ExportPreset {
+            name: "Docs 1200px".to_string(),
+            options: ExportOptions {
+                resize: Some(ResizeOptions::MaxSize {
+                    max_width: Some(1200),
+                    max_height: None,
+                }),
+                ..Default::default()
+            },
+        },
+        ExportPreset {
+            name: "Slack 2x".to_string(),
+            options: ExportOptions {
+                resize: Some(ResizeOptions::MaxSize {
+                    max_width: Some(1600),
+                    max_height: None,
+                }),
+                dpi: Some(144),
+                ..Default::default()
+            },
+        },
+        ExportPreset {
+            name: "Retina→1x".to_string(),
+            options: ExportOptions {
+                resize: Some(ResizeOptions::Logical),
+                dpi: Some(72),
+                ..Default::default()
+            },
+        },
+    ]
+}
+
+/// Text or logo stamped onto exported images
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Watermark {
+    pub content: WatermarkContent,
+    #[serde(default)]
+    pub position: WatermarkPosition,
+    /// 0 to 1
+    #[serde(default = "default_watermark_opacity")]
+    pub opacity: f32,
+    /// Repeat across the whole image instead of placing once
+    #[serde(default)]
+    pub tile: bool,
+    /// Multiplies the natural size of the text or logo
+    #[serde(default = "default_watermark_scale")]
+    pub scale: f32,
+    /// Degrees, clockwise
+    #[serde(default)]
+    pub rotation: f32,
+    /// Distance from the edges, or between tiles, in logical pixels
+    #[serde(default = "default_watermark_margin")]
+    pub margin: u32,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Watermark {
+            content: WatermarkContent::Text {
+                text: "INTERNAL".to_string(),
+                color: Color::WHITE,
+                font_size: default_watermark_font_size(),
+                bold: true,
+                background: Some(Color([0, 0, 0, 160])),
+            },
+            position: WatermarkPosition::default(),
+            opacity: default_watermark_opacity(),
+            tile: false,
+            scale: default_watermark_scale(),
+            rotation: 0.0,
+            margin: default_watermark_margin(),
+        }
+    }
+}
+
+fn default_watermark_opacity() -> f32 {
+    0.6
+}
+
+fn default_watermark_scale() -> f32 {
+    1.0
+}
+
+fn default_watermark_margin() -> u32 {
+    16
+}
+
+fn default_watermark_font_size() -> f32 {
+    24.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case", rename_all_fields = "camelCase")]
+pub enum WatermarkContent {
+    /// Supports the naming template tokens, e.g. `{user} {date}`; sizes
+    /// are in logical pixels
+    Text {
+        text: String,
+        #[serde(default = "default_watermark_color")]
+        color: Color,
+        #[serde(default = "default_watermark_font_size")]
+        font_size: f32,
+        #[serde(default)]
+        bold: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        background: Option<Color>,
+    },
+    /// Image file drawn at its own pixel size
+    Image { path: String },
+}
+
+fn default_watermark_color() -> Color {
+    Color::WHITE
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatermarkPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+/// When to apply the watermark from preferences
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatermarkSettings {
+    /// Stamp exports that don't set a watermark of their own
+    #[serde(default)]
+    pub on_export: bool,
+    /// Stamp captures as they are saved, so every copy carries it
+    #[serde(default)]
+    pub on_save: bool,
+    #[serde(default)]
+    pub watermark: Watermark,
+}
+
+/// Named look for framing a capture: padding, background, rounded
+/// corners, shadow and window chrome
+///
+/// Sizes are in logical pixels and scaled by the capture's scale factor.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BeautifyStyle {
+    pub name: String,
+    #[serde(default = "default_beautify_padding")]
+    pub padding: u32,
+    #[serde(default)]
+    pub background: BeautifyBackground,
+    #[serde(default)]
+    pub corner_radius: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<ShadowStyle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_chrome: Option<WindowChrome>,
+}
+
+fn default_beautify_padding() -> u32 {
+    64
+}
+
+/// What fills the padding around a beautified capture
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum BeautifyBackground {
+    Solid {
+        color: Color,
+    },
+    /// CSS-style linear gradient; 0° runs bottom to top, 90° left to right
+    LinearGradient {
+        from: Color,
+        to: Color,
+        #[serde(default)]
+        angle: f32,
+    },
+    Transparent,
+}
+
+impl Default for BeautifyBackground {
+    fn default() -> Self {
+        BeautifyBackground::Solid {
+            color: Color::WHITE,
+        }
+    }
+}
+
+/// Drop shadow behind a beautified capture
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowStyle {
+    #[serde(default = "default_shadow_color")]
+    pub color: Color,
+    /// Blur radius
+    pub blur: f32,
+    #[serde(default)]
+    pub offset_x: f32,
+    #[serde(default)]
+    pub offset_y: f32,
+    /// 0 to 1
+    pub opacity: f32,
+}
+
+fn default_shadow_color() -> Color {
+    Color([0, 0, 0, 255])
+}
+
+/// Title bar drawn above a beautified capture
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowChrome {
+    #[serde(default)]
+    pub style: ChromeStyle,
+    #[serde(default = "default_chrome_color")]
+    pub color: Color,
+}
+
+fn default_chrome_color() -> Color {
+    Color([232, 232, 232, 255])
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChromeStyle {
+    /// Traffic-light buttons on the left
+    #[default]
+    Macos,
+    /// Minimize, maximize and close on the right
+    Windows,
+}
+
+fn default_beautify_styles() -> Vec<BeautifyStyle> {
+    let soft_shadow = ShadowStyle {
+        color: default_shadow_color(),
+        blur: 24.0,
+        offset_x: 0.0,
+        offset_y: 8.0,
+        opacity: 0.3,
+    };
+    vec![
+        BeautifyStyle {
+            name: "Docs".to_string(),
+            padding: 32,
+            background: BeautifyBackground::Solid {
+                color: Color([243, 244, 246, 255]),
+            },
+            corner_radius: 8.0,
+            shadow: Some(ShadowStyle {
+                blur: 12.0,
+                offset_y: 4.0,
+                opacity: 0.2,
+                ..soft_shadow.clone()
+            }),
+            window_chrome: None,
+        },
+        BeautifyStyle {
+            name: "Social".to_string(),
+            padding: 64,
+            background: BeautifyBackground::LinearGradient {
+                from: Color([99, 102, 241, 255]),
+                to: Color([236, 72, 153, 255]),
+                angle: 135.0,
+            },
+            corner_radius: 12.0,
+            shadow: Some(soft_shadow.clone()),
+            window_chrome: Some(WindowChrome {
+                style: ChromeStyle::Macos,
+                color: default_chrome_color(),
+            }),
+        },
+        BeautifyStyle {
+            name: "Floating".to_string(),
+            padding: 48,
+            background: BeautifyBackground::Transparent,
+            corner_radius: 10.0,
+            shadow: Some(soft_shadow),
+            window_chrome: None,
+        },
+    ]
+}
+
+/// One output of a batch export: a named preset or explicit options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTarget {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<ExportOptions>,
+}
+
+/// Request to export several history items to several targets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchExportRequest {
+    /// Chosen by the caller so the job can be cancelled; generated if absent
+    #[serde(default)]
+    pub job_id: Option<String>,
+    pub item_ids: Vec<String>,
+    pub targets: Vec<BatchTarget>,
+    pub output_dir: String,
+    /// File name template; supports {name}, {target}, {index}, {date}, {time},
+    /// {timestamp}, {mode} and {user}
+    #[serde(default = "default_batch_template")]
+    pub naming_template: String,
+    /// Annotations to draw on each item before export, keyed by item id
+    #[serde(default)]
+    pub annotations: HashMap<String, Vec<Annotation>>,
+}
+
+fn default_batch_template() -> String {
+    "{name}-{target}".to_string()
+}
+
+/// Progress event emitted as each batch output is written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchProgress {
+    pub job_id: String,
+    pub completed: usize,
+    pub total: usize,
+    pub item_id: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of one item/target pair in a batch export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItemResult {
+    pub item_id: String,
+    pub source_path: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Report returned when a batch export finishes or is cancelled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchExportReport {
+    pub job_id: String,
+    pub results: Vec<BatchItemResult>,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub cancelled: bool,
+}
+
+/// Page size for PDF exports
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PdfPageSize {
+    #[default]
+    A4,
+    Letter,
+    /// Each page is sized to its image
+    Fit,
+}
+
+/// How images are arranged on PDF pages
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum PdfLayout {
+    /// One image per page
+    #[default]
+    Single,
+    /// Several images per page, filled row by row
+    Grid { columns: u32, rows: u32 },
+}
+
+/// Caption printed under each image in a PDF
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PdfCaption {
+    #[default]
+    None,
+    FileName,
+    Timestamp,
+}
+
+/// Compression used for images embedded in a PDF
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PdfImageEncoding {
+    /// Lossless; best for UI screenshots
+    #[default]
+    Flate,
+    Jpeg,
+}
+
+/// Options for PDF exports
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfOptions {
+    #[serde(default)]
+    pub page_size: PdfPageSize,
+    #[serde(default)]
+    pub layout: PdfLayout,
+    #[serde(default)]
+    pub caption: PdfCaption,
+    #[serde(default)]
+    pub encoding: PdfImageEncoding,
+    /// JPEG quality (1-100)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+}
+
+/// Request to bundle history items into a PDF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfExportRequest {
+    pub item_ids: Vec<String>,
+    #[serde(default)]
+    pub options: PdfOptions,
+    /// Asks with a save dialog when absent
+    #[serde(default)]
+    pub output_path: Option<String>,
+}
+
+/// Request to lay several history items out in one new capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompositionRequest {
+    /// In layout order
+    pub item_ids: Vec<String>,
+    #[serde(default)]
+    pub options: CompositionOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CompositionOptions {
+    #[serde(default)]
+    pub layout: CompositionLayout,
+    /// Columns of a grid; about square when not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<u32>,
+    #[serde(default)]
+    pub sizing: CompositionSizing,
+    /// Where images smaller than their cell sit in it
+    #[serde(default)]
+    pub align: CompositionAlign,
+    /// Space between images, in logical pixels
+    #[serde(default = "default_composition_spacing")]
+    pub gutter: u32,
+    /// Space around the edges, in logical pixels
+    #[serde(default = "default_composition_spacing")]
+    pub padding: u32,
+    #[serde(default = "default_composition_background")]
+    pub background: Color,
+    /// Text under each image, in order; empty entries get no label
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// Automatic labels for images without one in `labels`
+    #[serde(default)]
+    pub caption: PdfCaption,
+}
+
+impl Default for CompositionOptions {
+    fn default() -> Self {
+        CompositionOptions {
+            layout: CompositionLayout::default(),
+            columns: None,
+            sizing: CompositionSizing::default(),
+            align: CompositionAlign::default(),
+            gutter: default_composition_spacing(),
+            padding: default_composition_spacing(),
+            background: default_composition_background(),
+            labels: Vec::new(),
+            caption: PdfCaption::default(),
+        }
+    }
+}
+
+fn default_composition_spacing() -> u32 {
+    16
+}
+
+fn default_composition_background() -> Color {
+    Color::WHITE
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompositionLayout {
+    /// Side by side, e.g. before/after
+    #[default]
+    Horizontal,
+    Vertical,
+    Grid,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompositionSizing {
+    /// Scale down to the smallest height (horizontal and grid) or width
+    /// (vertical) so images line up
+    #[default]
+    Match,
+    /// Keep every image at its own size
+    Original,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompositionAlign {
+    Start,
+    #[default]
+    Center,
+    End,
+}
+
+/// Request to compare two history items
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffRequest {
+    /// The reference capture; regions are in its coordinates
+    pub before_id: String,
+    pub after_id: String,
+    #[serde(default)]
+    pub options: DiffOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffOptions {
+    /// Largest difference in any channel still counted as the same
+    #[serde(default = "default_diff_tolerance")]
+    pub tolerance: u8,
+    /// Find and undo a shift between the captures, e.g. from scrolling
+    #[serde(default = "default_true")]
+    pub align: bool,
+    /// Largest shift to look for, in pixels
+    #[serde(default = "default_diff_max_offset")]
+    pub max_offset: u32,
+    #[serde(default)]
+    pub output: DiffOutput,
+    /// Colour of changed pixels and region outlines
+    #[serde(default = "default_diff_color")]
+    pub color: Color,
+    /// Where a slider image switches from before to after, from 0 to 1
+    #[serde(default = "default_slider_position")]
+    pub slider_position: f32,
+    /// Areas left out of the comparison, e.g. clocks or cursors, in
+    /// before-capture pixels
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<RegionBounds>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            tolerance: default_diff_tolerance(),
+            align: true,
+            max_offset: default_diff_max_offset(),
+            output: DiffOutput::default(),
+            color: default_diff_color(),
+            slider_position: default_slider_position(),
+            ignore: Vec::new(),
+        }
+    }
+}
+
+fn default_diff_tolerance() -> u8 {
+    16
+}
+
+fn default_diff_max_offset() -> u32 {
+    64
+}
+
+fn default_diff_color() -> Color {
+    Color([255, 0, 80, 255])
+}
+
+fn default_slider_position() -> f32 {
+    0.5
+}
+
+/// Image produced by a diff
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffOutput {
+    /// The after capture faded, with changes painted over it
+    #[default]
+    Highlight,
+    /// Both captures next to each other with changed regions outlined
+    SideBySide,
+    /// Before on the left of a divider, after on the right
+    Slider,
+}
+
+/// Outcome of comparing two captures
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffResult {
+    /// Where the after capture sits over the before capture once aligned
+    pub offset_x: i32,
+    pub offset_y: i32,
+    /// Pixels covered by either capture
+    pub compared_pixels: u64,
+    pub changed_pixels: u64,
+    pub changed_percentage: f64,
+    /// Bounding boxes of changed areas, in before-capture pixels, top to
+    /// bottom
+    pub regions: Vec<RegionBounds>,
+    /// The diff image as a PNG data URL, when returned to the app
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// A point in image pixel coordinates
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// An annotation drawn over a capture, in image pixel coordinates
+///
+/// Annotations are applied in order, so a blur listed after a shape also
+/// blurs that shape.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case", rename_all_fields = "camelCase")]
+pub enum Annotation {
+    Rectangle {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Color,
+        stroke_width: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fill: Option<Color>,
+        #[serde(default)]
+        corner_radius: f32,
+    },
+    Ellipse {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Color,
+        stroke_width: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fill: Option<Color>,
+    },
+    Arrow {
+        from: Point,
+        to: Point,
+        color: Color,
+        stroke_width: f32,
+    },
+    Line {
+        from: Point,
+        to: Point,
+        color: Color,
+        stroke_width: f32,
+        #[serde(default)]
+        dashed: bool,
+    },
+    Freehand {
+        points: Vec<Point>,
+        color: Color,
+        stroke_width: f32,
+    },
+    /// Translucent marker stroke that multiplies with the image
+    Highlighter {
+        points: Vec<Point>,
+        color: Color,
+        stroke_width: f32,
+    },
+    /// Text with its top-left corner at `x`, `y`; may contain newlines
+    Text {
+        x: f32,
+        y: f32,
+        text: String,
+        color: Color,
+        font_size: f32,
+        #[serde(default)]
+        bold: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        background: Option<Color>,
+    },
+    /// Numbered circle centered on `x`, `y`
+    Step {
+        x: f32,
+        y: f32,
+        number: u32,
+        color: Color,
+        radius: f32,
+    },
+    Blur {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+    },
+    Pixelate {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        block_size: u32,
+    },
+}
+
+/// A named, toggleable group of annotations in a project
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotationLayer {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub visible: bool,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// Annotations of types this version doesn't know, as written by a newer
+    /// one; saved back unchanged after the others
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unknown_annotations: Vec<serde_json::Value>,
+}
+
+/// Editable state of a `.grab` project
+///
+/// Annotations are in original image coordinates; the crop is applied
+/// after they are drawn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrabProject {
+    pub metadata: CaptureMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crop: Option<RegionBounds>,
+    /// Applied to the original before the layers are drawn
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redactions: Vec<Redaction>,
+    #[serde(default)]
+    pub layers: Vec<AnnotationLayer>,
+}
+
+/// A project opened for editing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenedProject {
+    pub path: String,
+    /// Original capture as a PNG data URL
+    pub image: String,
+    pub project: GrabProject,
+}
+
+/// Save an editor session as a project
+///
+/// With an existing `project_path` the original and metadata are taken from
+/// it; otherwise `source_path` names the capture to start from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveProjectRequest {
+    #[serde(default)]
+    pub project_path: Option<String>,
+    #[serde(default)]
+    pub source_path: Option<String>,
+    #[serde(default)]
+    pub crop: Option<RegionBounds>,
+    #[serde(default)]
+    pub redactions: Vec<Redaction>,
+    #[serde(default)]
+    pub layers: Vec<AnnotationLayer>,
+}
+
+/// Flatten a project and export it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectExportRequest {
+    pub project_path: String,
+    #[serde(default)]
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub options: Option<ExportOptions>,
+    /// Named beautify style, applied on top of the export options
+    #[serde(default)]
+    pub beautify_style: Option<String>,
+    /// Ask with a save dialog when not given
+    #[serde(default)]
+    pub output_path: Option<String>,
+}
+
+/// History item for tracking recent captures
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryItem {
+    pub id: String,
+    pub file_path: String,
+    pub timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<CaptureMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    /// File size before the background optimization pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_size: Option<u64>,
+    /// File size after the background optimization pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimized_size: Option<u64>,
+    /// `.grab` project holding the editable version of this capture
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_path: Option<String>,
+    /// dHash of the image, as 16 hex digits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub perceptual_hash: Option<String>,
+    /// Digest of the image's pixels, equal only for identical captures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Size of the file on disk, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Kept when the library is trimmed to the history limit
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// A tag and how many captures have it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u32,
+}
+
+/// A named set of captures
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub item_count: u32,
+}
+
+/// Order of a history query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HistorySort {
+    #[default]
+    Newest,
+    Oldest,
+    /// Biggest files first
+    Largest,
+    Smallest,
+}
+
+/// A page of history, optionally filtered
+///
+/// Filters combine: an item has to match all of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryQuery {
+    #[serde(default)]
+    pub offset: u32,
+    #[serde(default = "default_history_page_size")]
+    pub limit: u32,
+    #[serde(default)]
+    pub sort: HistorySort,
+    #[serde(default)]
+    pub mode: Option<CaptureMode>,
+    /// Exact app name
+    #[serde(default)]
+    pub app_name: Option<String>,
+    /// Text the window title contains, ignoring case
+    #[serde(default)]
+    pub window_title: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub pinned: Option<bool>,
+    /// Id of a collection the items are in
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Earliest capture time, as RFC 3339 or a `YYYY-MM-DD` date
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Latest capture time; a bare date includes the whole day
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub min_width: Option<u32>,
+    #[serde(default)]
+    pub min_height: Option<u32>,
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        HistoryQuery {
+            offset: 0,
+            limit: default_history_page_size(),
+            sort: HistorySort::default(),
+            mode: None,
+            app_name: None,
+            window_title: None,
+            tag: None,
+            pinned: None,
+            collection: None,
+            from: None,
+            to: None,
+            min_width: None,
+            min_height: None,
+        }
+    }
+}
+
+fn default_history_page_size() -> u32 {
+    50
+}
+
+/// One page of history query results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPage {
+    pub items: Vec<HistoryItem>,
+    /// Matching items across all pages
+    pub total: u64,
+}
+
+/// Full-text search of history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchRequest {
+    /// Words to find, all of them; `"quoted text"` is a phrase and a
+    /// trailing `*` matches any word starting with what precedes it
+    pub query: String,
+    /// Earliest capture time, as RFC 3339 or a `YYYY-MM-DD` date
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Latest capture time; a bare date includes the whole day
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub offset: u32,
+    #[serde(default = "default_history_page_size")]
+    pub limit: u32,
+}
+
+/// A history item matching a search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub item: HistoryItem,
+    /// Relevance; higher is a better match
+    pub score: f64,
+    /// Text around the matches, from the best matching field
+    pub snippet: Vec<SnippetPart>,
+}
+
+/// A run of snippet text, highlighted where it matched the query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool,
+}
+
+/// One page of search results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    /// Best matches first
+    pub hits: Vec<SearchHit>,
+    /// Matching items across all pages
+    pub total: u64,
+}
+
+/// History items that look the same
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// Newest first
+    pub items: Vec<HistoryItem>,
+    /// Whether all items have exactly the same pixels
+    pub exact: bool,
+    /// Most bits any two items' perceptual hashes differ by
+    pub distance: u32,
 }
 
 /// Capture error codes
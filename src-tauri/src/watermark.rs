@@ -0,0 +1,288 @@
+//! Watermarks and text stamps
+//!
+//! Rendered here rather than in the editor so exports from the app, the
+//! CLI and batch jobs are all stamped the same way. Text watermarks expand
+//! the naming template tokens using the capture's own time and mode.
+
+use crate::annotate;
+use crate::capture;
+use crate::error::{GrabError, GrabResult};
+use crate::types::{
+    Annotation, CaptureMetadata, ExportOptions, Watermark, WatermarkContent, WatermarkPosition,
+    WatermarkSettings,
+};
+use chrono::{DateTime, Utc};
+use image::{imageops, RgbaImage};
+use tiny_skia::{FilterQuality, Pixmap, PixmapPaint, Transform};
+
+/// Use the watermark from preferences for an export that doesn't set one
+pub fn use_default(options: &mut ExportOptions, settings: &WatermarkSettings) {
+    if settings.on_export && options.watermark.is_none() {
+        options.watermark = Some(settings.watermark.clone());
+    }
+}
+
+/// A text watermark styled and placed like `watermark`
+pub fn with_text(watermark: &Watermark, text: String) -> Watermark {
+    // A logo has no text style to keep
+    let style = match watermark.content {
+        WatermarkContent::Text { .. } => watermark.content.clone(),
+        WatermarkContent::Image { .. } => Watermark::default().content,
+    };
+    let content = match style {
+        WatermarkContent::Text {
+            color,
+            font_size,
+            bold,
+            background,
+            ..
+        } => WatermarkContent::Text {
+            text,
+            color,
+            font_size,
+            bold,
+            background,
+        },
+        image => image,
+    };
+    Watermark {
+        content,
+        ..watermark.clone()
+    }
+}
+
+/// Stamp a watermark onto an image
+///
+/// `metadata` supplies the time and mode for text templates; without it
+/// the current time is used. Sizes are multiplied by `scale_factor`.
+pub fn apply(
+    image: &RgbaImage,
+    watermark: &Watermark,
+    metadata: Option<&CaptureMetadata>,
+    scale_factor: f64,
+) -> GrabResult<RgbaImage> {
+    let scale = if scale_factor.is_finite() && scale_factor > 0.0 {
+        scale_factor as f32
+    } else {
+        1.0
+    };
+    let Some(stamp) = render_stamp(watermark, metadata, scale)? else {
+        return Ok(image.clone());
+    };
+
+    let mut canvas = annotate::to_pixmap(image)?;
+    let (width, height) = (stamp.width() as f32, stamp.height() as f32);
+    let (sin, cos) = watermark.rotation.to_radians().sin_cos();
+    // Space the rotated stamp takes up
+    let (box_width, box_height) = (
+        width * cos.abs() + height * sin.abs(),
+        width * sin.abs() + height * cos.abs(),
+    );
+    let margin = watermark.margin as f32 * scale;
+    let (image_width, image_height) = (image.width() as f32, image.height() as f32);
+
+    let mut centers = Vec::new();
+    if watermark.tile {
+        // Brick pattern, every other row shifted by half a step
+        let (step_x, step_y) = (box_width + margin, box_height + margin);
+        let mut row = 0;
+        while row as f32 * step_y < image_height {
+            let cy = box_height / 2.0 + row as f32 * step_y;
+            let mut cx = box_width / 2.0 - if row % 2 == 1 { step_x / 2.0 } else { 0.0 };
+            while cx - box_width / 2.0 < image_width {
+                centers.push((cx, cy));
+                cx += step_x;
+            }
+            row += 1;
+        }
+    } else {
+        use WatermarkPosition::*;
+        let cx = match watermark.position {
+            TopLeft | Left | BottomLeft => margin + box_width / 2.0,
+            Top | Center | Bottom => image_width / 2.0,
+            TopRight | Right | BottomRight => image_width - margin - box_width / 2.0,
+        };
+        let cy = match watermark.position {
+            TopLeft | Top | TopRight => margin + box_height / 2.0,
+            Left | Center | Right => image_height / 2.0,
+            BottomLeft | Bottom | BottomRight => image_height - margin - box_height / 2.0,
+        };
+        centers.push((cx, cy));
+    }
+
+    let rotated = watermark.rotation % 360.0 != 0.0;
+    let paint = PixmapPaint {
+        opacity: watermark.opacity.clamp(0.0, 1.0),
+        quality: if rotated {
+            FilterQuality::Bilinear
+        } else {
+            FilterQuality::Nearest
+        },
+        ..Default::default()
+    };
+    for (cx, cy) in centers {
+        let transform = if rotated {
+            Transform::from_translate(cx, cy)
+                .pre_rotate(watermark.rotation)
+                .pre_translate(-width / 2.0, -height / 2.0)
+        } else {
+            // Whole pixels keep unrotated text crisp
+            Transform::from_translate((cx - width / 2.0).round(), (cy - height / 2.0).round())
+        };
+        canvas.draw_pixmap(0, 0, stamp.as_ref(), &paint, transform, None);
+    }
+
+    Ok(annotate::from_pixmap(&canvas))
+}
+
+/// The text or logo to stamp, or nothing when the text is empty
+fn render_stamp(
+    watermark: &Watermark,
+    metadata: Option<&CaptureMetadata>,
+    scale: f32,
+) -> GrabResult<Option<Pixmap>> {
+    let stamp = match watermark.content {
+        WatermarkContent::Text {
+            ref text,
+            color,
+            font_size,
+            bold,
+            background,
+        } => {
+            let time = metadata
+                .and_then(|m| DateTime::parse_from_rfc3339(&m.timestamp).ok())
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(Utc::now);
+            let text = capture::expand_template(text, Some(time), metadata.map(|m| m.mode));
+            if text.trim().is_empty() {
+                return Ok(None);
+            }
+
+            let font_size = font_size * scale * watermark.scale;
+            let (width, height) = annotate::text_size(&text, font_size, bold, background.is_some());
+            let inset = if background.is_some() {
+                font_size * annotate::TEXT_PADDING
+            } else {
+                0.0
+            };
+            let blank = RgbaImage::new(width.ceil() as u32 + 1, height.ceil() as u32 + 1);
+            annotate::render(
+                &blank,
+                &[Annotation::Text {
+                    x: inset,
+                    y: inset,
+                    text,
+                    color,
+                    font_size,
+                    bold,
+                    background,
+                }],
+            )?
+        }
+        WatermarkContent::Image { ref path } => {
+            let logo = image::open(path)
+                .map_err(|e| {
+                    GrabError::ExportFailed(format!("Can't read watermark image {}: {}", path, e))
+                })?
+                .to_rgba8();
+            if watermark.scale == 1.0 {
+                logo
+            } else {
+                let width = (logo.width() as f32 * watermark.scale).round().max(1.0) as u32;
+                let height = (logo.height() as f32 * watermark.scale).round().max(1.0) as u32;
+                imageops::resize(&logo, width, height, imageops::FilterType::CatmullRom)
+            }
+        }
+    };
+
+    Ok(Some(annotate::to_pixmap(&stamp)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Color;
+    use image::Rgba;
+
+    /// Whether any pixel in a `size`-square region differs
+    fn changed(before: &RgbaImage, after: &RgbaImage, x: u32, y: u32, size: u32) -> bool {
+        let (right, bottom) = (
+            (x + size).min(before.width()),
+            (y + size).min(before.height()),
+        );
+        (y..bottom).any(|y| (x..right).any(|x| before.get_pixel(x, y) != after.get_pixel(x, y)))
+    }
+
+    #[test]
+    fn test_text_watermark_placement_and_tiling() {
+        let image = RgbaImage::from_pixel(400, 240, Rgba([0, 0, 0, 255]));
+        let mut watermark = Watermark {
+            opacity: 1.0,
+            ..Default::default()
+        };
+
+        let stamped = apply(&image, &watermark, None, 1.0).unwrap();
+        assert!(changed(&image, &stamped, 300, 180, 84));
+        assert!(!changed(&image, &stamped, 0, 0, 200));
+        // The margins are kept clear
+        assert!(!changed(&image, &stamped, 384, 180, 60));
+        assert!(!changed(&image, &stamped, 300, 224, 16));
+
+        watermark.position = WatermarkPosition::TopLeft;
+        let stamped = apply(&image, &watermark, None, 1.0).unwrap();
+        assert!(changed(&image, &stamped, 16, 16, 40));
+        assert!(!changed(&image, &stamped, 200, 120, 200));
+
+        watermark.tile = true;
+        watermark.rotation = -30.0;
+        let stamped = apply(&image, &watermark, None, 1.0).unwrap();
+        for (x, y) in [(0, 0), (200, 0), (0, 120), (200, 120)] {
+            assert!(changed(&image, &stamped, x, y, 200));
+        }
+
+        // Nothing to stamp
+        watermark.content = WatermarkContent::Text {
+            text: "{mode}".to_string(),
+            color: Color::WHITE,
+            font_size: 24.0,
+            bold: false,
+            background: None,
+        };
+        assert_eq!(apply(&image, &watermark, None, 1.0).unwrap(), image);
+    }
+
+    #[test]
+    fn test_image_watermark_opacity_and_scale() {
+        let path = std::env::temp_dir().join("grab-watermark-logo.png");
+        RgbaImage::from_pixel(10, 10, Rgba([255, 0, 0, 255]))
+            .save(&path)
+            .unwrap();
+
+        let image = RgbaImage::from_pixel(100, 100, Rgba([255, 255, 255, 255]));
+        let watermark = Watermark {
+            content: WatermarkContent::Image {
+                path: path.to_string_lossy().to_string(),
+            },
+            position: WatermarkPosition::TopLeft,
+            opacity: 0.5,
+            scale: 2.0,
+            margin: 5,
+            ..Default::default()
+        };
+        let stamped = apply(&image, &watermark, None, 1.0).unwrap();
+        let [r, g, b, _] = stamped.get_pixel(14, 14).0;
+        assert_eq!(r, 255);
+        assert!((126..=129).contains(&g) && g == b);
+        assert_eq!(stamped.get_pixel(4, 4).0, [255, 255, 255, 255]);
+        assert_eq!(stamped.get_pixel(25, 25).0, [255, 255, 255, 255]);
+
+        let missing = Watermark {
+            content: WatermarkContent::Image {
+                path: "/nonexistent/logo.png".to_string(),
+            },
+            ..watermark
+        };
+        assert!(apply(&image, &missing, None, 1.0).is_err());
+        std::fs::remove_file(path).ok();
+    }
+}
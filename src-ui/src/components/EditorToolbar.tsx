@@ -5,6 +5,8 @@ interface EditorToolbarProps {
   onSelectTool: (tool: Tool) => void;
   onCopy: () => void;
   onSave: () => void;
+  exportPresets: string[];
+  onExport: (preset: string) => void;
   onReveal: () => void;
   onDelete: () => void;
   hasImage: boolean;
@@ -15,6 +17,8 @@ export default function EditorToolbar({
   onSelectTool,
   onCopy,
   onSave,
+  exportPresets,
+  onExport,
   onReveal,
   onDelete,
   hasImage,
@@ -74,6 +78,24 @@ export default function EditorToolbar({
           </svg>
           Save
         </button>
+        {exportPresets.length > 0 && (
+          <select
+            className="btn"
+            value=""
+            disabled={!hasImage}
+            title="Export with Preset"
+            onChange={(e) => {
+              if (e.target.value) onExport(e.target.value);
+            }}
+          >
+            <option value="">Export…</option>
+            {exportPresets.map((name) => (
+              <option key={name} value={name}>
+                {name}
+              </option>
+            ))}
+          </select>
+        )}
         <button className="btn" onClick={onReveal} disabled={!hasImage} title="Reveal in Finder">
           <svg width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2">
             <path d="M22 19a2 2 0 0 1-2 2H4a2 2 0 0 1-2-2V5a2 2 0 0 1 2-2h5l2 3h9a2 2 0 0 1 2 2z" />
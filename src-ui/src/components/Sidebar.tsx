@@ -109,6 +109,9 @@ export default function Sidebar({ history, currentCapture, onSelectCapture }: Si
                       </div>
                       <div className="history-date">
                         {formatTime(item.timestamp)}
+                        {item.originalSize !== undefined && item.optimizedSize !== undefined && (
+                          <> · {formatSize(item.originalSize)} → {formatSize(item.optimizedSize)}</>
+                        )}
                       </div>
                     </div>
                   </div>
@@ -158,3 +161,9 @@ function formatTime(timestamp: string): string {
   const date = new Date(timestamp);
   return date.toLocaleTimeString([], { hour: '2-digit', minute: '2-digit' });
 }
+
+function formatSize(bytes: number): string {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(0)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
@@ -10,6 +10,7 @@ type Tool = 'crop' | 'focus' | 'blur' | null;
 export default function Editor() {
   const currentCapture = useAppStore((state) => state.currentCapture);
   const loadHistory = useAppStore((state) => state.loadHistory);
+  const exportPresets = useAppStore((state) => state.preferences?.exportPresets ?? []);
   const [currentTool, setCurrentTool] = useState<Tool>(null);
   const [imageDataUrl, setImageDataUrl] = useState<string | null>(null);
   const [isLoading, setIsLoading] = useState(false);
@@ -69,6 +70,21 @@ export default function Editor() {
     }
   };
 
+  const handleExport = async (presetName: string) => {
+    if (!currentCapture && !imageDataUrl) return;
+    const preset = exportPresets.find((p) => p.name === presetName);
+    try {
+      await invoke('export_capture', {
+        imageData: imageDataUrl?.startsWith('data:') ? imageDataUrl : currentCapture,
+        format: preset?.format ?? 'png',
+        preset: presetName,
+        sourcePath: currentCapture?.startsWith('data:') ? undefined : currentCapture,
+      });
+    } catch (error) {
+      console.error('Export failed:', error);
+    }
+  };
+
   const handleReveal = async () => {
     if (!currentCapture) return;
     try {
@@ -136,6 +152,8 @@ export default function Editor() {
         onSelectTool={setCurrentTool}
         onCopy={handleCopy}
         onSave={handleSave}
+        exportPresets={exportPresets.map((p) => p.name)}
+        onExport={handleExport}
         onReveal={handleReveal}
         onDelete={handleDelete}
         hasImage={!!imageDataUrl}
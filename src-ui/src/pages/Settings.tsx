@@ -98,6 +98,54 @@ export default function Settings() {
 
       <div className="settings-section">
         <h2>Output</h2>
+
+        <div className="settings-row">
+          <div className="settings-label">
+            <span>Optimize PNG Files</span>
+            <small>Recompress captures in the background to save disk space</small>
+          </div>
+          <div
+            className={`toggle ${preferences.optimize.enabled ? 'on' : ''}`}
+            onClick={() =>
+              updatePreferences({
+                optimize: { ...preferences.optimize, enabled: !preferences.optimize.enabled },
+              })
+            }
+          />
+        </div>
+
+        <div className="settings-row">
+          <div className="settings-label">
+            <span>Strip Metadata</span>
+            <small>Don't embed window title, app name or capture details in images</small>
+          </div>
+          <div
+            className={`toggle ${preferences.stripMetadata ? 'on' : ''}`}
+            onClick={() => handleToggle('stripMetadata')}
+          />
+        </div>
+
+        <div className="settings-row">
+          <div className="settings-label">
+            <span>Auto-Export</span>
+            <small>Also export each capture with a preset into the exports folder</small>
+          </div>
+          <select
+            className="input-field"
+            style={{ width: 'auto' }}
+            value={preferences.autoExportPreset ?? ''}
+            onChange={(e) =>
+              updatePreferences({ autoExportPreset: e.target.value || undefined })
+            }
+          >
+            <option value="">Off</option>
+            {preferences.exportPresets.map((preset) => (
+              <option key={preset.name} value={preset.name}>
+                {preset.name}
+              </option>
+            ))}
+          </select>
+        </div>
         
         <div className="settings-row" style={{ flexDirection: 'column', alignItems: 'stretch' }}>
           <div className="settings-label" style={{ marginBottom: '8px' }}>
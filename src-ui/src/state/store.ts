@@ -1,5 +1,14 @@
 import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/core';
+import type {
+  AiSettings,
+  BeautifyStyle,
+  ExportPreset,
+  OcrSettings,
+  SecretScanSettings,
+  TrimOptions,
+  WatermarkSettings,
+} from '../types';
 
 // Types
 export interface HistoryItem {
@@ -7,6 +16,19 @@ export interface HistoryItem {
   filePath: string;
   timestamp: string;
   thumbnail?: string;
+  mode?: string;
+  windowTitle?: string;
+  appName?: string;
+  originalSize?: number;
+  optimizedSize?: number;
+  projectPath?: string;
+  perceptualHash?: string;
+  contentHash?: string;
+  width?: number;
+  height?: number;
+  fileSize?: number;
+  tags?: string[];
+  pinned?: boolean;
 }
 
 export interface CapturePreferences {
@@ -19,10 +41,28 @@ export interface CapturePreferences {
     fullScreen: string;
     region: string;
     window: string;
+    scanCode: string;
   };
   openEditorAfterCapture: boolean;
   hideEditorDuringCapture: boolean;
   showNotifications: boolean;
+  scanCodesAfterCapture: boolean;
+  optimize: {
+    enabled: boolean;
+    usePalette: boolean;
+  };
+  stripMetadata: boolean;
+  exportPresets: ExportPreset[];
+  autoExportPreset?: string;
+  beautifyStyles: BeautifyStyle[];
+  autoBeautifyStyle?: string;
+  autoTrim?: TrimOptions;
+  skipIdenticalCaptures?: boolean;
+  historyLimit?: number;
+  watermark: WatermarkSettings;
+  secretScan: SecretScanSettings;
+  ocr: OcrSettings;
+  ai: AiSettings;
 }
 
 export interface CaptureSource {
@@ -6,7 +6,7 @@
 /**
  * Supported capture modes
  */
-export type CaptureMode = 'full-screen' | 'display' | 'window' | 'region';
+export type CaptureMode = 'full-screen' | 'display' | 'window' | 'region' | 'composite';
 
 /**
  * Region bounds for capture
@@ -41,6 +41,324 @@ export interface CaptureMetadata {
   timestamp: string;
   scaleFactor: number;
   fileName?: string;
+  windowTitle?: string;
+  appName?: string;
+  /** Regions destructively redacted before the file was written */
+  redactions?: Redaction[];
+  /** Area kept when uniform margins were trimmed, in the untrimmed capture's pixels */
+  trim?: RegionBounds;
+  /** User tags, also written as the XMP `dc:subject` */
+  tags?: string[];
+}
+
+/**
+ * How a redacted region is obscured
+ */
+export type RedactionMethod =
+  | { type: 'blur'; radius: number }
+  | { type: 'pixelate'; blockSize: number }
+  | { type: 'fill'; color?: string };
+
+/**
+ * A region whose pixels are replaced before encoding
+ */
+export interface Redaction {
+  bounds: RegionBounds;
+  method: RedactionMethod;
+}
+
+/**
+ * A recognized word and where it is in the image
+ */
+export interface OcrWord {
+  text: string;
+  bounds: RegionBounds;
+  /** 0-100, as reported by the engine */
+  confidence: number;
+  line: number;
+}
+
+/**
+ * A line of recognized text
+ */
+export interface OcrLine {
+  text: string;
+  bounds: RegionBounds;
+  /** Mean confidence of the line's words */
+  confidence: number;
+  words: OcrWord[];
+}
+
+/**
+ * Text recognized in an image, in reading order
+ */
+export interface OcrResult {
+  /** Lines joined with newlines */
+  text: string;
+  lines: OcrLine[];
+  engine: string;
+  languages: string[];
+}
+
+export interface OcrSettings {
+  /** Tesseract language codes, e.g. eng or deu */
+  languages: string[];
+  /** Found on PATH when unset */
+  tesseractPath?: string;
+  /** Read the text of captures in the background so search can find it */
+  indexCaptures?: boolean;
+}
+
+/**
+ * Recognize text in a history item or image file
+ */
+export interface OcrRequest {
+  itemId?: string;
+  filePath?: string;
+  /** Boxes in the result stay in image coordinates */
+  region?: RegionBounds;
+  languages?: string[];
+}
+
+/**
+ * A QR code or barcode found in a capture
+ */
+export interface DecodedCode {
+  /** e.g. qr-code, data-matrix or ean-13 */
+  format: string;
+  text: string;
+  /** Finder patterns for 2D codes, scan line ends for 1D barcodes */
+  points: Point[];
+  bounds: RegionBounds;
+}
+
+/**
+ * Decode the codes in a history item or image file
+ */
+export interface CodeScanRequest {
+  itemId?: string;
+  filePath?: string;
+  /** Positions in the result stay in image coordinates */
+  region?: RegionBounds;
+}
+
+/**
+ * Payload of the capture:codes and codes:scanned events
+ */
+export interface CapturedCodes {
+  filePath?: string;
+  codes: DecodedCode[];
+}
+
+export type ColorFormat = 'hex' | 'rgb' | 'hsl' | 'oklch';
+
+/**
+ * A colour written out in each format
+ */
+export interface ColorValue {
+  /** e.g. #3b82f6 */
+  hex: string;
+  /** e.g. rgb(59, 130, 246) */
+  rgb: string;
+  /** e.g. hsl(217, 91%, 60%) */
+  hsl: string;
+  /** e.g. oklch(62.3% 0.188 259.8) */
+  oklch: string;
+}
+
+/**
+ * Read the colour at a point on screen or in a capture
+ */
+export interface ColorPickRequest {
+  /** Global screen coordinates, or image pixels with an item or file */
+  x: number;
+  y: number;
+  itemId?: string;
+  filePath?: string;
+  /** Pixels on each side of the point in the magnifier grid (max 16) */
+  radius?: number;
+  /** Copy the colour to the clipboard in this format */
+  copy?: ColorFormat;
+}
+
+export interface ColorSample {
+  /** Pixel that was read, in image coordinates */
+  x: number;
+  y: number;
+  color: ColorValue;
+  /** Side length of the grid, 2 * radius + 1 */
+  gridSize: number;
+  /** Row-major hex colours; null outside the image */
+  grid: (string | null)[];
+}
+
+/**
+ * Find the dominant colours of a capture
+ */
+export interface PaletteRequest {
+  itemId?: string;
+  filePath?: string;
+  /** Defaults to 6, at most 16 */
+  count?: number;
+  /** Copy the palette in this format, one colour per line */
+  copy?: ColorFormat;
+}
+
+export interface PaletteColor {
+  color: ColorValue;
+  /** Share of the image's pixels, from 0 to 100 */
+  percentage: number;
+}
+
+export interface EdgeProbeRequest {
+  itemId?: string;
+  filePath?: string;
+  /** Point in image pixels */
+  x: number;
+  y: number;
+  /** Largest per-channel difference still counted as the same colour (default 16) */
+  tolerance?: number;
+  /** Overrides the scale factor recorded in the capture */
+  scaleFactor?: number;
+}
+
+export interface DistanceRequest {
+  itemId?: string;
+  filePath?: string;
+  /** Points in image pixels */
+  from: Point;
+  to: Point;
+  /** Overrides the scale factor recorded in the capture */
+  scaleFactor?: number;
+}
+
+export interface ElementMeasurement {
+  x: number;
+  y: number;
+  /** In physical (image) pixels */
+  bounds: RegionBounds;
+  scaleFactor: number;
+  logicalWidth: number;
+  logicalHeight: number;
+}
+
+export interface DistanceMeasurement {
+  from: Point;
+  to: Point;
+  dx: number;
+  dy: number;
+  distance: number;
+  scaleFactor: number;
+  logicalDx: number;
+  logicalDy: number;
+  logicalDistance: number;
+}
+
+export type Measurement =
+  | ({ type: 'element' } & ElementMeasurement)
+  | ({ type: 'distance' } & DistanceMeasurement);
+
+export type SmartAction =
+  | 'ocr'
+  | 'clean-format'
+  | 'translate'
+  | 'summarize'
+  | 'markdown-notes'
+  | 'bug-report';
+
+/** Unset fields fall back to AZURE_OPENAI_* environment variables */
+export interface AzureOpenAiSettings {
+  endpoint?: string;
+  apiKey?: string;
+  deployment?: string;
+  apiVersion?: string;
+}
+
+export type AiProvider = 'azure' | 'openai-compatible';
+
+/** Any server speaking the OpenAI chat-completions API, e.g. Ollama */
+export interface OpenAiCompatibleSettings {
+  /** Including the API prefix, e.g. http://localhost:11434/v1 */
+  baseUrl: string;
+  model: string;
+  /** Sent as a bearer token when set */
+  apiKey?: string;
+  /**
+   * Whether the model accepts images; asked from the server when unset.
+   * Text-only models get the locally recognized text instead.
+   */
+  vision?: boolean;
+}
+
+export interface AiSettings {
+  /** Nothing is sent to a provider until enabled */
+  enabled: boolean;
+  /** Provider used by actions without their own choice */
+  provider: AiProvider;
+  actionProviders: Partial<Record<SmartAction, AiProvider>>;
+  azure: AzureOpenAiSettings;
+  openaiCompatible: OpenAiCompatibleSettings;
+  timeoutSecs: number;
+  /** Language for the translate action */
+  targetLanguage: string;
+}
+
+/**
+ * Run a smart action on a history item or image
+ */
+export interface SmartActionRequest {
+  /** Echoed in the streamed events */
+  requestId: string;
+  action: SmartAction;
+  itemId?: string;
+  /** Data URL or file path, used when no item is given */
+  imageData?: string;
+  /** Overrides the configured target language */
+  language?: string;
+  /** Overrides the provider configured for the action */
+  provider?: AiProvider;
+}
+
+/**
+ * Payload of the ai:stream event
+ */
+export interface SmartActionEvent {
+  requestId: string;
+  /** Text received since the last event */
+  delta: string;
+  done: boolean;
+  error?: string;
+}
+
+/**
+ * A pattern that flags likely secrets or personal data.
+ * A named group `secret` limits the flagged part of the match.
+ */
+export interface SecretRule {
+  name: string;
+  pattern: string;
+  /** Minimum Shannon entropy, in bits per character */
+  minEntropy?: number;
+  check?: 'luhn' | 'ipv4';
+  enabled: boolean;
+}
+
+export interface SecretScanSettings {
+  rules: SecretRule[];
+  padding: number;
+  /** How "safe share" exports obscure flagged regions */
+  method: RedactionMethod;
+}
+
+/**
+ * Text that looks like a secret, and where to redact it
+ */
+export interface SecretFinding {
+  rule: string;
+  /** Masked version of the matched text */
+  preview: string;
+  bounds: RegionBounds;
+  confidence: number;
 }
 
 /**
@@ -70,6 +388,8 @@ export interface ShortcutConfig {
   fullScreen: string;
   region: string;
   window: string;
+  /** Capture the screen and copy the text of the codes on it */
+  scanCode: string;
 }
 
 /**
@@ -85,6 +405,452 @@ export interface CapturePreferences {
   openEditorAfterCapture: boolean;
   hideEditorDuringCapture: boolean;
   showNotifications: boolean;
+  /** Look for QR codes and barcodes in new captures */
+  scanCodesAfterCapture: boolean;
+  optimize: OptimizeSettings;
+  stripMetadata: boolean;
+  exportPresets: ExportPreset[];
+  autoExportPreset?: string;
+  beautifyStyles: BeautifyStyle[];
+  /** Framed copy goes to the clipboard and exports/; the original is kept */
+  autoBeautifyStyle?: string;
+  /** Crop uniform margins off new captures before they are saved */
+  autoTrim?: TrimOptions;
+  /** Don't save a capture with exactly the same pixels as the last one */
+  skipIdenticalCaptures?: boolean;
+  /** Most captures to keep in the library; unset keeps them all */
+  historyLimit?: number;
+  watermark: WatermarkSettings;
+  secretScan: SecretScanSettings;
+  ocr: OcrSettings;
+  ai: AiSettings;
+}
+
+/**
+ * Settings for the background PNG optimization pass
+ */
+export interface OptimizeSettings {
+  enabled: boolean;
+  usePalette: boolean;
+}
+
+/**
+ * Image format for exports
+ */
+export type ExportFormat = 'png' | 'jpeg' | 'webp';
+
+/**
+ * Resampling filter used when resizing
+ */
+export type ResampleFilter = 'nearest' | 'bilinear' | 'catmull-rom' | 'gaussian' | 'lanczos3';
+
+/**
+ * How an exact-size resize handles a different aspect ratio
+ */
+export type FitMode = 'fit' | 'fill' | 'pad';
+
+/**
+ * Resize applied to an export
+ */
+export type ResizeOptions =
+  | { type: 'max-size'; maxWidth?: number; maxHeight?: number }
+  | { type: 'scale'; percent: number }
+  | { type: 'exact'; width: number; height: number; fit?: FitMode }
+  | { type: 'logical' };
+
+/**
+ * Options for exporting an image
+ */
+export interface ExportOptions {
+  format: ExportFormat;
+  quality?: number;
+  resize?: ResizeOptions;
+  filter?: ResampleFilter;
+  dpi?: number;
+  /** Hex colour (#rrggbb or #rrggbbaa) */
+  background?: string;
+  /** Stamped onto the image before it is framed and resized */
+  watermark?: Watermark;
+  /** Frame the image before it is resized */
+  beautify?: BeautifyStyle;
+  /** Crop uniform margins first */
+  trim?: TrimOptions;
+}
+
+/**
+ * How uniform margins are trimmed
+ */
+export interface TrimOptions {
+  /** Largest channel difference still counted as the same colour (default 8) */
+  tolerance?: number;
+  /** Most that may come off each side, in percent (default 25) */
+  maxPercent?: number;
+  /** Margin to keep around the content, in logical pixels */
+  padding?: number;
+}
+
+/**
+ * Text or logo stamped onto exported images
+ */
+export interface Watermark {
+  content: WatermarkContent;
+  position?: WatermarkPosition;
+  /** 0 to 1 */
+  opacity?: number;
+  /** Repeat across the whole image instead of placing once */
+  tile?: boolean;
+  /** Multiplies the natural size of the text or logo */
+  scale?: number;
+  /** Degrees, clockwise */
+  rotation?: number;
+  /** Distance from the edges, or between tiles, in logical pixels */
+  margin?: number;
+}
+
+/**
+ * Text supports the naming template tokens ({date}, {time}, {timestamp},
+ * {mode}, {user}); colours are hex strings
+ */
+export type WatermarkContent =
+  | {
+      type: 'text';
+      text: string;
+      color?: string;
+      fontSize?: number;
+      bold?: boolean;
+      background?: string;
+    }
+  | { type: 'image'; path: string };
+
+export type WatermarkPosition =
+  | 'top-left'
+  | 'top'
+  | 'top-right'
+  | 'left'
+  | 'center'
+  | 'right'
+  | 'bottom-left'
+  | 'bottom'
+  | 'bottom-right';
+
+export interface WatermarkSettings {
+  /** Stamp exports that don't set a watermark of their own */
+  onExport: boolean;
+  /** Stamp captures as they are saved, so every copy carries it */
+  onSave: boolean;
+  watermark: Watermark;
+}
+
+/**
+ * Named look for framing a capture. Sizes are in logical pixels and
+ * scaled by the capture's scale factor.
+ */
+export interface BeautifyStyle {
+  name: string;
+  padding?: number;
+  background?: BeautifyBackground;
+  cornerRadius?: number;
+  shadow?: ShadowStyle;
+  windowChrome?: WindowChrome;
+}
+
+/** Colours are hex strings; gradient angles follow CSS (90 = left to right) */
+export type BeautifyBackground =
+  | { type: 'solid'; color: string }
+  | { type: 'linear-gradient'; from: string; to: string; angle?: number }
+  | { type: 'transparent' };
+
+export interface ShadowStyle {
+  color?: string;
+  blur: number;
+  offsetX?: number;
+  offsetY?: number;
+  /** 0 to 1 */
+  opacity: number;
+}
+
+export interface WindowChrome {
+  style?: 'macos' | 'windows';
+  /** Title bar colour */
+  color?: string;
+}
+
+/**
+ * Named set of export options
+ */
+export interface ExportPreset extends ExportOptions {
+  name: string;
+}
+
+/**
+ * One output of a batch export: a named preset or explicit options
+ */
+export interface BatchTarget {
+  preset?: string;
+  options?: ExportOptions;
+}
+
+/**
+ * Request to export several history items to several targets
+ */
+export interface BatchExportRequest {
+  jobId?: string;
+  itemIds: string[];
+  targets: BatchTarget[];
+  outputDir: string;
+  /** Supports {name}, {target}, {index}, {date}, {time}, {timestamp}, {mode} and {user} */
+  namingTemplate?: string;
+  /** Annotations to draw on each item before export, keyed by item id */
+  annotations?: Record<string, Annotation[]>;
+}
+
+/**
+ * Progress event emitted as each batch output is written
+ */
+export interface BatchProgress {
+  jobId: string;
+  completed: number;
+  total: number;
+  itemId: string;
+  target: string;
+  error?: string;
+}
+
+/**
+ * Outcome of one item/target pair in a batch export
+ */
+export interface BatchItemResult {
+  itemId: string;
+  sourcePath: string;
+  target: string;
+  outputPath?: string;
+  error?: string;
+}
+
+/**
+ * Report returned when a batch export finishes or is cancelled
+ */
+export interface BatchExportReport {
+  jobId: string;
+  results: BatchItemResult[];
+  succeeded: number;
+  failed: number;
+  cancelled: boolean;
+}
+
+/**
+ * Page size for PDF exports
+ */
+export type PdfPageSize = 'a4' | 'letter' | 'fit';
+
+/**
+ * How images are arranged on PDF pages
+ */
+export type PdfLayout = { type: 'single' } | { type: 'grid'; columns: number; rows: number };
+
+/**
+ * Caption printed under each image in a PDF
+ */
+export type PdfCaption = 'none' | 'file-name' | 'timestamp';
+
+/**
+ * Compression used for images embedded in a PDF
+ */
+export type PdfImageEncoding = 'flate' | 'jpeg';
+
+/**
+ * Options for PDF exports
+ */
+export interface PdfOptions {
+  pageSize?: PdfPageSize;
+  layout?: PdfLayout;
+  caption?: PdfCaption;
+  encoding?: PdfImageEncoding;
+  quality?: number;
+}
+
+/**
+ * Request to bundle history items into a PDF
+ */
+export interface PdfExportRequest {
+  itemIds: string[];
+  options?: PdfOptions;
+  outputPath?: string;
+}
+
+export type CompositionLayout = 'horizontal' | 'vertical' | 'grid';
+
+/** 'match' scales to the smallest height (or width for vertical layouts) */
+export type CompositionSizing = 'match' | 'original';
+
+export type CompositionAlign = 'start' | 'center' | 'end';
+
+export interface CompositionOptions {
+  layout?: CompositionLayout;
+  /** Columns of a grid; about square when not given */
+  columns?: number;
+  sizing?: CompositionSizing;
+  align?: CompositionAlign;
+  /** Logical pixels (default 16) */
+  gutter?: number;
+  padding?: number;
+  /** Hex colour (default white) */
+  background?: string;
+  /** Text under each image, in order; empty entries get no label */
+  labels?: string[];
+  /** Automatic labels for images without one in `labels` */
+  caption?: PdfCaption;
+}
+
+/**
+ * Request to lay history items out in one new capture
+ */
+export interface CompositionRequest {
+  /** In layout order */
+  itemIds: string[];
+  options?: CompositionOptions;
+}
+
+/** 'highlight' paints changes over a faded copy of the after capture */
+export type DiffOutput = 'highlight' | 'side-by-side' | 'slider';
+
+export interface DiffOptions {
+  /** Largest channel difference still counted as the same (default 16) */
+  tolerance?: number;
+  /** Find and undo a scroll offset (default true) */
+  align?: boolean;
+  /** Pixels (default 64) */
+  maxOffset?: number;
+  output?: DiffOutput;
+  /** Hex colour of changes and region outlines */
+  color?: string;
+  /** Where a slider image switches from before to after, 0 to 1 */
+  sliderPosition?: number;
+  /** Areas left out of the comparison, in before-capture pixels */
+  ignore?: RegionBounds[];
+}
+
+/**
+ * Request to compare two history items
+ */
+export interface DiffRequest {
+  beforeId: string;
+  afterId: string;
+  options?: DiffOptions;
+}
+
+/**
+ * Outcome of comparing two captures
+ */
+export interface DiffResult {
+  /** Where the after capture sits over the before capture once aligned */
+  offsetX: number;
+  offsetY: number;
+  comparedPixels: number;
+  changedPixels: number;
+  changedPercentage: number;
+  /** In before-capture pixels, top to bottom */
+  regions: RegionBounds[];
+  /** PNG data URL */
+  image?: string;
+}
+
+/**
+ * A point in image pixel coordinates
+ */
+export interface Point {
+  x: number;
+  y: number;
+}
+
+/**
+ * An annotation drawn over a capture, in image pixel coordinates.
+ * Colours are hex strings (#rrggbb or #rrggbbaa).
+ */
+export type Annotation =
+  | {
+      type: 'rectangle';
+      x: number;
+      y: number;
+      width: number;
+      height: number;
+      color: string;
+      strokeWidth: number;
+      fill?: string;
+      cornerRadius?: number;
+    }
+  | {
+      type: 'ellipse';
+      x: number;
+      y: number;
+      width: number;
+      height: number;
+      color: string;
+      strokeWidth: number;
+      fill?: string;
+    }
+  | { type: 'arrow'; from: Point; to: Point; color: string; strokeWidth: number }
+  | { type: 'line'; from: Point; to: Point; color: string; strokeWidth: number; dashed?: boolean }
+  | { type: 'freehand'; points: Point[]; color: string; strokeWidth: number }
+  | { type: 'highlighter'; points: Point[]; color: string; strokeWidth: number }
+  | {
+      type: 'text';
+      x: number;
+      y: number;
+      text: string;
+      color: string;
+      fontSize: number;
+      bold?: boolean;
+      background?: string;
+    }
+  | { type: 'step'; x: number; y: number; number: number; color: string; radius: number }
+  | { type: 'blur'; x: number; y: number; width: number; height: number; radius: number }
+  | { type: 'pixelate'; x: number; y: number; width: number; height: number; blockSize: number };
+
+/**
+ * A named, toggleable group of annotations in a project
+ */
+export interface AnnotationLayer {
+  id: string;
+  name: string;
+  visible: boolean;
+  annotations: Annotation[];
+  /** Annotations of types this version doesn't know; send back unchanged */
+  unknownAnnotations?: unknown[];
+}
+
+/**
+ * Editable state of a .grab project
+ */
+export interface GrabProject {
+  metadata: CaptureMetadata;
+  crop?: RegionBounds;
+  redactions?: Redaction[];
+  layers: AnnotationLayer[];
+}
+
+export interface OpenedProject {
+  path: string;
+  /** Original capture as a PNG data URL */
+  image: string;
+  project: GrabProject;
+}
+
+export interface SaveProjectRequest {
+  projectPath?: string;
+  sourcePath?: string;
+  crop?: RegionBounds;
+  redactions?: Redaction[];
+  layers: AnnotationLayer[];
+}
+
+export interface ProjectExportRequest {
+  projectPath: string;
+  preset?: string;
+  options?: ExportOptions;
+  /** Named beautify style, applied on top of the export options */
+  beautifyStyle?: string;
+  outputPath?: string;
 }
 
 /**
@@ -95,6 +861,130 @@ export interface HistoryItem {
   filePath: string;
   timestamp: string;
   thumbnail?: string;
+  mode?: CaptureMode;
+  windowTitle?: string;
+  appName?: string;
+  originalSize?: number;
+  optimizedSize?: number;
+  projectPath?: string;
+  /** dHash, as 16 hex digits */
+  perceptualHash?: string;
+  /** Equal only for captures with identical pixels */
+  contentHash?: string;
+  width?: number;
+  height?: number;
+  /** Size of the file on disk, in bytes */
+  fileSize?: number;
+  tags?: string[];
+  /** Kept when history is trimmed to its limit */
+  pinned?: boolean;
+}
+
+/** A tag and how many captures have it */
+export interface TagCount {
+  tag: string;
+  count: number;
+}
+
+/**
+ * A named set of captures; a capture can be in several
+ */
+export interface Collection {
+  id: string;
+  name: string;
+  createdAt: string;
+  itemCount: number;
+}
+
+/** Order of a history query */
+export type HistorySort = 'newest' | 'oldest' | 'largest' | 'smallest';
+
+/**
+ * A page of history, optionally filtered; filters combine
+ */
+export interface HistoryQuery {
+  offset?: number;
+  /** Defaults to 50 */
+  limit?: number;
+  sort?: HistorySort;
+  mode?: CaptureMode;
+  /** Exact app name */
+  appName?: string;
+  /** Text the window title contains, ignoring case */
+  windowTitle?: string;
+  tag?: string;
+  /** Earliest capture time, as RFC 3339 or a `YYYY-MM-DD` date */
+  from?: string;
+  /** Latest capture time; a bare date includes the whole day */
+  to?: string;
+  minWidth?: number;
+  minHeight?: number;
+  pinned?: boolean;
+  /** Collection id */
+  collection?: string;
+}
+
+/**
+ * One page of history query results
+ */
+export interface HistoryPage {
+  items: HistoryItem[];
+  /** Matching items across all pages */
+  total: number;
+}
+
+/**
+ * Full-text search of history
+ */
+export interface SearchRequest {
+  /** All words must match; "quoted text" is a phrase, a trailing * a prefix */
+  query: string;
+  /** Earliest capture time, as RFC 3339 or a `YYYY-MM-DD` date */
+  from?: string;
+  /** Latest capture time; a bare date includes the whole day */
+  to?: string;
+  offset?: number;
+  /** Defaults to 50 */
+  limit?: number;
+}
+
+/** A run of snippet text, highlighted where it matched the query */
+export interface SnippetPart {
+  text: string;
+  highlight: boolean;
+}
+
+/**
+ * A history item matching a search
+ */
+export interface SearchHit {
+  item: HistoryItem;
+  /** Relevance; higher is a better match */
+  score: number;
+  /** Text around the matches, from the best matching field */
+  snippet: SnippetPart[];
+}
+
+/**
+ * One page of search results
+ */
+export interface SearchResults {
+  /** Best matches first */
+  hits: SearchHit[];
+  /** Matching items across all pages */
+  total: number;
+}
+
+/**
+ * History items that look the same
+ */
+export interface DuplicateGroup {
+  /** Newest first */
+  items: HistoryItem[];
+  /** Whether all items have exactly the same pixels */
+  exact: boolean;
+  /** Most bits any two items' perceptual hashes differ by */
+  distance: number;
 }
 
 /**